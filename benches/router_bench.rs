@@ -0,0 +1,71 @@
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::Request;
+use criterion::{Criterion, criterion_group, criterion_main};
+use mock_openai::config::Config;
+use mock_openai::profile::Latency;
+use std::hint::black_box;
+use tower::ServiceExt;
+
+fn router() -> Router {
+    let config = Config {
+        inter_token_latency: Latency::Fixed(0),
+        ..Config::default()
+    };
+    mock_openai::build_router(config).expect("default config is valid")
+}
+
+fn chat_request(stream: bool, max_tokens: usize) -> Request<Body> {
+    let body = format!(
+        r#"{{"model":"bench","stream":{},"max_tokens":{},"messages":[{{"role":"user","content":"Hello"}}]}}"#,
+        stream, max_tokens
+    );
+    Request::post("/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// One request through the whole router, reading the response to the end
+async fn call(router: Router, request: Request<Body>) -> usize {
+    let response = router.oneshot(request).await.unwrap();
+    to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap()
+        .len()
+}
+
+fn bench_chat_completion(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let router = router();
+    c.bench_function("router_chat_completion", |b| {
+        b.iter(|| runtime.block_on(call(router.clone(), black_box(chat_request(false, 100)))))
+    });
+}
+
+fn bench_chat_completion_stream(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let router = router();
+    c.bench_function("router_chat_completion_stream", |b| {
+        b.iter(|| runtime.block_on(call(router.clone(), black_box(chat_request(true, 100)))))
+    });
+}
+
+fn bench_models(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let router = router();
+    c.bench_function("router_models", |b| {
+        b.iter(|| {
+            let request = Request::get("/v1/models").body(Body::empty()).unwrap();
+            runtime.block_on(call(router.clone(), black_box(request)))
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_chat_completion,
+    bench_chat_completion_stream,
+    bench_models
+);
+criterion_main!(benches);
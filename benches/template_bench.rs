@@ -1,39 +1,57 @@
 use criterion::{Criterion, criterion_group, criterion_main};
-use mock_openai::template;
+use mock_openai::compat::Compat;
+use mock_openai::template::{FinishReason, Values};
 use std::hint::black_box;
 
 fn bench_render_sse_chunk(c: &mut Criterion) {
+    let templates = Compat::Vllm.templates();
     c.bench_function("render_sse_chunk", |b| {
-        b.iter(|| template::render_sse_chunk(black_box("Hello world")))
+        b.iter(|| templates.chunk(black_box("Hello world"), None, None))
     });
 }
 
 fn bench_render_sse_finish(c: &mut Criterion) {
+    let templates = Compat::Vllm.templates();
     c.bench_function("render_sse_finish", |b| {
-        b.iter(|| template::render_sse_finish(black_box("")))
+        b.iter(|| templates.finish_chunk(black_box(None), None, None, FinishReason::Length))
     });
 }
 
 fn bench_render_sse_usage(c: &mut Criterion) {
+    let templates = Compat::Vllm.templates();
     c.bench_function("render_sse_usage", |b| {
-        b.iter(|| template::render_sse_usage(black_box(100), black_box(100)))
+        b.iter(|| {
+            templates.usage_chunk(black_box(20), black_box(100), black_box(120), black_box(0))
+        })
     });
 }
 
 fn bench_render_chat_completion(c: &mut Criterion) {
+    let templates = Compat::Vllm.templates();
     let content = "FROM fairest creatures we desire increase,";
     c.bench_function("render_chat_completion", |b| {
         b.iter(|| {
-            template::render_chat_completion(black_box(content), black_box(100), black_box(100))
+            templates.completion(&Values {
+                content: black_box(content),
+                completion_tokens: black_box(100),
+                total_tokens: black_box(100),
+                ..Values::default()
+            })
         })
     });
 }
 
 fn bench_render_chat_completion_large(c: &mut Criterion) {
+    let templates = Compat::Vllm.templates();
     let content = "FROM fairest creatures we desire increase,".repeat(100);
     c.bench_function("render_chat_completion_large", |b| {
         b.iter(|| {
-            template::render_chat_completion(black_box(&content), black_box(2048), black_box(2048))
+            templates.completion(&Values {
+                content: black_box(&content),
+                completion_tokens: black_box(2048),
+                total_tokens: black_box(2048),
+                ..Values::default()
+            })
         })
     });
 }
@@ -20,6 +20,18 @@ fn main() {
         .map(|s| serde_json::to_string(s).expect("Failed to escape token string"))
         .collect();
 
+    // Tokens that end a sentence, for natural stops with `ignore_eos: false`
+    let sentence_ends: Vec<usize> = tokens
+        .get_ids()
+        .iter()
+        .enumerate()
+        .filter(|(_, id)| {
+            let text = tokenizer.decode(&[**id], false).unwrap_or_default();
+            text.trim_end().ends_with(['.', '!', '?'])
+        })
+        .map(|(i, _)| i)
+        .collect();
+
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("generated.rs");
     let mut f = BufWriter::new(File::create(&dest_path).unwrap());
@@ -30,6 +42,13 @@ fn main() {
         writeln!(f, "    {:?},", token).unwrap();
     }
     writeln!(f, "];").unwrap();
+    writeln!(f, "pub static TOKEN_IDS: &[u32] = &{:?};", tokens.get_ids()).unwrap();
+    writeln!(
+        f,
+        "pub static SENTENCE_ENDS: &[usize] = &{:?};",
+        sentence_ends
+    )
+    .unwrap();
     writeln!(f, "pub static MAX_OUTPUT: &str = {:?};", raw_string).unwrap();
     writeln!(
         f,
@@ -0,0 +1,171 @@
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::auth;
+use crate::config::Config;
+use crate::error::{ApiError, ApiJson};
+use crate::health::HealthStatus;
+use crate::journal::ReceivedRequest;
+use crate::request_log::RequestRecord;
+use crate::routes::AppState;
+
+#[derive(Serialize)]
+struct List<T> {
+    object: &'static str,
+    data: Vec<T>,
+}
+
+/// Routes mounted under `/admin` for test harnesses to inspect and steer the server
+pub fn admin_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/config",
+            get(get_config).put(replace_config).patch(patch_config),
+        )
+        .route("/health", get(get_health).put(set_health))
+        .route("/requests", get(list_requests).delete(clear_requests))
+        .route(
+            "/cancellations",
+            get(list_cancellations).delete(clear_cancellations),
+        )
+        .route_layer(middleware::from_fn_with_state(state, auth::require_admin))
+}
+
+async fn get_config(State(state): State<AppState>) -> Json<Config> {
+    Json(state.config.load().config.clone())
+}
+
+/// Replace the whole config, omitted fields take their defaults except
+/// `keys`, which stay as they are unless the body lists them
+async fn replace_config(State(state): State<AppState>, ApiJson(body): ApiJson<Value>) -> Response {
+    apply(&state, |current| {
+        let keep_keys = !body
+            .as_object()
+            .is_some_and(|body| body.contains_key("keys"));
+        let mut config = parse_config(body)?;
+        if keep_keys {
+            config.keys = current.keys.clone();
+        }
+        keep_secrets(&mut config, current);
+        Ok(config)
+    })
+}
+
+/// Update part of the config with a JSON merge patch (RFC 7386), so
+/// `{"inter_token_latency": 50}` changes only the latency
+async fn patch_config(State(state): State<AppState>, ApiJson(patch): ApiJson<Value>) -> Response {
+    apply(&state, |current| {
+        let keep_keys = patch
+            .as_object()
+            .is_some_and(|patch| !patch.contains_key("keys"));
+        let mut merged = serde_json::to_value(current).expect("config serializes");
+        merge_patch(&mut merged, patch);
+        let mut config = parse_config(merged)?;
+        if keep_keys {
+            config.keys = current.keys.clone();
+        }
+        keep_secrets(&mut config, current);
+        Ok(config)
+    })
+}
+
+/// Secrets aren't serialized, so keys sent back the way `GET` lists them,
+/// without one, keep the secret of the current key with the same name
+fn keep_secrets(config: &mut Config, current: &Config) {
+    for key in config.keys.iter_mut().filter(|key| key.key.is_empty()) {
+        if let Some(old) = current.keys.iter().find(|old| old.name == key.name) {
+            key.key = old.key.clone();
+        }
+    }
+}
+
+fn apply(state: &AppState, change: impl FnOnce(&Config) -> Result<Config, ApiError>) -> Response {
+    match state.config.update(change) {
+        Ok(live) => {
+            log::info!("Config updated through /admin/config");
+            Json(live.config.clone()).into_response()
+        }
+        Err(err) => {
+            log::warn!("Rejected config update: {}", err.message);
+            err.into_response()
+        }
+    }
+}
+
+fn parse_config(value: Value) -> Result<Config, ApiError> {
+    serde_json::from_value(value)
+        .map_err(|err| ApiError::invalid_request(format!("Invalid config: {}", err), None, None))
+}
+
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct HealthBody {
+    status: HealthStatus,
+}
+
+async fn get_health(State(state): State<AppState>) -> Json<HealthBody> {
+    Json(HealthBody {
+        status: state.health.status(),
+    })
+}
+
+/// Set the health reported by `/health/ready`: healthy, degraded or unhealthy
+async fn set_health(
+    State(state): State<AppState>,
+    ApiJson(body): ApiJson<HealthBody>,
+) -> Json<HealthBody> {
+    log::info!("Health set to {:?}", body.status);
+    state.health.set_status(body.status);
+    Json(body)
+}
+
+/// Requests received on the API routes, oldest first
+async fn list_requests(State(state): State<AppState>) -> Json<List<ReceivedRequest>> {
+    Json(List {
+        object: "list",
+        data: state.journal.list(),
+    })
+}
+
+async fn clear_requests(State(state): State<AppState>) -> impl IntoResponse {
+    state.journal.clear();
+    StatusCode::NO_CONTENT
+}
+
+/// Streams the client disconnected from before `[DONE]`, oldest first
+async fn list_cancellations(State(state): State<AppState>) -> Json<List<RequestRecord>> {
+    Json(List {
+        object: "list",
+        data: state.cancellations.list(),
+    })
+}
+
+async fn clear_cancellations(State(state): State<AppState>) -> impl IntoResponse {
+    state.cancellations.clear();
+    StatusCode::NO_CONTENT
+}
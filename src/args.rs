@@ -1,7 +1,18 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use duration_string::DurationString;
+use std::collections::HashSet;
+use std::path::PathBuf;
 
-#[derive(Parser, Debug)]
+use crate::bench::BenchArgs;
+use crate::compat::Compat;
+use crate::config::{Config, DEFAULT_INTER_TOKEN_LATENCY, ServerConfig};
+use crate::conformance::ConformanceArgs;
+use crate::corpus::OffsetMode;
+use crate::listen::Bind;
+use crate::reasoning::{DEFAULT_REASONING_TOKENS, ReasoningField};
+
+#[derive(Parser, Debug, Clone)]
 #[command(name = "mock-openai")]
 #[command(about = "A mock OpenAI API server for testing purposes")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
@@ -38,7 +49,236 @@ pub struct Args {
     #[arg(long, env = "OPENAI_API_KEY")]
     pub token: Option<String>,
 
+    /// JSON file of API keys, each with a name, allowed models, rate limits and a token quota
+    #[arg(long, env = "MOCK_KEYS_FILE")]
+    pub keys_file: Option<PathBuf>,
+
     /// Inter-token latency in milliseconds (0 to disable streaming delay)
-    #[arg(long = "inter-token-latency", default_value = "10", env = "MOCK_ITL")]
+    #[arg(long = "inter-token-latency", default_value_t = DEFAULT_INTER_TOKEN_LATENCY, env = "MOCK_ITL")]
     pub inter_token_latency: u64,
+
+    /// How each response picks its starting point in the corpus. A request `seed` always overrides this
+    #[arg(long, value_enum, default_value_t = OffsetMode::Random, env = "MOCK_OFFSET_MODE")]
+    pub offset_mode: OffsetMode,
+
+    /// Models that reason before answering (comma separated). Any model reasons when `reasoning_effort` is set
+    #[arg(long, value_delimiter = ',', env = "MOCK_REASONING_MODELS")]
+    pub reasoning_models: Vec<String>,
+
+    /// Reasoning tokens streamed before the answer at `medium` effort. `low` halves it, `high` doubles it
+    #[arg(long, default_value_t = DEFAULT_REASONING_TOKENS, env = "MOCK_REASONING_TOKENS")]
+    pub reasoning_tokens: usize,
+
+    /// Field that carries the reasoning text
+    #[arg(long, value_enum, default_value_t = ReasoningField::ReasoningContent, env = "MOCK_REASONING_FIELD")]
+    pub reasoning_field: ReasoningField,
+
+    /// Models that take text only and reject image and audio content parts (comma separated)
+    #[arg(long, value_delimiter = ',', env = "MOCK_TEXT_ONLY_MODELS")]
+    pub text_only_models: Vec<String>,
+
+    /// Validate request bodies against the OpenAI parameter constraints and reject invalid ones with a 400
+    #[arg(long, env = "MOCK_STRICT")]
+    pub strict: bool,
+
+    /// Backend whose response fields and conventions to imitate
+    #[arg(long, value_enum, default_value_t = Compat::Vllm, env = "MOCK_COMPAT")]
+    pub compat: Compat,
+
+    /// Append a JSON record of every completion request to this file
+    #[arg(long, env = "MOCK_REQUEST_LOG")]
+    pub request_log: Option<PathBuf>,
+
+    /// Milliseconds before the first token, in place of the first inter-token gap
+    #[arg(long, default_value = "0", env = "MOCK_TTFT")]
+    pub time_to_first_token: u64,
+
+    /// Models listed by /v1/models, comma separated. Other models get a 404 if set
+    #[arg(long, value_delimiter = ',', env = "MOCK_MODELS")]
+    pub models: Vec<String>,
+
+    /// Plain text file to draw responses from instead of the built-in corpus
+    #[arg(long, env = "MOCK_CORPUS")]
+    pub corpus: Option<PathBuf>,
+
+    /// Token for the /admin API. Without it /admin is only open while authentication is off
+    #[arg(long, env = "MOCK_ADMIN_TOKEN")]
+    pub admin_token: Option<String>,
+
+    /// How long in-flight streams get to finish on SIGINT or SIGTERM before they are ended
+    #[arg(
+        long,
+        default_value = "30s",
+        value_parser = clap::value_parser!(DurationString), env = "MOCK_DRAIN_TIMEOUT"
+    )]
+    pub drain_timeout: DurationString,
+
+    /// Simulated model loading time after startup, during which /health/ready and completions answer 503
+    #[arg(
+        long,
+        default_value = "0s",
+        value_parser = clap::value_parser!(DurationString), env = "MOCK_WARMUP"
+    )]
+    pub warmup: DurationString,
+
+    /// Serve on a Unix socket instead of --address and --port, unless those are given explicitly
+    #[arg(long, env = "MOCK_UNIX_SOCKET")]
+    pub unix_socket: Option<PathBuf>,
+
+    /// More addresses to serve the same API on, `host:port` or `unix:<path>`
+    #[arg(long, value_delimiter = ',', env = "MOCK_LISTEN")]
+    pub listen: Vec<Bind>,
+
+    /// File to write the ready line to once every listener is bound, with the ports picked for port 0
+    #[arg(long, env = "MOCK_READY_FILE")]
+    pub ready_file: Option<PathBuf>,
+
+    /// How many received requests /admin/requests keeps. None by default, as every body is copied to keep it
+    #[arg(long, default_value_t = 0, env = "MOCK_JOURNAL_SIZE")]
+    pub journal_size: usize,
+
+    /// PEM certificate chain to serve HTTPS with, together with --tls-key
+    #[arg(long, requires = "tls_key", env = "MOCK_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, requires = "tls_cert", env = "MOCK_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Serve HTTPS with a certificate generated at startup, signed by a throwaway CA
+    #[arg(long, conflicts_with = "tls_cert", env = "MOCK_TLS_SELF_SIGNED")]
+    pub tls_self_signed: bool,
+
+    /// Where --tls-self-signed writes the CA certificate for clients to trust
+    #[arg(long, default_value = "mock-openai-ca.pem", env = "MOCK_TLS_CA_FILE")]
+    pub tls_ca_file: PathBuf,
+
+    /// TOML, YAML or JSON file with any of these options, reloaded on change or SIGHUP.
+    /// Options given on the command line or in the environment take precedence
+    #[arg(long, env = "MOCK_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Options set on the command line, in the environment or in the config file
+    /// rather than left at their default
+    #[arg(skip)]
+    explicit: HashSet<String>,
+}
+
+/// Tools that run instead of the server
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Load test the chat completions endpoint of any OpenAI-compatible server,
+    /// reporting throughput and TTFT, ITL and end-to-end latency percentiles
+    Bench(BenchArgs),
+    /// Check the responses of any OpenAI-compatible server against the OpenAI
+    /// schema and SSE framing rules, reporting each check as passed or failed
+    Conformance(ConformanceArgs),
+}
+
+impl Args {
+    /// Parse the command line, noting which options were set explicitly
+    pub fn parse_explicit() -> Self {
+        let matches = Args::command().get_matches();
+        let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        args.explicit = matches
+            .ids()
+            .filter(|id| {
+                matches!(
+                    matches.value_source(id.as_str()),
+                    Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+                )
+            })
+            .map(|id| id.to_string())
+            .collect();
+        args
+    }
+
+    fn is_explicit(&self, id: &str) -> bool {
+        self.explicit.contains(id)
+    }
+
+    /// Take the server options from a config file's `[server]` section,
+    /// except those set explicitly
+    pub fn merge_server(&mut self, server: ServerConfig) -> Result<(), String> {
+        macro_rules! merge {
+            ($($field:ident),*) => {$(
+                if let Some(value) = server.$field
+                    && !self.is_explicit(stringify!($field))
+                {
+                    self.$field = value.into();
+                    self.explicit.insert(stringify!($field).to_string());
+                }
+            )*};
+        }
+        merge!(workers, max_connection_rate, port, address);
+        merge!(token, keys_file, request_log, admin_token, journal_size);
+        merge!(unix_socket, listen, ready_file);
+        merge!(tls_cert, tls_key, tls_self_signed, tls_ca_file);
+        if let Some(timeout) = server.client_request_timeout
+            && !self.is_explicit("client_request_timeout")
+        {
+            self.client_request_timeout = timeout
+                .parse()
+                .map_err(|e| format!("Invalid server.client_request_timeout: {}", e))?;
+        }
+        if let Some(warmup) = server.warmup
+            && !self.is_explicit("warmup")
+        {
+            self.warmup = warmup
+                .parse()
+                .map_err(|e| format!("Invalid server.warmup: {}", e))?;
+        }
+        if let Some(timeout) = server.drain_timeout
+            && !self.is_explicit("drain_timeout")
+        {
+            self.drain_timeout = timeout
+                .parse()
+                .map_err(|e| format!("Invalid server.drain_timeout: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Addresses to serve on. --address and --port are left out when
+    /// --unix-socket is given without either of them.
+    pub fn binds(&self) -> Vec<Bind> {
+        let mut binds = Vec::new();
+        if self.unix_socket.is_none() || self.is_explicit("address") || self.is_explicit("port") {
+            let address = if self.address.contains(':') {
+                format!("[{}]:{}", self.address, self.port)
+            } else {
+                format!("{}:{}", self.address, self.port)
+            };
+            binds.push(Bind::Tcp(address));
+        }
+        binds.extend(self.unix_socket.clone().map(Bind::Unix));
+        binds.extend(self.listen.iter().cloned());
+        binds
+    }
+
+    /// Apply the runtime options set explicitly on top of `config`
+    pub fn override_config(&self, config: &mut Config) {
+        macro_rules! set {
+            ($($field:ident => $($target:ident).+),* $(,)?) => {$(
+                if self.is_explicit(stringify!($field)) {
+                    config.$($target).+ = self.$field.clone().into();
+                }
+            )*};
+        }
+        set!(
+            inter_token_latency => inter_token_latency,
+            time_to_first_token => time_to_first_token,
+            offset_mode => offset_mode,
+            strict => strict,
+            compat => compat,
+            reasoning_models => reasoning.models,
+            reasoning_tokens => reasoning.tokens,
+            reasoning_field => reasoning.field,
+            text_only_models => text_only_models,
+            models => models,
+            corpus => corpus,
+        );
+    }
 }
@@ -0,0 +1,371 @@
+use axum::{
+    extract::{OriginalUri, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::headers::{
+    HeaderMapExt,
+    authorization::{Authorization, Bearer},
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use subtle::ConstantTimeEq;
+use tokio::time::{Duration, Instant};
+
+use crate::error::ApiError;
+use crate::request_log::RequestRecord;
+use crate::routes::AppState;
+
+/// Identity used when authentication is disabled
+pub const ANONYMOUS: &str = "anonymous";
+/// Identity of requests turned away for a missing or unknown key
+const UNAUTHENTICATED: &str = "unauthenticated";
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// One entry of the key file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct KeyConfig {
+    /// Never serialized, so `/admin/config` doesn't hand out the secrets.
+    /// Left out, `/admin/config` keeps the secret of the key with this name.
+    #[serde(default, skip_serializing)]
+    pub key: String,
+    /// Identity shown in logs and metrics
+    pub name: String,
+    /// Models this key may use, all of them if empty
+    #[serde(default)]
+    pub models: Vec<String>,
+    pub requests_per_minute: Option<u64>,
+    pub tokens_per_minute: Option<u64>,
+    /// Total completion tokens this key may use over the server's lifetime
+    pub token_quota: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct KeyFile {
+    keys: Vec<KeyConfig>,
+}
+
+#[derive(Debug, Clone)]
+struct KeyUsage {
+    window_start: Instant,
+    window_requests: u64,
+    window_tokens: u64,
+    total_tokens: u64,
+}
+
+/// An API key with its limits and the usage counted against them
+#[derive(Debug)]
+pub struct ApiKey {
+    pub config: KeyConfig,
+    usage: Mutex<KeyUsage>,
+}
+
+impl ApiKey {
+    pub fn new(config: KeyConfig) -> Self {
+        ApiKey {
+            config,
+            usage: Mutex::new(KeyUsage {
+                window_start: Instant::now(),
+                window_requests: 0,
+                window_tokens: 0,
+                total_tokens: 0,
+            }),
+        }
+    }
+
+    /// Key without any restrictions, used when authentication is disabled
+    pub fn anonymous() -> Self {
+        ApiKey::new(KeyConfig {
+            key: String::new(),
+            name: ANONYMOUS.to_string(),
+            models: Vec::new(),
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            token_quota: None,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// Check model access and limits before serving a request, counting it
+    /// against the request rate limit if it is let through
+    pub fn admit(&self, model: Option<&str>) -> Result<(), ApiError> {
+        if !self.config.models.is_empty() {
+            let model = model.unwrap_or_default();
+            if !self.config.models.iter().any(|m| m == model) {
+                return Err(ApiError {
+                    status: StatusCode::FORBIDDEN,
+                    message: format!(
+                        "The model `{}` does not exist or you do not have access to it.",
+                        model
+                    ),
+                    kind: "invalid_request_error",
+                    param: Some("model".into()),
+                    code: Some("model_not_found"),
+                });
+            }
+        }
+
+        let mut usage = self.usage.lock().unwrap();
+        if let Some(quota) = self.config.token_quota
+            && usage.total_tokens >= quota
+        {
+            return Err(ApiError {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                message:
+                    "You exceeded your current quota, please check your plan and billing details."
+                        .into(),
+                kind: "insufficient_quota",
+                param: None,
+                code: Some("insufficient_quota"),
+            });
+        }
+
+        let now = Instant::now();
+        if now.duration_since(usage.window_start) >= RATE_LIMIT_WINDOW {
+            usage.window_start = now;
+            usage.window_requests = 0;
+            usage.window_tokens = 0;
+        }
+        if let Some(limit) = self.config.requests_per_minute
+            && usage.window_requests >= limit
+        {
+            return Err(rate_limited(
+                &self.config.name,
+                "requests",
+                limit,
+                usage.window_requests,
+            ));
+        }
+        if let Some(limit) = self.config.tokens_per_minute
+            && usage.window_tokens >= limit
+        {
+            return Err(rate_limited(
+                &self.config.name,
+                "tokens",
+                limit,
+                usage.window_tokens,
+            ));
+        }
+        usage.window_requests += 1;
+        Ok(())
+    }
+
+    /// Count tokens served to this key
+    pub fn charge(&self, tokens: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.window_tokens += tokens;
+        usage.total_tokens += tokens;
+    }
+}
+
+fn rate_limited(name: &str, kind: &'static str, limit: u64, used: u64) -> ApiError {
+    ApiError {
+        status: StatusCode::TOO_MANY_REQUESTS,
+        message: format!(
+            "Rate limit reached for {} per min on key '{}': Limit {}, Used {}.",
+            kind, name, limit, used
+        ),
+        kind,
+        param: None,
+        code: Some("rate_limit_exceeded"),
+    }
+}
+
+/// All keys accepted by the server. Empty means authentication is disabled.
+#[derive(Debug)]
+pub struct KeyStore {
+    keys: Vec<Arc<ApiKey>>,
+    anonymous: Arc<ApiKey>,
+}
+
+impl Default for KeyStore {
+    fn default() -> Self {
+        KeyStore::new(Vec::new())
+    }
+}
+
+impl KeyStore {
+    pub fn new(keys: Vec<KeyConfig>) -> Self {
+        KeyStore {
+            keys: keys.into_iter().map(|k| Arc::new(ApiKey::new(k))).collect(),
+            anonymous: Arc::new(ApiKey::anonymous()),
+        }
+    }
+
+    /// Load keys from a JSON file of the form `{"keys": [{"key": ..., "name": ...}]}`
+    pub fn load(path: &Path) -> std::io::Result<Vec<KeyConfig>> {
+        let file = std::fs::read_to_string(path)?;
+        let parsed: KeyFile = serde_json::from_str(&file).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid key file {}: {}", path.display(), e),
+            )
+        })?;
+        Ok(parsed.keys)
+    }
+
+    /// Build a store for a new key list, carrying over the usage counted so
+    /// far for keys whose secret is unchanged, so editing a limit doesn't reset it
+    pub fn replace(&self, keys: Vec<KeyConfig>) -> Self {
+        let keys = keys
+            .into_iter()
+            .map(|config| {
+                let usage = self
+                    .keys
+                    .iter()
+                    .find(|old| old.config.key == config.key)
+                    .map(|old| old.usage.lock().unwrap().clone());
+                let key = ApiKey::new(config);
+                if let Some(usage) = usage {
+                    *key.usage.lock().unwrap() = usage;
+                }
+                Arc::new(key)
+            })
+            .collect();
+        KeyStore {
+            keys,
+            anonymous: self.anonymous.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Identity for requests when authentication is disabled
+    pub fn anonymous(&self) -> Arc<ApiKey> {
+        self.anonymous.clone()
+    }
+
+    /// Find the key matching `provided`.
+    ///
+    /// Every configured key is compared in constant time, so the time taken
+    /// doesn't reveal how much of a key matched or which key it was.
+    pub fn authenticate(&self, provided: &str) -> Option<Arc<ApiKey>> {
+        let mut found = None;
+        for key in &self.keys {
+            if bool::from(key.config.key.as_bytes().ct_eq(provided.as_bytes())) {
+                found = Some(key.clone());
+            }
+        }
+        found
+    }
+}
+
+/// The API key presented by the client, in order of preference: an OpenAI
+/// style `Authorization: Bearer` header, Azure's `api-key` or Anthropic's `x-api-key`
+fn presented_key(headers: &HeaderMap) -> Option<String> {
+    if let Some(Authorization(bearer)) = headers.typed_get::<Authorization<Bearer>>() {
+        return Some(bearer.token().to_string());
+    }
+    ["api-key", "x-api-key"]
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Middleware that authenticates every request it wraps.
+///
+/// The caller's [`ApiKey`] (anonymous when no keys are configured) is stored
+/// in the request extensions for handlers, and every response is counted
+/// against it in the metrics.
+pub async fn require_api_key(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let live = state.config.load();
+    let key = if live.keys.is_empty() {
+        Ok(live.keys.anonymous())
+    } else {
+        match presented_key(req.headers()) {
+            Some(token) => live.keys.authenticate(&token).ok_or_else(|| {
+                log::warn!("Authentication failed: invalid token");
+                crate::template::ERROR_INVALID_API_KEY
+            }),
+            None => {
+                log::warn!("Authentication failed: missing API key header");
+                Err(crate::template::ERROR_MISSING_API_KEY)
+            }
+        }
+    };
+    drop(live);
+
+    let (key_name, response) = match key {
+        Ok(key) => {
+            let name = key.config.name.clone();
+            req.extensions_mut().insert(key);
+            (name, next.run(req).await)
+        }
+        Err(error) => {
+            // Journaled here as the journal only sees authenticated requests
+            state.journal.push_rejected(&req);
+            let path = req
+                .extensions()
+                .get::<OriginalUri>()
+                .map_or(req.uri(), |OriginalUri(uri)| uri)
+                .path();
+            let record = RequestRecord::new(path, UNAUTHENTICATED);
+            state.log_request(record.complete(started, StatusCode::UNAUTHORIZED, 0, None, false));
+            let response = (
+                StatusCode::UNAUTHORIZED,
+                [(header::CONTENT_TYPE, "application/json")],
+                error,
+            )
+                .into_response();
+            (UNAUTHENTICATED.to_string(), response)
+        }
+    };
+    state.metrics.inc(
+        "mock_openai_requests_total",
+        &[("key", &key_name), ("status", response.status().as_str())],
+        1,
+    );
+    response
+}
+
+/// Middleware for `/admin`. Requires the admin token when one is configured.
+/// Without one, admin is open only when auth is off, as any tenant's key could
+/// otherwise change every other key and limit.
+pub async fn require_admin(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(admin_token) = &state.admin_token else {
+        if state.config.load().keys.is_empty() {
+            return next.run(req).await;
+        }
+        log::warn!("Refused an admin request: API keys are configured without an admin token");
+        return ApiError {
+            status: StatusCode::FORBIDDEN,
+            message: "The admin API is disabled because API keys are configured without an admin token. Start the server with --admin-token to enable it.".to_string(),
+            kind: "invalid_request_error",
+            param: None,
+            code: Some("admin_disabled"),
+        }
+        .into_response();
+    };
+    let error = match presented_key(req.headers()) {
+        Some(token) if bool::from(token.as_bytes().ct_eq(admin_token.as_bytes())) => {
+            return next.run(req).await;
+        }
+        Some(_) => crate::template::ERROR_INVALID_API_KEY,
+        None => crate::template::ERROR_MISSING_API_KEY,
+    };
+    log::warn!("Admin authentication failed");
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::CONTENT_TYPE, "application/json")],
+        error,
+    )
+        .into_response()
+}
@@ -0,0 +1,128 @@
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, Path, Query, Request},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::auth;
+use crate::journal;
+use crate::media;
+use crate::routes::{self, AppState};
+
+/// API versions the mock answers to, as published for Azure OpenAI
+pub const API_VERSIONS: &[&str] = &[
+    "2022-12-01",
+    "2023-05-15",
+    "2023-06-01-preview",
+    "2023-07-01-preview",
+    "2023-08-01-preview",
+    "2023-09-01-preview",
+    "2023-12-01-preview",
+    "2024-02-01",
+    "2024-02-15-preview",
+    "2024-03-01-preview",
+    "2024-04-01-preview",
+    "2024-05-01-preview",
+    "2024-06-01",
+    "2024-07-01-preview",
+    "2024-08-01-preview",
+    "2024-09-01-preview",
+    "2024-10-01-preview",
+    "2024-10-21",
+    "2024-12-01-preview",
+    "2025-01-01-preview",
+    "2025-02-01-preview",
+    "2025-03-01-preview",
+    "2025-04-01-preview",
+    "preview",
+    "latest",
+];
+
+/// Deployment named in an Azure URL, which stands in for the request's `model`
+#[derive(Clone, Debug)]
+pub struct Deployment(pub String);
+
+#[derive(Deserialize)]
+struct ApiVersionQuery {
+    #[serde(rename = "api-version")]
+    api_version: Option<String>,
+}
+
+/// Azure's error format, which differs from OpenAI's
+#[derive(Serialize)]
+struct AzureErrorBody<'a> {
+    error: AzureErrorDetail<'a>,
+}
+
+#[derive(Serialize)]
+struct AzureErrorDetail<'a> {
+    code: &'a str,
+    message: String,
+}
+
+fn azure_error(status: StatusCode, code: &str, message: String) -> Response {
+    let body = AzureErrorBody {
+        error: AzureErrorDetail { code, message },
+    };
+    (status, Json(body)).into_response()
+}
+
+/// Routes mounted under `/openai`, laid out like Azure OpenAI:
+/// `/openai/deployments/{deployment}/chat/completions?api-version=...`
+pub fn azure_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/deployments/{deployment}/completions",
+            post(routes::common_completions),
+        )
+        .route(
+            "/deployments/{deployment}/chat/completions",
+            post(routes::common_completions),
+        )
+        // Layers run bottom to top, so the api-version is checked after auth
+        // and the journal
+        .route_layer(middleware::from_fn(check_api_version))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            journal::record,
+        ))
+        .route_layer(middleware::from_fn_with_state(state, auth::require_api_key))
+        .layer(DefaultBodyLimit::max(media::MAX_BODY_BYTES))
+}
+
+/// Reject missing or unknown `api-version`s and record the deployment for the handler
+async fn check_api_version(
+    Path(deployment): Path<String>,
+    Query(query): Query<ApiVersionQuery>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let Some(api_version) = query.api_version else {
+        log::warn!(
+            "Azure request without api-version for deployment '{}'",
+            deployment
+        );
+        return azure_error(
+            StatusCode::NOT_FOUND,
+            "404",
+            "Resource not found".to_string(),
+        );
+    };
+    if !API_VERSIONS.contains(&api_version.as_str()) {
+        log::warn!(
+            "Azure request with unsupported api-version '{}'",
+            api_version
+        );
+        return azure_error(
+            StatusCode::BAD_REQUEST,
+            "BadRequest",
+            format!("API version not supported: {}", api_version),
+        );
+    }
+    req.extensions_mut().insert(Deployment(deployment));
+    next.run(req).await
+}
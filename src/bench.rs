@@ -0,0 +1,393 @@
+use duration_string::DurationString;
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::{Duration, Instant, MissedTickBehavior};
+
+use crate::client::{SseParser, Target};
+
+/// Options for `mock-openai bench`
+#[derive(clap::Args, Debug, Clone)]
+pub struct BenchArgs {
+    #[command(flatten)]
+    pub target: Target,
+
+    /// Model to request
+    #[arg(long, default_value = "mock-model")]
+    pub model: String,
+
+    /// Requests in flight at once
+    #[arg(short, long, default_value_t = 8)]
+    pub concurrency: usize,
+
+    /// Requests to send in total. Defaults to 100 unless --duration is given
+    #[arg(short = 'n', long)]
+    pub requests: Option<usize>,
+
+    /// Keep sending requests for this long, e.g. 30s
+    #[arg(short, long, value_parser = clap::value_parser!(DurationString))]
+    pub duration: Option<DurationString>,
+
+    /// Requests started per second. Without it, a new request starts as soon as one finishes
+    #[arg(short, long)]
+    pub rate: Option<f64>,
+
+    /// Send non-streaming requests
+    #[arg(long)]
+    pub no_stream: bool,
+
+    /// `max_tokens` of every request
+    #[arg(long, default_value_t = 128)]
+    pub max_tokens: usize,
+
+    /// User message of every request
+    #[arg(long, default_value = "Write a story about a lighthouse keeper.")]
+    pub prompt: String,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// What happened to one request
+#[derive(Default)]
+struct Outcome {
+    error: Option<String>,
+    /// Time to the first content, or to the whole response when not streaming
+    ttft: Option<Duration>,
+    /// Gaps between content chunks
+    itl: Vec<Duration>,
+    e2e: Duration,
+    output_tokens: usize,
+}
+
+/// Load test the chat completions endpoint of `args.target` and print a report
+pub async fn run(args: BenchArgs) -> std::io::Result<()> {
+    if args.concurrency == 0 {
+        return Err(invalid("--concurrency must be at least 1".to_string()));
+    }
+    if let Some(rate) = args.rate
+        && !(rate > 0.0 && rate.is_finite())
+    {
+        return Err(invalid("--rate must be a positive number".to_string()));
+    }
+    let client = args.target.client(args.concurrency).map_err(invalid)?;
+    let body = Arc::new(request_body(&args));
+    let limit = match (args.requests, args.duration) {
+        (None, None) => Some(100),
+        (requests, _) => requests,
+    };
+    let duration: Option<Duration> = args.duration.map(Into::into);
+
+    log::info!(
+        "Benchmarking {} with {} concurrent {} requests",
+        args.target.url("/chat/completions"),
+        args.concurrency,
+        if args.no_stream {
+            "non-streaming"
+        } else {
+            "streaming"
+        }
+    );
+    let semaphore = Arc::new(Semaphore::new(args.concurrency));
+    let mut ticker = args.rate.map(|rate| {
+        let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+    let started = Instant::now();
+    let deadline = duration.map(|duration| started + duration);
+    let past_deadline = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+    let mut tasks = JoinSet::new();
+    let mut outcomes = Vec::new();
+    let mut sent = 0;
+    while limit.is_none_or(|limit| sent < limit) && !past_deadline() {
+        if let Some(ticker) = &mut ticker {
+            ticker.tick().await;
+        }
+        let permit = semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("never closed");
+        if past_deadline() {
+            break;
+        }
+        sent += 1;
+        let (client, target, body) = (client.clone(), args.target.clone(), body.clone());
+        let stream = !args.no_stream;
+        tasks.spawn(async move {
+            let outcome = send(&client, &target, &body, stream).await;
+            drop(permit);
+            outcome
+        });
+        while let Some(outcome) = tasks.try_join_next() {
+            outcomes.push(outcome.map_err(std::io::Error::other)?);
+        }
+    }
+    while let Some(outcome) = tasks.join_next().await {
+        outcomes.push(outcome.map_err(std::io::Error::other)?);
+    }
+
+    let report = Report::new(&outcomes, started.elapsed());
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).map_err(std::io::Error::other)?
+        );
+    } else {
+        report.print();
+    }
+    Ok(())
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+fn request_body(args: &BenchArgs) -> Value {
+    let mut body = json!({
+        "model": args.model,
+        "messages": [{"role": "user", "content": args.prompt}],
+        "max_tokens": args.max_tokens,
+        "stream": !args.no_stream,
+    });
+    if !args.no_stream {
+        body["stream_options"] = json!({"include_usage": true});
+    }
+    body
+}
+
+async fn send(client: &reqwest::Client, target: &Target, body: &Value, stream: bool) -> Outcome {
+    let started = Instant::now();
+    let mut outcome = match target
+        .post(client, "/chat/completions")
+        .json(body)
+        .send()
+        .await
+    {
+        Ok(response) if !response.status().is_success() => Outcome {
+            error: Some(format!("HTTP {}", response.status().as_u16())),
+            ..Outcome::default()
+        },
+        Ok(response) if stream => read_stream(response, started).await,
+        Ok(response) => read_completion(response, started).await,
+        Err(e) => Outcome {
+            error: Some(request_error(&e)),
+            ..Outcome::default()
+        },
+    };
+    outcome.e2e = started.elapsed();
+    outcome
+}
+
+fn request_error(e: &reqwest::Error) -> String {
+    if e.is_timeout() {
+        "timeout".to_string()
+    } else if e.is_connect() {
+        "connection failed".to_string()
+    } else {
+        "request failed".to_string()
+    }
+}
+
+async fn read_completion(response: reqwest::Response, started: Instant) -> Outcome {
+    match response.json::<Value>().await {
+        Ok(body) => Outcome {
+            ttft: Some(started.elapsed()),
+            output_tokens: completion_tokens(&body).unwrap_or(0),
+            ..Outcome::default()
+        },
+        Err(e) => Outcome {
+            error: Some(request_error(&e)),
+            ..Outcome::default()
+        },
+    }
+}
+
+async fn read_stream(response: reqwest::Response, started: Instant) -> Outcome {
+    let mut outcome = Outcome::default();
+    let mut parser = SseParser::default();
+    let mut body = response.bytes_stream();
+    let mut last_token = None;
+    let mut chunks = 0;
+    let mut usage_tokens = None;
+    let mut done = false;
+    while let Some(bytes) = body.next().await {
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                outcome.error = Some(request_error(&e));
+                break;
+            }
+        };
+        for data in parser.push(&bytes) {
+            if data == "[DONE]" {
+                done = true;
+                continue;
+            }
+            let Ok(chunk) = serde_json::from_str::<Value>(&data) else {
+                outcome.error = Some("invalid chunk".to_string());
+                continue;
+            };
+            if let Some(error) = chunk.get("error") {
+                let code = error.get("code").and_then(Value::as_str);
+                outcome.error = Some(format!("stream error: {}", code.unwrap_or("unknown")));
+            }
+            usage_tokens = completion_tokens(&chunk).or(usage_tokens);
+            if has_content(&chunk) {
+                let now = Instant::now();
+                match last_token {
+                    None => outcome.ttft = Some(now - started),
+                    Some(last) => outcome.itl.push(now - last),
+                }
+                last_token = Some(now);
+                chunks += 1;
+            }
+        }
+    }
+    if !done && outcome.error.is_none() {
+        outcome.error = Some("stream ended without [DONE]".to_string());
+    }
+    // Servers send a token per chunk, but the usage chunk is exact when there is one
+    outcome.output_tokens = usage_tokens.unwrap_or(chunks);
+    outcome
+}
+
+fn completion_tokens(body: &Value) -> Option<usize> {
+    Some(body.get("usage")?.get("completion_tokens")?.as_u64()? as usize)
+}
+
+/// Whether a chunk carries any output, in the content or the reasoning
+fn has_content(chunk: &Value) -> bool {
+    let Some(choices) = chunk.get("choices").and_then(Value::as_array) else {
+        return false;
+    };
+    choices.iter().any(|choice| {
+        let Some(delta) = choice.get("delta") else {
+            return false;
+        };
+        ["content", "reasoning_content", "reasoning"]
+            .iter()
+            .any(|field| {
+                delta
+                    .get(field)
+                    .and_then(Value::as_str)
+                    .is_some_and(|s| !s.is_empty())
+            })
+    })
+}
+
+/// Summary of a latency in milliseconds
+#[derive(Serialize)]
+struct Percentiles {
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p95: f64,
+    p99: f64,
+    max: f64,
+}
+
+impl Percentiles {
+    fn new(durations: impl Iterator<Item = Duration>) -> Option<Self> {
+        let mut millis: Vec<f64> = durations.map(|d| d.as_secs_f64() * 1000.0).collect();
+        if millis.is_empty() {
+            return None;
+        }
+        millis.sort_by(f64::total_cmp);
+        // Nearest rank
+        let at =
+            |p: f64| millis[((p * millis.len() as f64).ceil() as usize).clamp(1, millis.len()) - 1];
+        Some(Percentiles {
+            mean: millis.iter().sum::<f64>() / millis.len() as f64,
+            p50: at(0.50),
+            p90: at(0.90),
+            p95: at(0.95),
+            p99: at(0.99),
+            max: millis[millis.len() - 1],
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    requests: usize,
+    succeeded: usize,
+    failed: usize,
+    duration_seconds: f64,
+    requests_per_second: f64,
+    output_tokens: usize,
+    output_tokens_per_second: f64,
+    ttft_ms: Option<Percentiles>,
+    itl_ms: Option<Percentiles>,
+    e2e_ms: Option<Percentiles>,
+    errors: BTreeMap<String, usize>,
+}
+
+impl Report {
+    fn new(outcomes: &[Outcome], elapsed: Duration) -> Self {
+        let ok = || outcomes.iter().filter(|o| o.error.is_none());
+        let mut errors = BTreeMap::new();
+        for error in outcomes.iter().filter_map(|o| o.error.as_ref()) {
+            *errors.entry(error.clone()).or_default() += 1;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let succeeded = ok().count();
+        let output_tokens = ok().map(|o| o.output_tokens).sum();
+        Report {
+            requests: outcomes.len(),
+            succeeded,
+            failed: outcomes.len() - succeeded,
+            duration_seconds: seconds,
+            requests_per_second: succeeded as f64 / seconds,
+            output_tokens,
+            output_tokens_per_second: output_tokens as f64 / seconds,
+            ttft_ms: Percentiles::new(ok().filter_map(|o| o.ttft)),
+            itl_ms: Percentiles::new(ok().flat_map(|o| o.itl.iter().copied())),
+            e2e_ms: Percentiles::new(ok().map(|o| o.e2e)),
+            errors,
+        }
+    }
+
+    fn print(&self) {
+        println!(
+            "Requests:      {} succeeded, {} failed in {:.2}s ({:.2} req/s)",
+            self.succeeded, self.failed, self.duration_seconds, self.requests_per_second
+        );
+        println!(
+            "Output tokens: {} ({:.1} tok/s)",
+            self.output_tokens, self.output_tokens_per_second
+        );
+        println!();
+        println!(
+            "{:<10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "", "mean", "p50", "p90", "p95", "p99", "max"
+        );
+        for (name, percentiles) in [
+            ("TTFT (ms)", &self.ttft_ms),
+            ("ITL (ms)", &self.itl_ms),
+            ("E2E (ms)", &self.e2e_ms),
+        ] {
+            match percentiles {
+                Some(p) => println!(
+                    "{:<10} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+                    name, p.mean, p.p50, p.p90, p.p95, p.p99, p.max
+                ),
+                None => println!("{:<10} {:>10}", name, "-"),
+            }
+        }
+        if !self.errors.is_empty() {
+            println!();
+            println!("Errors:");
+            for (error, count) in &self.errors {
+                println!("  {}: {}", error, count);
+            }
+        }
+    }
+}
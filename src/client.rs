@@ -0,0 +1,127 @@
+use duration_string::DurationString;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Where the `bench` and `conformance` subcommands send their requests, which
+/// can be any OpenAI-compatible server, not only this one
+#[derive(clap::Args, Debug, Clone)]
+pub struct Target {
+    /// Base URL of the API, up to and including `/v1`
+    #[arg(
+        long,
+        default_value = "http://127.0.0.1:8000/v1",
+        env = "OPENAI_BASE_URL"
+    )]
+    pub target: String,
+
+    /// API key, sent as a bearer token
+    #[arg(long, env = "OPENAI_API_KEY")]
+    pub api_key: Option<String>,
+
+    /// PEM CA certificate to trust, such as the one --tls-self-signed writes
+    #[arg(long)]
+    pub cacert: Option<PathBuf>,
+
+    /// Skip verifying the server's certificate
+    #[arg(long)]
+    pub insecure: bool,
+
+    /// Timeout for each request, including reading the whole response
+    #[arg(long, default_value = "60s", value_parser = clap::value_parser!(DurationString))]
+    pub timeout: DurationString,
+}
+
+impl Target {
+    /// `base_url` with the command line defaults for everything else, for
+    /// pointing the checks at a server from code, such as a [`crate::MockServer`]
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Target {
+            target: base_url.into(),
+            api_key: None,
+            cacert: None,
+            insecure: false,
+            timeout: Duration::from_secs(60).into(),
+        }
+    }
+
+    /// A client for this target, keeping up to `connections` idle connections open
+    pub fn client(&self, connections: usize) -> Result<reqwest::Client, String> {
+        // reqwest is built without a crypto provider of its own, so it shares
+        // the ring one the server uses for TLS
+        let _ = tokio_rustls::rustls::crypto::ring::default_provider().install_default();
+        let mut builder = reqwest::Client::builder()
+            .pool_max_idle_per_host(connections)
+            .timeout(Duration::from(self.timeout))
+            .tls_danger_accept_invalid_certs(self.insecure);
+        if let Some(path) = &self.cacert {
+            let pem = std::fs::read(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let certs = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| format!("Invalid certificate {}: {}", path.display(), e))?;
+            builder = builder.tls_certs_merge(certs);
+        }
+        builder
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))
+    }
+
+    /// URL of `path` under the base URL, e.g. `/chat/completions`
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.target.trim_end_matches('/'), path)
+    }
+
+    /// A POST to `path` with the API key, if any
+    pub fn post(&self, client: &reqwest::Client, path: &str) -> reqwest::RequestBuilder {
+        self.authorize(client.post(self.url(path)))
+    }
+
+    /// A GET of `path` with the API key, if any
+    pub fn get(&self, client: &reqwest::Client, path: &str) -> reqwest::RequestBuilder {
+        self.authorize(client.get(self.url(path)))
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+}
+
+/// Splits a response body into server-sent events as it arrives
+#[derive(Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    /// Feed the next bytes of the body, getting back the `data` of every event
+    /// they complete. Events without data, such as comments, are skipped.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        // Lines may end in CRLF, which is easier to handle by dropping the CRs
+        self.buffer.extend(bytes.iter().filter(|&&b| b != b'\r'));
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+            let event: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            if let Some(data) = event_data(&String::from_utf8_lossy(&event)) {
+                events.push(data);
+            }
+        }
+        events
+    }
+
+    /// Bytes left over after the last complete event
+    pub fn remainder(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+/// The `data` lines of one event, joined with newlines
+fn event_data(event: &str) -> Option<String> {
+    let lines: Vec<&str> = event
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
@@ -0,0 +1,196 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+use crate::template::Templates;
+
+/// Whose responses to imitate, field for field.
+///
+/// Each backend's responses are written out below as they come from the real
+/// server, with `{...}` slots for what changes between responses. See
+/// [`crate::template::Template`] for the slots.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compat {
+    /// The OpenAI API, with `service_tier` and `system_fingerprint` set and a
+    /// finish chunk of its own. Reasoning text isn't returned, only counted.
+    #[value(name = "openai")]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// vLLM's OpenAI-compatible server, with `stop_reason`, `reasoning_content`
+    /// and the token ids of `return_token_ids`
+    #[default]
+    Vllm,
+    /// SGLang, with `matched_stop` and every delta field, null or not
+    Sglang,
+    /// Hugging Face Text Generation Inference's Messages API, with the role
+    /// in every delta. Reasoning text isn't returned.
+    Tgi,
+}
+
+impl Compat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Compat::OpenAi => "openai",
+            Compat::Vllm => "vllm",
+            Compat::Sglang => "sglang",
+            Compat::Tgi => "tgi",
+        }
+    }
+
+    /// Templates for `/v1/completions`, which answers with `text_completion`
+    /// objects and has no role or reasoning
+    pub fn text_templates(self) -> &'static Templates {
+        static OPENAI: LazyLock<Templates> = LazyLock::new(|| Templates::new(&OPENAI_TEXT_SAMPLES));
+        static VLLM: LazyLock<Templates> = LazyLock::new(|| Templates::new(&VLLM_TEXT_SAMPLES));
+        static SGLANG: LazyLock<Templates> = LazyLock::new(|| Templates::new(&SGLANG_TEXT_SAMPLES));
+        static TGI: LazyLock<Templates> = LazyLock::new(|| Templates::new(&TGI_TEXT_SAMPLES));
+        match self {
+            Compat::OpenAi => &OPENAI,
+            Compat::Vllm => &VLLM,
+            Compat::Sglang => &SGLANG,
+            Compat::Tgi => &TGI,
+        }
+    }
+
+    /// This backend's chat templates, parsed on first use
+    pub fn templates(self) -> &'static Templates {
+        static OPENAI: LazyLock<Templates> = LazyLock::new(|| Templates::new(&OPENAI_SAMPLES));
+        static VLLM: LazyLock<Templates> = LazyLock::new(|| Templates::new(&VLLM_SAMPLES));
+        static SGLANG: LazyLock<Templates> = LazyLock::new(|| Templates::new(&SGLANG_SAMPLES));
+        static TGI: LazyLock<Templates> = LazyLock::new(|| Templates::new(&TGI_SAMPLES));
+        match self {
+            Compat::OpenAi => &OPENAI,
+            Compat::Vllm => &VLLM,
+            Compat::Sglang => &SGLANG,
+            Compat::Tgi => &TGI,
+        }
+    }
+}
+
+/// One backend's responses to one endpoint, with slots
+pub struct Samples {
+    /// Non-streaming completion
+    pub completion: &'static str,
+    /// Usage object, filled into the `{usage}` slot of the completion
+    pub usage: &'static str,
+    /// Usage object of the usage chunk, when it differs from `usage`
+    pub stream_usage: Option<&'static str>,
+    /// Chunk carrying only the role, sent before the first token
+    pub role_chunk: Option<&'static str>,
+    /// Chunk carrying one token
+    pub chunk: &'static str,
+    /// Chunk carrying the finish reason, and the last token if it has a
+    /// `{content}` slot. Otherwise the last token goes in a chunk of its own.
+    pub finish_chunk: &'static str,
+    /// Chunk carrying one reasoning token. Without it, reasoning takes its
+    /// time but nothing is sent until the answer.
+    pub reasoning_chunk: Option<&'static str>,
+    /// Final chunk with the usage and no choices
+    pub usage_chunk: &'static str,
+}
+
+const OPENAI_SAMPLES: Samples = Samples {
+    completion: r#"{"id":"chatcmpl-xxx","object":"chat.completion","created":1770188771,"model":"sonnet-mock-model","choices":[{"index":0,"message":{"role":"assistant","content":{content},"refusal":null,"annotations":[]},"logprobs":{logprobs},"finish_reason":{finish_reason}}],"usage":{usage},"service_tier":"default","system_fingerprint":"fp_6b68a8204b"}"#,
+    usage: r#"{"prompt_tokens":{prompt_tokens},"completion_tokens":{completion_tokens},"total_tokens":{total_tokens},"prompt_tokens_details":{"cached_tokens":0,"audio_tokens":0},"completion_tokens_details":{"reasoning_tokens":{reasoning_tokens},"audio_tokens":0,"accepted_prediction_tokens":0,"rejected_prediction_tokens":0}}"#,
+    stream_usage: None,
+    role_chunk: Some(
+        r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","service_tier":"default","system_fingerprint":"fp_6b68a8204b","choices":[{"index":0,"delta":{"role":"assistant","content":"","refusal":null},"logprobs":null,"finish_reason":null}]}"#,
+    ),
+    chunk: r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","service_tier":"default","system_fingerprint":"fp_6b68a8204b","choices":[{"index":0,"delta":{"content":{content}},"logprobs":{logprobs},"finish_reason":null}]}"#,
+    finish_chunk: r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","service_tier":"default","system_fingerprint":"fp_6b68a8204b","choices":[{"index":0,"delta":{},"logprobs":null,"finish_reason":{finish_reason}}]}"#,
+    reasoning_chunk: None,
+    usage_chunk: r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","service_tier":"default","system_fingerprint":"fp_6b68a8204b","choices":[],"usage":{usage}}"#,
+};
+
+// vLLM leaves unset fields out of chunks, but writes every field of a completion
+const VLLM_SAMPLES: Samples = Samples {
+    completion: r#"{"id":"chatcmpl-xxx","object":"chat.completion","created":1770188771,"model":"sonnet-mock-model","choices":[{"index":0,"message":{"role":"assistant","content":{content},"refusal":null,"annotations":null,"audio":null,"function_call":null,"tool_calls":[],"reasoning":{reasoning:reasoning},"reasoning_content":{reasoning:reasoning_content}},"logprobs":{logprobs},"finish_reason":{finish_reason},"stop_reason":null,"token_ids":{token_ids}}],"service_tier":null,"system_fingerprint":null,"usage":{usage},"prompt_logprobs":null,"prompt_token_ids":{prompt_token_ids},"kv_transfer_params":null}"#,
+    usage: r#"{"prompt_tokens":{prompt_tokens},"total_tokens":{total_tokens},"completion_tokens":{completion_tokens}{reasoning_details},"prompt_tokens_details":null}"#,
+    stream_usage: Some(
+        r#"{"prompt_tokens":{prompt_tokens},"total_tokens":{total_tokens},"completion_tokens":{completion_tokens}{reasoning_details}}"#,
+    ),
+    role_chunk: Some(
+        r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"delta":{"role":"assistant","content":""},"logprobs":null,"finish_reason":null}]{prompt_token_ids_field}}"#,
+    ),
+    chunk: r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"delta":{"content":{content}},"logprobs":{logprobs},"finish_reason":null,"token_ids":{token_ids}}]}"#,
+    finish_chunk: r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"delta":{"content":{content}},"logprobs":{logprobs},"finish_reason":{finish_reason},"stop_reason":null,"token_ids":{token_ids}}]}"#,
+    reasoning_chunk: Some(
+        r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"delta":{{reasoning_key}:{reasoning}},"logprobs":null,"finish_reason":null,"token_ids":{token_ids}}]}"#,
+    ),
+    usage_chunk: r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[],"usage":{usage}}"#,
+};
+
+const SGLANG_SAMPLES: Samples = Samples {
+    completion: r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"chat.completion","created":1770188771,"model":"sonnet-mock-model","choices":[{"index":0,"message":{"role":"assistant","content":{content},"reasoning_content":{reasoning:reasoning_content},"tool_calls":null},"logprobs":{logprobs},"finish_reason":{finish_reason},"matched_stop":null}],"usage":{usage}}"#,
+    usage: r#"{"prompt_tokens":{prompt_tokens},"total_tokens":{total_tokens},"completion_tokens":{completion_tokens},"prompt_tokens_details":null}"#,
+    stream_usage: None,
+    role_chunk: Some(
+        r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"delta":{"role":"assistant","content":"","reasoning_content":null,"tool_calls":null},"logprobs":null,"finish_reason":null,"matched_stop":null}],"usage":null}"#,
+    ),
+    chunk: r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"delta":{"role":null,"content":{content},"reasoning_content":null,"tool_calls":null},"logprobs":{logprobs},"finish_reason":null,"matched_stop":null}],"usage":null}"#,
+    finish_chunk: r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"delta":{"role":null,"content":null,"reasoning_content":null,"tool_calls":null},"logprobs":null,"finish_reason":{finish_reason},"matched_stop":null}],"usage":null}"#,
+    reasoning_chunk: Some(
+        r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"delta":{"role":null,"content":null,{reasoning_key}:{reasoning},"tool_calls":null},"logprobs":null,"finish_reason":null,"matched_stop":null}],"usage":null}"#,
+    ),
+    usage_chunk: r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[],"usage":{usage}}"#,
+};
+
+const TGI_SAMPLES: Samples = Samples {
+    completion: r#"{"object":"chat.completion","id":"","created":1770188771,"model":"sonnet-mock-model","system_fingerprint":"3.3.6-native","choices":[{"index":0,"message":{"role":"assistant","content":{content}},"logprobs":{logprobs},"finish_reason":{finish_reason}}],"usage":{usage}}"#,
+    usage: r#"{"prompt_tokens":{prompt_tokens},"completion_tokens":{completion_tokens},"total_tokens":{total_tokens}}"#,
+    stream_usage: None,
+    role_chunk: None,
+    chunk: r#"{"object":"chat.completion.chunk","id":"","created":1770187171,"model":"sonnet-mock-model","system_fingerprint":"3.3.6-native","choices":[{"index":0,"delta":{"role":"assistant","content":{content}},"logprobs":{logprobs},"finish_reason":null}],"usage":null}"#,
+    finish_chunk: r#"{"object":"chat.completion.chunk","id":"","created":1770187171,"model":"sonnet-mock-model","system_fingerprint":"3.3.6-native","choices":[{"index":0,"delta":{"role":"assistant","content":{content}},"logprobs":{logprobs},"finish_reason":{finish_reason}}],"usage":null}"#,
+    reasoning_chunk: None,
+    usage_chunk: r#"{"object":"chat.completion.chunk","id":"","created":1770187171,"model":"sonnet-mock-model","system_fingerprint":"3.3.6-native","choices":[],"usage":{usage}}"#,
+};
+
+// Legacy completions, whose logprobs have a shape of their own
+
+const OPENAI_TEXT_SAMPLES: Samples = Samples {
+    completion: r#"{"id":"cmpl-xxx","object":"text_completion","created":1770188771,"model":"sonnet-mock-model","choices":[{"text":{content},"index":0,"logprobs":{logprobs},"finish_reason":{finish_reason}}],"usage":{usage}}"#,
+    usage: r#"{"prompt_tokens":{prompt_tokens},"completion_tokens":{completion_tokens},"total_tokens":{total_tokens}}"#,
+    stream_usage: None,
+    role_chunk: None,
+    chunk: r#"{"id":"cmpl-xxx","object":"text_completion","created":1770187171,"choices":[{"text":{content},"index":0,"logprobs":{logprobs},"finish_reason":null}],"model":"sonnet-mock-model"}"#,
+    finish_chunk: r#"{"id":"cmpl-xxx","object":"text_completion","created":1770187171,"choices":[{"text":"","index":0,"logprobs":null,"finish_reason":{finish_reason}}],"model":"sonnet-mock-model"}"#,
+    reasoning_chunk: None,
+    usage_chunk: r#"{"id":"cmpl-xxx","object":"text_completion","created":1770187171,"choices":[],"model":"sonnet-mock-model","usage":{usage}}"#,
+};
+
+const VLLM_TEXT_SAMPLES: Samples = Samples {
+    completion: r#"{"id":"cmpl-xxx","object":"text_completion","created":1770188771,"model":"sonnet-mock-model","choices":[{"index":0,"text":{content},"logprobs":{logprobs},"finish_reason":{finish_reason},"stop_reason":null,"token_ids":{token_ids},"prompt_logprobs":null,"prompt_token_ids":{prompt_token_ids}}],"service_tier":null,"system_fingerprint":null,"usage":{usage},"kv_transfer_params":null}"#,
+    usage: r#"{"prompt_tokens":{prompt_tokens},"total_tokens":{total_tokens},"completion_tokens":{completion_tokens},"prompt_tokens_details":null}"#,
+    stream_usage: Some(
+        r#"{"prompt_tokens":{prompt_tokens},"total_tokens":{total_tokens},"completion_tokens":{completion_tokens}}"#,
+    ),
+    role_chunk: None,
+    chunk: r#"{"id":"cmpl-xxx","object":"text_completion","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"text":{content},"logprobs":{logprobs},"finish_reason":null,"stop_reason":null,"token_ids":{token_ids}}]}"#,
+    finish_chunk: r#"{"id":"cmpl-xxx","object":"text_completion","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"text":{content},"logprobs":{logprobs},"finish_reason":{finish_reason},"stop_reason":null,"token_ids":{token_ids}}]}"#,
+    reasoning_chunk: None,
+    usage_chunk: r#"{"id":"cmpl-xxx","object":"text_completion","created":1770187171,"model":"sonnet-mock-model","choices":[],"usage":{usage}}"#,
+};
+
+const SGLANG_TEXT_SAMPLES: Samples = Samples {
+    completion: r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"text_completion","created":1770188771,"model":"sonnet-mock-model","choices":[{"index":0,"text":{content},"logprobs":{logprobs},"finish_reason":{finish_reason},"matched_stop":null}],"usage":{usage}}"#,
+    usage: r#"{"prompt_tokens":{prompt_tokens},"total_tokens":{total_tokens},"completion_tokens":{completion_tokens},"prompt_tokens_details":null}"#,
+    stream_usage: None,
+    role_chunk: None,
+    chunk: r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"text_completion","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"text":{content},"logprobs":{logprobs},"finish_reason":null,"matched_stop":null}],"usage":null}"#,
+    finish_chunk: r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"text_completion","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"text":"","logprobs":null,"finish_reason":{finish_reason},"matched_stop":null}],"usage":null}"#,
+    reasoning_chunk: None,
+    usage_chunk: r#"{"id":"5f1c0a3b9e2d4c7f8a6b1d0e3f2a9c4b","object":"text_completion","created":1770187171,"model":"sonnet-mock-model","choices":[],"usage":{usage}}"#,
+};
+
+const TGI_TEXT_SAMPLES: Samples = Samples {
+    completion: r#"{"object":"text_completion","id":"","created":1770188771,"model":"sonnet-mock-model","system_fingerprint":"3.3.6-native","choices":[{"index":0,"text":{content},"logprobs":{logprobs},"finish_reason":{finish_reason}}],"usage":{usage}}"#,
+    usage: r#"{"prompt_tokens":{prompt_tokens},"completion_tokens":{completion_tokens},"total_tokens":{total_tokens}}"#,
+    stream_usage: None,
+    role_chunk: None,
+    chunk: r#"{"object":"text_completion","id":"","created":1770187171,"model":"sonnet-mock-model","system_fingerprint":"3.3.6-native","choices":[{"index":0,"text":{content},"logprobs":{logprobs},"finish_reason":null}]}"#,
+    finish_chunk: r#"{"object":"text_completion","id":"","created":1770187171,"model":"sonnet-mock-model","system_fingerprint":"3.3.6-native","choices":[{"index":0,"text":{content},"logprobs":{logprobs},"finish_reason":{finish_reason}}]}"#,
+    reasoning_chunk: None,
+    usage_chunk: r#"{"object":"text_completion","id":"","created":1770187171,"model":"sonnet-mock-model","system_fingerprint":"3.3.6-native","choices":[],"usage":{usage}}"#,
+};
@@ -0,0 +1,420 @@
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+use tokio::time::{Duration, interval};
+
+use crate::auth::{KeyConfig, KeyStore};
+use crate::compat::Compat;
+use crate::corpus::{Corpus, OffsetMode};
+use crate::error::ApiError;
+use crate::listen::Bind;
+use crate::profile::{Latency, ModelProfile, Profile};
+use crate::reasoning::ReasoningConfig;
+
+/// Milliseconds between streamed tokens unless configured otherwise
+pub const DEFAULT_INTER_TOKEN_LATENCY: u64 = 10;
+
+/// Settings that can change while the server is running
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Milliseconds between streamed tokens
+    pub inter_token_latency: Latency,
+    /// Milliseconds before the first token, in place of the first inter-token gap.
+    /// Non-streaming responses are held back by this long.
+    pub time_to_first_token: Latency,
+    pub offset_mode: OffsetMode,
+    pub strict: bool,
+    /// Backend whose response fields to imitate
+    pub compat: Compat,
+    pub reasoning: ReasoningConfig,
+    /// Models that reject image and audio content parts
+    pub text_only_models: Vec<String>,
+    pub faults: Faults,
+    /// Models served, listed by `/v1/models` along with the profiled ones.
+    /// Any model is accepted if both are empty.
+    pub models: Vec<String>,
+    /// Behaviour of individual models, keyed by the requested `model`
+    pub profiles: BTreeMap<String, Profile>,
+    /// Serve models outside the catalog with the top-level settings instead of a 404
+    pub fallback_to_default: bool,
+    /// Plain text file to draw responses from instead of the built-in corpus
+    pub corpus: Option<PathBuf>,
+    pub keys: Vec<KeyConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            inter_token_latency: Latency::Fixed(DEFAULT_INTER_TOKEN_LATENCY),
+            time_to_first_token: Latency::Fixed(0),
+            offset_mode: OffsetMode::default(),
+            strict: false,
+            compat: Compat::default(),
+            reasoning: ReasoningConfig::default(),
+            text_only_models: Vec::new(),
+            faults: Faults::default(),
+            models: Vec::new(),
+            profiles: BTreeMap::new(),
+            fallback_to_default: false,
+            corpus: None,
+            keys: Vec::new(),
+        }
+    }
+}
+
+/// Failures injected into otherwise valid requests
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Faults {
+    /// Fraction of requests answered with `error_status` instead of a completion
+    pub error_rate: f64,
+    pub error_status: u16,
+    /// Fraction of streams cut off partway through, without a finish reason or `[DONE]`
+    pub disconnect_rate: f64,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Faults {
+            error_rate: 0.0,
+            error_status: 500,
+            disconnect_rate: 0.0,
+        }
+    }
+}
+
+impl Faults {
+    /// The error to answer with, if this request was picked to fail
+    pub fn injected_error(&self) -> Option<ApiError> {
+        if self.error_rate <= 0.0 || rand::random::<f64>() >= self.error_rate {
+            return None;
+        }
+        let status = StatusCode::from_u16(self.error_status).ok()?;
+        let (message, kind, code) = match status {
+            StatusCode::TOO_MANY_REQUESTS => (
+                "Rate limit reached, please try again later.",
+                "requests",
+                Some("rate_limit_exceeded"),
+            ),
+            StatusCode::SERVICE_UNAVAILABLE => (
+                "The engine is currently overloaded, please try again later.",
+                "server_error",
+                None,
+            ),
+            status if status.is_server_error() => (
+                "The server had an error while processing your request. Sorry about that!",
+                "server_error",
+                None,
+            ),
+            _ => (
+                "Injected fault, the request was rejected.",
+                "invalid_request_error",
+                None,
+            ),
+        };
+        Some(ApiError {
+            status,
+            message: message.to_string(),
+            kind,
+            param: None,
+            code,
+        })
+    }
+
+    fn validate(&self, param: &str) -> Result<(), String> {
+        for (field, rate) in [
+            ("error_rate", self.error_rate),
+            ("disconnect_rate", self.disconnect_rate),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(format!(
+                    "{}.{} must be between 0 and 1, got {}",
+                    param, field, rate
+                ));
+            }
+        }
+        if !(400..=599).contains(&self.error_status) {
+            return Err(format!(
+                "{}.error_status must be an HTTP error status, got {}",
+                param, self.error_status
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether this stream should be cut off partway through
+    pub fn disconnects(&self) -> bool {
+        self.disconnect_rate > 0.0 && rand::random::<f64>() < self.disconnect_rate
+    }
+}
+
+/// A validated [`Config`] with the keys and model profiles built from it
+pub struct LiveConfig {
+    pub config: Config,
+    pub keys: KeyStore,
+    default_profile: ModelProfile,
+    profiles: HashMap<String, ModelProfile>,
+}
+
+impl LiveConfig {
+    /// Models listed by `/v1/models`
+    pub fn catalog(&self) -> impl Iterator<Item = &str> {
+        let config = &self.config;
+        let profiled = config
+            .profiles
+            .keys()
+            .filter(|model| !config.models.contains(model));
+        config.models.iter().chain(profiled).map(String::as_str)
+    }
+
+    /// The profile `model` is served with, or a 404 for models outside the catalog
+    pub fn profile(&self, model: Option<&str>) -> Result<&ModelProfile, ApiError> {
+        let model = model.unwrap_or_default();
+        if let Some(profile) = self.profiles.get(model) {
+            return Ok(profile);
+        }
+        let config = &self.config;
+        if config.fallback_to_default
+            || (config.models.is_empty() && config.profiles.is_empty())
+            || config.models.iter().any(|m| m == model)
+        {
+            return Ok(&self.default_profile);
+        }
+        Err(ApiError {
+            status: StatusCode::NOT_FOUND,
+            message: format!("The model `{}` does not exist.", model),
+            kind: "invalid_request_error",
+            param: Some("model".into()),
+            code: Some("model_not_found"),
+        })
+    }
+
+    /// Validate `config` and build what it describes, carrying over key usage
+    /// from `previous`
+    fn build(config: Config, previous: Option<&KeyStore>) -> Result<Self, ApiError> {
+        let invalid = |param: &str, message: String| {
+            ApiError::invalid_request(message, Some(param.to_string()), None)
+        };
+        let load_corpus = |param: &str, path: &Option<PathBuf>| match path {
+            Some(path) => Corpus::load(path).map_err(|e| {
+                invalid(
+                    param,
+                    format!("Failed to load corpus {}: {}", path.display(), e),
+                )
+            }),
+            None => Ok(Corpus::builtin()),
+        };
+
+        config
+            .faults
+            .validate("faults")
+            .map_err(|e| invalid("faults", e))?;
+        config
+            .inter_token_latency
+            .validate("inter_token_latency")
+            .and_then(|_| config.time_to_first_token.validate("time_to_first_token"))
+            .map_err(|e| invalid("inter_token_latency", e))?;
+        if let Some(key) = config.keys.iter().find(|key| key.key.is_empty()) {
+            return Err(invalid(
+                "keys",
+                format!("Key '{}' has an empty secret", key.name),
+            ));
+        }
+
+        let corpus = load_corpus("corpus", &config.corpus)?;
+        let default_profile = ModelProfile::default_for(&config, corpus);
+        let mut profiles = HashMap::new();
+        for (model, profile) in &config.profiles {
+            let param = format!("profiles.{}", model);
+            let check = |latency: Option<Latency>, field: &str| {
+                latency.map_or(Ok(()), |l| l.validate(&format!("{}.{}", param, field)))
+            };
+            check(profile.inter_token_latency, "inter_token_latency")
+                .and_then(|_| check(profile.time_to_first_token, "time_to_first_token"))
+                .and_then(|_| {
+                    profile
+                        .faults
+                        .as_ref()
+                        .map_or(Ok(()), |f| f.validate(&format!("{}.faults", param)))
+                })
+                .map_err(|e| invalid(&param, e))?;
+            if profile.chars_per_token == Some(0) {
+                return Err(invalid(
+                    &param,
+                    format!("{}.chars_per_token must be at least 1", param),
+                ));
+            }
+            let corpus = match &profile.corpus {
+                Some(_) => load_corpus(&param, &profile.corpus)?,
+                None => corpus,
+            };
+            profiles.insert(
+                model.clone(),
+                ModelProfile::resolve(&default_profile, model, profile, corpus),
+            );
+        }
+
+        let keys = match previous {
+            Some(previous) => previous.replace(config.keys.clone()),
+            None => KeyStore::new(config.keys.clone()),
+        };
+        Ok(LiveConfig {
+            config,
+            keys,
+            default_profile,
+            profiles,
+        })
+    }
+}
+
+/// The current [`LiveConfig`], swapped as a whole on every change.
+///
+/// Requests take a snapshot when they start, so an update applies atomically
+/// to the requests that come after it and never to one half-way through.
+pub struct SharedConfig {
+    current: RwLock<Arc<LiveConfig>>,
+    /// Serializes updates, so `current` is only locked to swap in the result
+    updating: Mutex<()>,
+}
+
+impl SharedConfig {
+    pub fn new(config: Config) -> Result<Self, ApiError> {
+        Ok(SharedConfig {
+            current: RwLock::new(Arc::new(LiveConfig::build(config, None)?)),
+            updating: Mutex::new(()),
+        })
+    }
+
+    pub fn load(&self) -> Arc<LiveConfig> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Replace the config with what `change` makes of the current one.
+    /// The current config stays active if the result is invalid.
+    pub fn update(
+        &self,
+        change: impl FnOnce(&Config) -> Result<Config, ApiError>,
+    ) -> Result<Arc<LiveConfig>, ApiError> {
+        let _updating = self.updating.lock().unwrap();
+        // Corpus files are read here, with requests still served the current config
+        let current = self.load();
+        let config = change(&current.config)?;
+        let live = Arc::new(LiveConfig::build(config, Some(&current.keys))?);
+        *self.current.write().unwrap() = live.clone();
+        Ok(live)
+    }
+}
+
+/// Options in the `[server]` section of a config file. These are read once at
+/// startup, changing them needs a restart.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub workers: Option<usize>,
+    pub max_connection_rate: Option<usize>,
+    pub port: Option<u16>,
+    pub address: Option<String>,
+    pub client_request_timeout: Option<String>,
+    pub drain_timeout: Option<String>,
+    pub warmup: Option<String>,
+    pub token: Option<String>,
+    pub keys_file: Option<PathBuf>,
+    pub request_log: Option<PathBuf>,
+    pub admin_token: Option<String>,
+    pub journal_size: Option<usize>,
+    pub unix_socket: Option<PathBuf>,
+    pub listen: Option<Vec<Bind>>,
+    pub ready_file: Option<PathBuf>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub tls_self_signed: Option<bool>,
+    pub tls_ca_file: Option<PathBuf>,
+}
+
+/// Read a config file, TOML, YAML or JSON depending on its extension.
+///
+/// The `[server]` section holds the startup options, everything else is the
+/// runtime [`Config`].
+pub fn read_file(path: &Path) -> Result<(ServerConfig, Config), String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let parsed: Result<Value, String> = match extension {
+        "toml" => toml::from_str(&text).map_err(|e| e.to_string()),
+        "yaml" | "yml" => serde_yaml::from_str(&text).map_err(|e| e.to_string()),
+        "json" => serde_json::from_str(&text).map_err(|e| e.to_string()),
+        _ => Err("expected a .toml, .yaml, .yml or .json file".to_string()),
+    };
+    let invalid = |e: String| format!("Invalid config file {}: {}", path.display(), e);
+    let mut value = parsed.map_err(invalid)?;
+    // An empty YAML file parses as null
+    if value.is_null() {
+        value = Value::Object(Default::default());
+    }
+    let server = match value.as_object_mut().and_then(|map| map.remove("server")) {
+        Some(server) => {
+            serde_json::from_value(server).map_err(|e| invalid(format!("server: {}", e)))?
+        }
+        None => ServerConfig::default(),
+    };
+    let config = serde_json::from_value(value).map_err(|e| invalid(e.to_string()))?;
+    Ok((server, config))
+}
+
+/// Reload the config whenever `path` changes or the process gets SIGHUP.
+///
+/// `load` builds the full config from the file, so command line overrides
+/// stay in place. Streams already running keep the config they started with,
+/// and a file that doesn't load or validate leaves the current config active.
+pub fn watch(
+    path: PathBuf,
+    shared: Arc<SharedConfig>,
+    load: impl Fn() -> Result<Config, String> + Send + 'static,
+) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut hangup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        let mut poll = interval(Duration::from_secs(1));
+        let mut modified = modified_time(&path);
+        loop {
+            #[cfg(unix)]
+            let reason = tokio::select! {
+                _ = poll.tick() => "change",
+                Some(_) = async { hangup.as_mut()?.recv().await } => "SIGHUP",
+            };
+            #[cfg(not(unix))]
+            let reason = {
+                poll.tick().await;
+                "change"
+            };
+            let current = modified_time(&path);
+            if reason == "change" && current == modified {
+                continue;
+            }
+            modified = current;
+
+            log::info!("Reloading {} on {}", path.display(), reason);
+            match load().and_then(|config| {
+                shared.update(|_| Ok(config)).map_err(|err| {
+                    format!("Invalid config file {}: {}", path.display(), err.message)
+                })
+            }) {
+                Ok(_) => log::info!("Reloaded {}", path.display()),
+                Err(e) => log::error!("{}. Keeping the previous config", e),
+            }
+        }
+    });
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,1064 @@
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::client::{SseParser, Target};
+
+/// Options for `mock-openai conformance`
+#[derive(clap::Args, Debug, Clone)]
+pub struct ConformanceArgs {
+    #[command(flatten)]
+    pub target: Target,
+
+    /// Model to request
+    #[arg(long, default_value = "mock-model")]
+    pub model: String,
+
+    /// Checks to leave out, comma separated, e.g. completions,completions-stream
+    #[arg(long, value_delimiter = ',')]
+    pub skip: Vec<String>,
+
+    /// Also fail on differences clients tolerate, such as fields outside the OpenAI schema
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Check every response of `args.target` against the OpenAI schema and print
+/// a report, returning whether it passed
+pub async fn run(args: ConformanceArgs) -> std::io::Result<bool> {
+    let invalid = |message| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+    log::info!("Checking {} against the OpenAI schema", args.target.target);
+    let report = check(&args.target, &args.model, &args.skip, args.strict)
+        .await
+        .map_err(invalid)?;
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).map_err(std::io::Error::other)?
+        );
+    } else {
+        report.print();
+    }
+    Ok(report.passed())
+}
+
+/// Run every check not named in `skip` against `target`. With `strict`,
+/// warnings fail a check too.
+pub async fn check(
+    target: &Target,
+    model: &str,
+    skip: &[String],
+    strict: bool,
+) -> Result<Report, String> {
+    if let Some(unknown) = skip
+        .iter()
+        .find(|name| !Check::ALL.iter().any(|check| check.name() == *name))
+    {
+        let names: Vec<&str> = Check::ALL.iter().map(|check| check.name()).collect();
+        return Err(format!(
+            "Unknown check {:?}, expected one of {}",
+            unknown,
+            names.join(", ")
+        ));
+    }
+    let suite = Suite {
+        client: target.client(1)?,
+        target,
+        model,
+    };
+    let mut checks = Vec::new();
+    for check in Check::ALL {
+        let findings = if skip.iter().any(|name| name == check.name()) {
+            Findings::skipped("left out with --skip")
+        } else {
+            check.run(&suite).await
+        };
+        checks.push(CheckResult::new(check, findings, strict));
+    }
+    Ok(Report {
+        target: target.target.clone(),
+        checks,
+    })
+}
+
+/// The requests the suite sends, each checked on its own
+#[derive(Clone, Copy, Debug)]
+enum Check {
+    Chat,
+    ChatStream,
+    Completions,
+    CompletionsStream,
+    Models,
+    InvalidJson,
+    InvalidType,
+    MissingMessages,
+    UnknownPath,
+    InvalidApiKey,
+}
+
+impl Check {
+    const ALL: [Check; 10] = [
+        Check::Chat,
+        Check::ChatStream,
+        Check::Completions,
+        Check::CompletionsStream,
+        Check::Models,
+        Check::InvalidJson,
+        Check::InvalidType,
+        Check::MissingMessages,
+        Check::UnknownPath,
+        Check::InvalidApiKey,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Check::Chat => "chat",
+            Check::ChatStream => "chat-stream",
+            Check::Completions => "completions",
+            Check::CompletionsStream => "completions-stream",
+            Check::Models => "models",
+            Check::InvalidJson => "invalid-json",
+            Check::InvalidType => "invalid-type",
+            Check::MissingMessages => "missing-messages",
+            Check::UnknownPath => "unknown-path",
+            Check::InvalidApiKey => "invalid-api-key",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Check::Chat => "Chat completion",
+            Check::ChatStream => "Streamed chat completion with a usage chunk",
+            Check::Completions => "Legacy completion",
+            Check::CompletionsStream => "Streamed legacy completion without usage",
+            Check::Models => "Model list",
+            Check::InvalidJson => "400 for a body that isn't JSON",
+            Check::InvalidType => "400 for a parameter of the wrong type",
+            Check::MissingMessages => "400 for a chat request without messages",
+            Check::UnknownPath => "404 for an unknown endpoint",
+            Check::InvalidApiKey => "401 for a wrong API key",
+        }
+    }
+
+    async fn run(self, suite: &Suite<'_>) -> Findings {
+        let mut f = Findings::default();
+        let model = suite.model;
+        let chat = |extra: Value| {
+            let mut body = json!({
+                "model": model,
+                "messages": [{"role": "user", "content": "Say hello."}],
+                "max_tokens": 16,
+            });
+            merge(&mut body, extra);
+            suite
+                .target
+                .post(&suite.client, "/chat/completions")
+                .json(&body)
+        };
+        let completion = |extra: Value| {
+            let mut body = json!({"model": model, "prompt": "Say hello.", "max_tokens": 16});
+            merge(&mut body, extra);
+            suite.target.post(&suite.client, "/completions").json(&body)
+        };
+        match self {
+            Check::Chat => {
+                if let Some(body) = send(&mut f, chat(json!({})))
+                    .await
+                    .and_then(|r| r.json(&mut f, 200))
+                {
+                    check_completion(&mut f, &body, Endpoint::Chat);
+                }
+            }
+            Check::ChatStream => {
+                let request =
+                    chat(json!({"stream": true, "stream_options": {"include_usage": true}}));
+                if let Some(reply) = send(&mut f, request).await {
+                    reply.check_stream(&mut f, Endpoint::Chat, true);
+                }
+            }
+            Check::Completions => {
+                if let Some(body) = send(&mut f, completion(json!({})))
+                    .await
+                    .and_then(|r| r.json(&mut f, 200))
+                {
+                    check_completion(&mut f, &body, Endpoint::Completions);
+                }
+            }
+            Check::CompletionsStream => {
+                if let Some(reply) = send(&mut f, completion(json!({"stream": true}))).await {
+                    reply.check_stream(&mut f, Endpoint::Completions, false);
+                }
+            }
+            Check::Models => {
+                let request = suite.target.get(&suite.client, "/models");
+                if let Some(body) = send(&mut f, request)
+                    .await
+                    .and_then(|r| r.json(&mut f, 200))
+                {
+                    check_models(&mut f, &body);
+                }
+            }
+            Check::InvalidJson => {
+                let request = suite
+                    .target
+                    .post(&suite.client, "/chat/completions")
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(r#"{"model": "#);
+                if let Some(reply) = send(&mut f, request).await {
+                    reply.check_error(&mut f, 400, None);
+                }
+            }
+            Check::InvalidType => {
+                if let Some(reply) = send(&mut f, chat(json!({"max_tokens": "sixteen"}))).await {
+                    reply.check_error(&mut f, 400, Some("max_tokens"));
+                }
+            }
+            Check::MissingMessages => {
+                let request = suite
+                    .target
+                    .post(&suite.client, "/chat/completions")
+                    .json(&json!({"model": model}));
+                if let Some(reply) = send(&mut f, request).await {
+                    reply.check_error(&mut f, 400, Some("messages"));
+                }
+            }
+            Check::UnknownPath => {
+                let request = suite
+                    .target
+                    .post(&suite.client, "/conformance-unknown-endpoint");
+                if let Some(reply) = send(&mut f, request.json(&json!({}))).await {
+                    reply.check_error(&mut f, 404, None);
+                }
+            }
+            Check::InvalidApiKey => {
+                let request = suite
+                    .client
+                    .post(suite.target.url("/chat/completions"))
+                    .bearer_auth("sk-conformance-invalid-key")
+                    .json(&json!({
+                        "model": model,
+                        "messages": [{"role": "user", "content": "Say hello."}],
+                        "max_tokens": 1,
+                    }));
+                match send(&mut f, request).await {
+                    Some(reply) if (200..300).contains(&reply.status) => {
+                        return Findings::skipped("the server accepts any API key");
+                    }
+                    Some(reply) => reply.check_error(&mut f, 401, None),
+                    None => {}
+                }
+            }
+        }
+        f
+    }
+}
+
+struct Suite<'a> {
+    client: reqwest::Client,
+    target: &'a Target,
+    model: &'a str,
+}
+
+fn merge(body: &mut Value, extra: Value) {
+    if let (Some(body), Value::Object(extra)) = (body.as_object_mut(), extra) {
+        body.extend(extra);
+    }
+}
+
+/// What a check found wrong. Failures break OpenAI clients, warnings are
+/// differences from the OpenAI API that clients tolerate.
+#[derive(Default)]
+struct Findings {
+    failures: Vec<String>,
+    warnings: Vec<String>,
+    skipped: Option<String>,
+}
+
+impl Findings {
+    fn skipped(reason: &str) -> Self {
+        Findings {
+            skipped: Some(reason.to_string()),
+            ..Findings::default()
+        }
+    }
+
+    // Chunks repeat the same problems, so each is only noted once
+    fn fail(&mut self, problem: String) {
+        if !self.failures.contains(&problem) {
+            self.failures.push(problem);
+        }
+    }
+
+    fn warn(&mut self, problem: String) {
+        if !self.warnings.contains(&problem) {
+            self.warnings.push(problem);
+        }
+    }
+}
+
+/// A response, read to the end
+struct Reply {
+    status: u16,
+    content_type: String,
+    body: String,
+}
+
+async fn send(f: &mut Findings, request: reqwest::RequestBuilder) -> Option<Reply> {
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            f.fail(format!("request failed: {}", with_sources(&e)));
+            return None;
+        }
+    };
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    match response.text().await {
+        Ok(body) => Some(Reply {
+            status,
+            content_type,
+            body,
+        }),
+        Err(e) => {
+            f.fail(format!("reading the response failed: {}", with_sources(&e)));
+            None
+        }
+    }
+}
+
+/// `e` followed by what caused it, which reqwest leaves out of its message
+fn with_sources(e: &dyn std::error::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+impl Reply {
+    /// The body as JSON, if the status is `status` and it is JSON
+    fn json(self, f: &mut Findings, status: u16) -> Option<Value> {
+        if self.status != status {
+            f.fail(format!(
+                "expected HTTP {}, got {}: {}",
+                status,
+                self.status,
+                truncate(&self.body, 200)
+            ));
+            return None;
+        }
+        if !self.content_type.starts_with("application/json") {
+            f.warn(format!(
+                "content-type: expected application/json, got {:?}",
+                self.content_type
+            ));
+        }
+        match serde_json::from_str(&self.body) {
+            Ok(body) => Some(body),
+            Err(e) => {
+                f.fail(format!(
+                    "body isn't JSON ({}): {}",
+                    e,
+                    truncate(&self.body, 200)
+                ));
+                None
+            }
+        }
+    }
+
+    /// An error response with `status`, naming `param` if given
+    fn check_error(self, f: &mut Findings, status: u16, param: Option<&str>) {
+        let Some(body) = self.json(f, status) else {
+            return;
+        };
+        let Some(error) = object(f, "", &body, ERROR_BODY).and_then(|body| body.get("error"))
+        else {
+            return;
+        };
+        object(f, "error", error, ERROR);
+        if let Some(param) = param
+            && error.get("param").and_then(Value::as_str) != Some(param)
+        {
+            f.warn(format!(
+                "error.param: expected {:?}, got {}",
+                param,
+                error.get("param").unwrap_or(&Value::Null)
+            ));
+        }
+    }
+
+    /// An event stream of `endpoint` chunks, ending in a usage chunk when
+    /// `include_usage` was asked for
+    fn check_stream(self, f: &mut Findings, endpoint: Endpoint, include_usage: bool) {
+        if self.status != 200 {
+            f.fail(format!(
+                "expected HTTP 200, got {}: {}",
+                self.status,
+                truncate(&self.body, 200)
+            ));
+            return;
+        }
+        if !self.content_type.starts_with("text/event-stream") {
+            f.fail(format!(
+                "content-type: expected text/event-stream, got {:?}",
+                self.content_type
+            ));
+        }
+        check_framing(f, &self.body);
+
+        let mut parser = SseParser::default();
+        let events = parser.push(self.body.as_bytes());
+        if !parser.remainder().is_empty() {
+            f.fail("the last event doesn't end with a blank line".to_string());
+        }
+        let mut chunks = Vec::new();
+        let mut done = false;
+        for (i, data) in events.iter().enumerate() {
+            if done {
+                f.fail(format!("event {}: sent after [DONE]", i));
+                break;
+            }
+            if data == "[DONE]" {
+                done = true;
+                continue;
+            }
+            match serde_json::from_str::<Value>(data) {
+                Ok(chunk) => chunks.push(chunk),
+                Err(e) => f.fail(format!(
+                    "event {}: data isn't JSON ({}): {}",
+                    i,
+                    e,
+                    truncate(data, 200)
+                )),
+            }
+        }
+        if !done {
+            f.fail("the stream doesn't end with data: [DONE]".to_string());
+        }
+        check_chunks(f, &chunks, endpoint, include_usage);
+    }
+}
+
+/// Every line is a `data` field, a comment or the blank line ending an event
+fn check_framing(f: &mut Findings, body: &str) {
+    for line in body.lines() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.is_empty() || line.starts_with(':') || line.starts_with("data:") {
+            continue;
+        }
+        match line.split_once(':') {
+            Some((field @ ("event" | "id" | "retry"), _)) => {
+                f.warn(format!("{}: field, which OpenAI doesn't send", field))
+            }
+            _ => f.fail(format!("not an SSE field: {}", truncate(line, 80))),
+        }
+    }
+}
+
+fn check_chunks(f: &mut Findings, chunks: &[Value], endpoint: Endpoint, include_usage: bool) {
+    let Some(first) = chunks.first() else {
+        f.fail("no chunks before [DONE]".to_string());
+        return;
+    };
+    // Per choice index, whether it has finished
+    let mut choices = BTreeMap::<u64, bool>::new();
+    let mut usage_chunks = Vec::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        if let Some(error) = chunk.get("error") {
+            f.fail(format!("chunk {}: error: {}", i, error));
+            continue;
+        }
+        let Some(fields) = object(f, "", chunk, endpoint.chunk_fields()) else {
+            continue;
+        };
+        for field in ["id", "created", "model"] {
+            if fields.get(field) != first.get(field) {
+                f.warn(format!("{}: differs between chunks", field));
+            }
+        }
+        if let Some(usage) = fields.get("usage").filter(|usage| !usage.is_null()) {
+            usage_chunks.push(i);
+            check_usage(f, "usage", usage);
+        }
+        let Some(list) = fields.get("choices").and_then(Value::as_array) else {
+            continue;
+        };
+        for (j, choice) in list.iter().enumerate() {
+            let path = format!("choices[{}]", j);
+            let Some(choice) = object(f, &path, choice, endpoint.chunk_choice_fields()) else {
+                continue;
+            };
+            let Some(index) = choice.get("index").and_then(Value::as_u64) else {
+                continue;
+            };
+            let delta = choice.get("delta");
+            if let Some(delta) = delta {
+                object(f, &format!("{}.delta", path), delta, DELTA);
+            }
+            let first_of_choice = !choices.contains_key(&index);
+            if endpoint == Endpoint::Chat
+                && first_of_choice
+                && delta.and_then(|d| d.get("role")).and_then(Value::as_str) != Some("assistant")
+            {
+                f.warn(format!(
+                    "choice {}: the first delta has no role, OpenAI sends \"assistant\"",
+                    index
+                ));
+            }
+            let finished = choices.entry(index).or_insert(false);
+            if *finished {
+                f.fail(format!(
+                    "choice {}: chunk {} comes after its finish_reason",
+                    index, i
+                ));
+            }
+            if choice
+                .get("finish_reason")
+                .is_some_and(|reason| !reason.is_null())
+            {
+                *finished = true;
+            }
+            if let Some(tool_calls) = delta.and_then(|d| d.get("tool_calls"))
+                && tool_calls.as_array().is_some_and(Vec::is_empty)
+            {
+                f.warn(format!(
+                    "{}.delta.tool_calls: empty array, where OpenAI leaves the field out",
+                    path
+                ));
+            }
+        }
+    }
+    if choices.is_empty() {
+        f.fail("no chunk has a choice".to_string());
+    }
+    for (index, finished) in choices {
+        if !finished {
+            f.fail(format!("choice {}: no chunk has a finish_reason", index));
+        }
+    }
+
+    let last = chunks.len() - 1;
+    match (include_usage, usage_chunks.as_slice()) {
+        (true, []) => {
+            f.fail("no usage chunk, although stream_options.include_usage was set".to_string())
+        }
+        (true, &[i]) if i != last => f.fail(format!(
+            "the usage chunk is chunk {}, rather than the last one before [DONE]",
+            i
+        )),
+        (true, &[i]) => {
+            if chunks[i]
+                .get("choices")
+                .and_then(Value::as_array)
+                .is_none_or(|choices| !choices.is_empty())
+            {
+                f.fail(
+                    "the usage chunk has choices, OpenAI sends it with `choices: []`".to_string(),
+                );
+            }
+        }
+        (true, _) => f.fail(format!(
+            "usage is set in chunks {:?}, rather than only the last one",
+            usage_chunks
+        )),
+        (false, []) => {}
+        (false, _) => f.warn("usage is sent without stream_options.include_usage".to_string()),
+    }
+}
+
+fn check_completion(f: &mut Findings, body: &Value, endpoint: Endpoint) {
+    let Some(fields) = object(f, "", body, endpoint.fields()) else {
+        return;
+    };
+    match fields.get("usage") {
+        Some(usage) => check_usage(f, "usage", usage),
+        None => f.warn("usage: missing, OpenAI always sends it".to_string()),
+    }
+    let Some(choices) = fields.get("choices").and_then(Value::as_array) else {
+        return;
+    };
+    if choices.is_empty() {
+        f.fail("choices: empty".to_string());
+    }
+    for (i, choice) in choices.iter().enumerate() {
+        let path = format!("choices[{}]", i);
+        let Some(choice) = object(f, &path, choice, endpoint.choice_fields()) else {
+            continue;
+        };
+        if choice.get("index").and_then(Value::as_u64) != Some(i as u64) {
+            f.fail(format!("{}.index: expected {}", path, i));
+        }
+        let Some(message) = choice.get("message") else {
+            continue;
+        };
+        object(f, &format!("{}.message", path), message, MESSAGE);
+        if message
+            .get("tool_calls")
+            .and_then(Value::as_array)
+            .is_some_and(Vec::is_empty)
+        {
+            f.warn(format!(
+                "{}.message.tool_calls: empty array, where OpenAI leaves the field out",
+                path
+            ));
+        }
+    }
+}
+
+fn check_usage(f: &mut Findings, path: &str, usage: &Value) {
+    let Some(usage) = object(f, path, usage, USAGE) else {
+        return;
+    };
+    let count = |field| usage.get(field).and_then(Value::as_u64);
+    if let (Some(prompt), Some(completion), Some(total)) = (
+        count("prompt_tokens"),
+        count("completion_tokens"),
+        count("total_tokens"),
+    ) && prompt + completion != total
+    {
+        f.fail(format!(
+            "{}.total_tokens: {} isn't prompt_tokens + completion_tokens = {}",
+            path,
+            total,
+            prompt + completion
+        ));
+    }
+}
+
+fn check_models(f: &mut Findings, body: &Value) {
+    let Some(list) = object(f, "", body, MODEL_LIST) else {
+        return;
+    };
+    for (i, model) in list
+        .get("data")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        object(f, &format!("data[{}]", i), model, MODEL);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    Chat,
+    Completions,
+}
+
+impl Endpoint {
+    fn fields(self) -> &'static [Field] {
+        match self {
+            Endpoint::Chat => CHAT_COMPLETION,
+            Endpoint::Completions => COMPLETION,
+        }
+    }
+
+    fn choice_fields(self) -> &'static [Field] {
+        match self {
+            Endpoint::Chat => CHAT_CHOICE,
+            Endpoint::Completions => COMPLETION_CHOICE,
+        }
+    }
+
+    fn chunk_fields(self) -> &'static [Field] {
+        match self {
+            Endpoint::Chat => CHAT_CHUNK,
+            Endpoint::Completions => COMPLETION_CHUNK,
+        }
+    }
+
+    fn chunk_choice_fields(self) -> &'static [Field] {
+        match self {
+            Endpoint::Chat => CHAT_CHUNK_CHOICE,
+            Endpoint::Completions => COMPLETION_CHUNK_CHOICE,
+        }
+    }
+}
+
+/// A JSON type from the schema
+#[derive(Clone, Copy)]
+enum Kind {
+    String,
+    Integer,
+    Object,
+    Array,
+    /// A string with exactly this value
+    Const(&'static str),
+    /// A string with one of these values
+    OneOf(&'static [&'static str]),
+}
+
+impl Kind {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            Kind::String => value.is_string(),
+            Kind::Integer => value.is_u64() || value.is_i64(),
+            Kind::Object => value.is_object(),
+            Kind::Array => value.is_array(),
+            Kind::Const(expected) => value.as_str() == Some(expected),
+            Kind::OneOf(allowed) => value.as_str().is_some_and(|s| allowed.contains(&s)),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::String => write!(f, "a string"),
+            Kind::Integer => write!(f, "an integer"),
+            Kind::Object => write!(f, "an object"),
+            Kind::Array => write!(f, "an array"),
+            Kind::Const(expected) => write!(f, "{:?}", expected),
+            Kind::OneOf(allowed) => write!(f, "one of {}", allowed.join(", ")),
+        }
+    }
+}
+
+/// A field of an object in the schema
+struct Field {
+    name: &'static str,
+    kind: Kind,
+    required: bool,
+    nullable: bool,
+}
+
+const fn required(name: &'static str, kind: Kind) -> Field {
+    Field {
+        name,
+        kind,
+        required: true,
+        nullable: false,
+    }
+}
+
+const fn required_nullable(name: &'static str, kind: Kind) -> Field {
+    Field {
+        name,
+        kind,
+        required: true,
+        nullable: true,
+    }
+}
+
+const fn optional(name: &'static str, kind: Kind) -> Field {
+    Field {
+        name,
+        kind,
+        required: false,
+        nullable: false,
+    }
+}
+
+const fn optional_nullable(name: &'static str, kind: Kind) -> Field {
+    Field {
+        name,
+        kind,
+        required: false,
+        nullable: true,
+    }
+}
+
+/// Check `value` at `path` is an object of `fields`, returning it to check further.
+///
+/// Fields outside the schema and null for fields that are only optional are
+/// warnings, since the OpenAI SDKs accept both.
+fn object<'a>(
+    f: &mut Findings,
+    path: &str,
+    value: &'a Value,
+    fields: &[Field],
+) -> Option<&'a Map<String, Value>> {
+    let at = |name: &str| {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        }
+    };
+    let Some(object) = value.as_object() else {
+        f.fail(format!(
+            "{}: expected an object, got {}",
+            if path.is_empty() { "body" } else { path },
+            describe(value)
+        ));
+        return None;
+    };
+    for field in fields {
+        match object.get(field.name) {
+            None if field.required => f.fail(format!("{}: missing", at(field.name))),
+            None => {}
+            Some(Value::Null) if field.nullable => {}
+            Some(Value::Null) if !field.required => f.warn(format!(
+                "{}: null, where OpenAI leaves the field out",
+                at(field.name)
+            )),
+            Some(value) if !field.kind.matches(value) => f.fail(format!(
+                "{}: expected {}, got {}",
+                at(field.name),
+                field.kind,
+                describe(value)
+            )),
+            Some(_) => {}
+        }
+    }
+    for name in object.keys() {
+        if !fields.iter().any(|field| field.name == name) {
+            f.warn(format!("{}: not in the OpenAI schema", at(name)));
+        }
+    }
+    Some(object)
+}
+
+/// `value` for a message, shortened if it's long
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Object(_) => "an object".to_string(),
+        Value::Array(_) => "an array".to_string(),
+        value => truncate(&value.to_string(), 40),
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+// The schema, following OpenAI's published OpenAPI spec
+
+const FINISH_REASONS: &[&str] = &[
+    "stop",
+    "length",
+    "tool_calls",
+    "content_filter",
+    "function_call",
+];
+const COMPLETION_FINISH_REASONS: &[&str] = &["stop", "length", "content_filter"];
+const SERVICE_TIERS: &[&str] = &["auto", "default", "flex", "scale", "priority"];
+const ROLES: &[&str] = &["developer", "system", "user", "assistant", "tool"];
+
+const CHAT_COMPLETION: &[Field] = &[
+    required("id", Kind::String),
+    required("object", Kind::Const("chat.completion")),
+    required("created", Kind::Integer),
+    required("model", Kind::String),
+    required("choices", Kind::Array),
+    optional("usage", Kind::Object),
+    optional_nullable("system_fingerprint", Kind::String),
+    optional_nullable("service_tier", Kind::OneOf(SERVICE_TIERS)),
+];
+
+const CHAT_CHOICE: &[Field] = &[
+    required("index", Kind::Integer),
+    required("message", Kind::Object),
+    required_nullable("logprobs", Kind::Object),
+    required("finish_reason", Kind::OneOf(FINISH_REASONS)),
+];
+
+const MESSAGE: &[Field] = &[
+    required("role", Kind::Const("assistant")),
+    required_nullable("content", Kind::String),
+    required_nullable("refusal", Kind::String),
+    optional("tool_calls", Kind::Array),
+    optional("annotations", Kind::Array),
+    optional_nullable("audio", Kind::Object),
+    optional("function_call", Kind::Object),
+];
+
+const CHAT_CHUNK: &[Field] = &[
+    required("id", Kind::String),
+    required("object", Kind::Const("chat.completion.chunk")),
+    required("created", Kind::Integer),
+    required("model", Kind::String),
+    required("choices", Kind::Array),
+    optional_nullable("usage", Kind::Object),
+    optional_nullable("system_fingerprint", Kind::String),
+    optional_nullable("service_tier", Kind::OneOf(SERVICE_TIERS)),
+];
+
+const CHAT_CHUNK_CHOICE: &[Field] = &[
+    required("index", Kind::Integer),
+    required("delta", Kind::Object),
+    optional_nullable("logprobs", Kind::Object),
+    required_nullable("finish_reason", Kind::OneOf(FINISH_REASONS)),
+];
+
+const DELTA: &[Field] = &[
+    optional("role", Kind::OneOf(ROLES)),
+    optional_nullable("content", Kind::String),
+    optional_nullable("refusal", Kind::String),
+    optional("tool_calls", Kind::Array),
+    optional("function_call", Kind::Object),
+];
+
+const COMPLETION: &[Field] = &[
+    required("id", Kind::String),
+    required("object", Kind::Const("text_completion")),
+    required("created", Kind::Integer),
+    required("model", Kind::String),
+    required("choices", Kind::Array),
+    optional("usage", Kind::Object),
+    optional("system_fingerprint", Kind::String),
+];
+
+const COMPLETION_CHOICE: &[Field] = &[
+    required("index", Kind::Integer),
+    required("text", Kind::String),
+    required_nullable("logprobs", Kind::Object),
+    required("finish_reason", Kind::OneOf(COMPLETION_FINISH_REASONS)),
+];
+
+const COMPLETION_CHUNK: &[Field] = &[
+    required("id", Kind::String),
+    required("object", Kind::Const("text_completion")),
+    required("created", Kind::Integer),
+    required("model", Kind::String),
+    required("choices", Kind::Array),
+    optional_nullable("usage", Kind::Object),
+    optional("system_fingerprint", Kind::String),
+];
+
+const COMPLETION_CHUNK_CHOICE: &[Field] = &[
+    required("index", Kind::Integer),
+    required("text", Kind::String),
+    required_nullable("logprobs", Kind::Object),
+    required_nullable("finish_reason", Kind::OneOf(COMPLETION_FINISH_REASONS)),
+];
+
+const USAGE: &[Field] = &[
+    required("prompt_tokens", Kind::Integer),
+    required("completion_tokens", Kind::Integer),
+    required("total_tokens", Kind::Integer),
+    optional("prompt_tokens_details", Kind::Object),
+    optional("completion_tokens_details", Kind::Object),
+];
+
+const ERROR_BODY: &[Field] = &[required("error", Kind::Object)];
+
+const ERROR: &[Field] = &[
+    required("message", Kind::String),
+    required("type", Kind::String),
+    required_nullable("param", Kind::String),
+    required_nullable("code", Kind::String),
+];
+
+const MODEL_LIST: &[Field] = &[
+    required("object", Kind::Const("list")),
+    required("data", Kind::Array),
+];
+
+const MODEL: &[Field] = &[
+    required("id", Kind::String),
+    required("object", Kind::Const("model")),
+    required("created", Kind::Integer),
+    required("owned_by", Kind::String),
+];
+
+/// How a check went
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    /// Passed, with differences clients tolerate
+    Warn,
+    Fail,
+    Skip,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+            Status::Skip => "SKIP",
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub status: Status,
+    pub failures: Vec<String>,
+    pub warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+}
+
+impl CheckResult {
+    fn new(check: Check, findings: Findings, strict: bool) -> Self {
+        let status = if findings.skipped.is_some() {
+            Status::Skip
+        } else if !findings.failures.is_empty() || (strict && !findings.warnings.is_empty()) {
+            Status::Fail
+        } else if !findings.warnings.is_empty() {
+            Status::Warn
+        } else {
+            Status::Pass
+        };
+        CheckResult {
+            name: check.name(),
+            description: check.description(),
+            status,
+            failures: findings.failures,
+            warnings: findings.warnings,
+            skipped: findings.skipped,
+        }
+    }
+}
+
+/// Results of the whole suite against one server
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub target: String,
+    pub checks: Vec<CheckResult>,
+}
+
+impl Report {
+    /// Whether no check failed
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.status != Status::Fail)
+    }
+
+    fn print(&self) {
+        for check in &self.checks {
+            println!("{}  {:<20} {}", check.status, check.name, check.description);
+            for failure in &check.failures {
+                println!("      fail: {}", failure);
+            }
+            for warning in &check.warnings {
+                println!("      warn: {}", warning);
+            }
+            if let Some(reason) = &check.skipped {
+                println!("      {}", reason);
+            }
+        }
+        let count = |status| self.checks.iter().filter(|c| c.status == status).count();
+        println!();
+        println!(
+            "{} checks: {} passed, {} with warnings, {} failed, {} skipped",
+            self.checks.len(),
+            count(Status::Pass),
+            count(Status::Warn),
+            count(Status::Fail),
+            count(Status::Skip)
+        );
+    }
+}
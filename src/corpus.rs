@@ -0,0 +1,223 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::generated;
+
+/// Tokens that responses are drawn from
+#[derive(Clone, Copy, Debug)]
+pub struct Corpus {
+    /// Each token as a JSON string literal, quotes included
+    pub tokens: &'static [&'static str],
+    /// Tokenizer id of each token
+    pub ids: &'static [u32],
+    /// Sorted indices of the tokens that end a sentence
+    pub sentence_ends: &'static [usize],
+}
+
+impl Corpus {
+    /// The corpus tokenized at build time
+    pub fn builtin() -> Self {
+        Corpus {
+            tokens: generated::TOKENIZED_OUTPUT,
+            ids: generated::TOKEN_IDS,
+            sentence_ends: generated::SENTENCE_ENDS,
+        }
+    }
+
+    /// Load a plain text file as a corpus.
+    ///
+    /// There's no tokenizer at runtime, so the text is split into words with
+    /// their leading space, punctuation and line breaks, and each distinct word
+    /// gets the next id. Loaded corpora live for the rest of the process, as
+    /// streams may still be reading them. Loading the same path again reuses
+    /// the corpus until the file's modification time or length changes.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        type Stamp = (Option<SystemTime>, u64);
+        static LOADED: OnceLock<Mutex<HashMap<PathBuf, (Stamp, Corpus)>>> = OnceLock::new();
+        let mut loaded = LOADED.get_or_init(Default::default).lock().unwrap();
+        let metadata = std::fs::metadata(path)?;
+        let stamp = (metadata.modified().ok(), metadata.len());
+        if let Some((loaded_stamp, corpus)) = loaded.get(path)
+            && *loaded_stamp == stamp
+        {
+            return Ok(*corpus);
+        }
+        let text = std::fs::read_to_string(path)?;
+        let words = split_words(&text);
+        if words.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Corpus {} has no text", path.display()),
+            ));
+        }
+        let sentence_ends = words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| word.trim_end().ends_with(['.', '!', '?']))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let tokens = words
+            .iter()
+            .map(|word| &*serde_json::to_string(word).unwrap().leak())
+            .collect::<Vec<_>>();
+        let mut vocab = HashMap::new();
+        let ids = words
+            .iter()
+            .map(|word| {
+                let next = vocab.len() as u32;
+                *vocab.entry(*word).or_insert(next)
+            })
+            .collect::<Vec<_>>();
+        let corpus = Corpus {
+            tokens: tokens.leak(),
+            ids: ids.leak(),
+            sentence_ends: sentence_ends.leak(),
+        };
+        log::info!(
+            "Loaded corpus {} with {} tokens",
+            path.display(),
+            corpus.tokens.len()
+        );
+        loaded.insert(path.to_path_buf(), (stamp, corpus));
+        Ok(corpus)
+    }
+
+    /// Most tokens a single response can have
+    pub fn max_tokens(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+/// Split text roughly the way GPT-2 pre-tokenizes it: runs of letters or
+/// digits keep their leading space, everything else stands alone
+fn split_words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        // A space belongs to the word that follows it
+        let c = match (c, chars.peek()) {
+            (' ', Some(&(_, next))) if next.is_alphanumeric() => {
+                chars.next();
+                next
+            }
+            _ => c,
+        };
+        if c.is_alphanumeric() {
+            while chars.next_if(|&(_, next)| next.is_alphanumeric()).is_some() {}
+        }
+        let end = chars.peek().map_or(text.len(), |&(i, _)| i);
+        words.push(&text[start..end]);
+        start = end;
+    }
+    words
+}
+
+/// How the starting position in the corpus is chosen for each request
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OffsetMode {
+    /// Pick a random offset for every request
+    #[default]
+    Random,
+    /// Derive the offset from a hash of the prompt, so identical prompts give identical output
+    Prompt,
+    /// Always start from the beginning of the corpus
+    Zero,
+}
+
+/// Pick the starting token for a response.
+///
+/// A request `seed` always wins so that seeded requests are reproducible
+/// regardless of the server's offset mode.
+pub fn start_offset(
+    mode: OffsetMode,
+    seed: Option<i64>,
+    prompt: Option<&Value>,
+    len: usize,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    let key = match (seed, mode) {
+        (Some(seed), _) => hash_of(&seed),
+        (None, OffsetMode::Random) => rand::random::<u64>(),
+        (None, OffsetMode::Prompt) => prompt.map(|p| hash_of(&p.to_string())).unwrap_or(0),
+        (None, OffsetMode::Zero) => 0,
+    };
+    (key % len as u64) as usize
+}
+
+pub(crate) fn hash_of<T: Hash>(value: &T) -> u64 {
+    // DefaultHasher::new() uses fixed keys, so this is stable for a given build
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Join `count` tokens starting at `offset` into a single JSON string literal,
+/// wrapping around the end of the corpus.
+///
+/// Each token is already an escaped JSON string (quotes included), so we only
+/// need to strip the quotes and concatenate the inner parts.
+pub fn join_tokens(tokens: &[&str], offset: usize, count: usize) -> String {
+    let mut out = String::with_capacity(count * 8 + 2);
+    out.push('"');
+    if !tokens.is_empty() {
+        for i in 0..count {
+            let token = tokens[(offset + i) % tokens.len()];
+            out.push_str(&token[1..token.len() - 1]);
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The ids of `count` tokens starting at `offset` as a JSON array, wrapping
+/// around the end of the corpus like [`join_tokens`]
+pub fn join_ids(ids: &[u32], offset: usize, count: usize) -> String {
+    let mut out = String::with_capacity(count * 7 + 2);
+    out.push('[');
+    if !ids.is_empty() {
+        for i in 0..count {
+            if i > 0 {
+                out.push(',');
+            }
+            // Writing to a String can't fail
+            let _ = write!(out, "{}", ids[(offset + i) % ids.len()]);
+        }
+    }
+    out.push(']');
+    out
+}
+
+/// Length of a response that stops at the end of a sentence.
+///
+/// Returns the number of tokens from `offset` up to and including the first
+/// sentence end that gives at least `min_tokens`, or `None` if there is no such
+/// boundary within `max_tokens`. `ends` must be sorted token indices.
+pub fn natural_length(
+    ends: &[usize],
+    len: usize,
+    offset: usize,
+    min_tokens: usize,
+    max_tokens: usize,
+) -> Option<usize> {
+    let min_tokens = min_tokens.max(1);
+    if ends.is_empty() || len == 0 || min_tokens > max_tokens {
+        return None;
+    }
+    let start = (offset + min_tokens - 1) % len;
+    // Wrap around to the first sentence end if there are none left
+    let i = ends.partition_point(|&end| end < start);
+    let end = ends.get(i).copied().unwrap_or(ends[0]);
+    let count = min_tokens + (end + len - start) % len;
+    (count <= max_tokens).then_some(count)
+}
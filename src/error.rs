@@ -0,0 +1,126 @@
+use axum::{
+    Json,
+    extract::{FromRequest, Request, rejection::JsonRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// An error in the OpenAI `{"error": {...}}` format
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+    pub kind: &'static str,
+    pub param: Option<String>,
+    pub code: Option<&'static str>,
+}
+
+impl ApiError {
+    /// 400 `invalid_request_error`, the most common error
+    pub fn invalid_request(
+        message: impl Into<String>,
+        param: Option<String>,
+        code: Option<&'static str>,
+    ) -> Self {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+            kind: "invalid_request_error",
+            param,
+            code,
+        }
+    }
+
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+// Field order matches the OpenAI API
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail<'a> {
+    message: &'a str,
+    #[serde(rename = "type")]
+    kind: &'a str,
+    param: Option<&'a str>,
+    code: Option<&'a str>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            error: ErrorDetail {
+                message: &self.message,
+                kind: self.kind,
+                param: self.param.as_deref(),
+                code: self.code,
+            },
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+const INVALID_JSON_MESSAGE: &str = "We could not parse the JSON body of your request. (HINT: This likely means you aren't using your HTTP library correctly. The OpenAI API expects a JSON payload, but what was sent was not valid JSON.)";
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection {
+            JsonRejection::JsonDataError(err) => {
+                // axum reports the failing field as "<path>: <serde error>"
+                let text = err.body_text();
+                let detail = text
+                    .split_once("target type: ")
+                    .map_or(text.as_str(), |(_, detail)| detail);
+                let param = detail
+                    .split_once(": ")
+                    .filter(|(path, _)| !path.contains(' '))
+                    .map(|(path, _)| path.to_string());
+                ApiError::invalid_request(
+                    format!("Invalid request body: {}", detail),
+                    param,
+                    Some("invalid_type"),
+                )
+            }
+            JsonRejection::MissingJsonContentType(_) => ApiError::invalid_request(
+                "Expected request with `Content-Type: application/json`",
+                None,
+                None,
+            ),
+            JsonRejection::BytesRejection(err) if err.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                ApiError {
+                    status: StatusCode::PAYLOAD_TOO_LARGE,
+                    message: "Request body is too large".to_string(),
+                    kind: "invalid_request_error",
+                    param: None,
+                    code: None,
+                }
+            }
+            _ => ApiError::invalid_request(INVALID_JSON_MESSAGE, None, None),
+        }
+    }
+}
+
+/// Drop-in replacement for [`axum::Json`] that rejects with an OpenAI error body
+/// instead of axum's plain text
+pub struct ApiJson<T>(pub T);
+
+impl<S, T> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        Ok(ApiJson(value))
+    }
+}
@@ -0,0 +1,240 @@
+use serde_json::Value;
+use std::fmt;
+
+use crate::journal::ReceivedRequest;
+
+/// How many matching requests an [`Expectation`] allows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Times {
+    Exactly(usize),
+    AtLeast(usize),
+    AtMost(usize),
+}
+
+impl Times {
+    fn allows(self, count: usize) -> bool {
+        match self {
+            Times::Exactly(n) => count == n,
+            Times::AtLeast(n) => count >= n,
+            Times::AtMost(n) => count <= n,
+        }
+    }
+}
+
+impl fmt::Display for Times {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Times::Exactly(n) => write!(f, "exactly {}", n),
+            Times::AtLeast(n) => write!(f, "at least {}", n),
+            Times::AtMost(n) => write!(f, "at most {}", n),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Condition {
+    /// A body field, by name or JSON pointer
+    Field(String, Value),
+    /// `stream`, which is false when left out
+    Stream(bool),
+    Header(String, String),
+}
+
+impl Condition {
+    /// Why `request` doesn't meet this condition, if it doesn't
+    fn mismatch(&self, request: &ReceivedRequest) -> Option<String> {
+        match self {
+            Condition::Field(field, expected) => match request.field(field) {
+                Some(actual) if actual == expected => None,
+                Some(actual) => Some(format!("{}: expected {}, got {}", field, expected, actual)),
+                None => Some(format!("{}: expected {}, got nothing", field, expected)),
+            },
+            Condition::Stream(expected) => (request.stream() != *expected).then(|| {
+                format!(
+                    "stream: expected {}, got {}",
+                    expected,
+                    request.field("stream").unwrap_or(&Value::Null)
+                )
+            }),
+            Condition::Header(name, expected) => match request.header(name) {
+                Some(actual) if actual == expected => None,
+                Some(actual) => Some(format!(
+                    "header {}: expected {:?}, got {:?}",
+                    name, expected, actual
+                )),
+                None => Some(format!(
+                    "header {}: expected {:?}, got nothing",
+                    name, expected
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Field(field, value) => write!(f, "{} = {}", field, value),
+            Condition::Stream(stream) => write!(f, "stream = {}", stream),
+            Condition::Header(name, value) => write!(f, "header {} = {:?}", name, value),
+        }
+    }
+}
+
+/// Requests a test expects the server to have received, checked against the
+/// journal by [`crate::MockServer::verify`]:
+///
+/// ```
+/// # use mock_openai::expect::Expectation;
+/// Expectation::post("/v1/chat/completions")
+///     .model("x")
+///     .stream(true)
+///     .times(3);
+/// ```
+///
+/// Without a count, at least one matching request is expected.
+#[derive(Clone, Debug)]
+pub struct Expectation {
+    method: Option<String>,
+    path: Option<String>,
+    conditions: Vec<Condition>,
+    times: Times,
+}
+
+impl Expectation {
+    /// Requests with any method and path
+    pub fn any() -> Self {
+        Expectation {
+            method: None,
+            path: None,
+            conditions: Vec::new(),
+            times: Times::AtLeast(1),
+        }
+    }
+
+    pub fn post(path: impl Into<String>) -> Self {
+        Expectation::any().method("POST").path(path)
+    }
+
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.method = Some(method.into().to_ascii_uppercase());
+        self
+    }
+
+    /// The full path, including the `/v1` prefix
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn model(self, model: impl Into<String>) -> Self {
+        self.field("model", model.into())
+    }
+
+    /// `stream` set to `stream`, where leaving it out counts as false
+    pub fn stream(mut self, stream: bool) -> Self {
+        self.conditions.push(Condition::Stream(stream));
+        self
+    }
+
+    /// A body field by name, or by JSON pointer such as `/stream_options/include_usage`
+    pub fn field(mut self, field: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.conditions
+            .push(Condition::Field(field.into(), value.into()));
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.conditions
+            .push(Condition::Header(name.into(), value.into()));
+        self
+    }
+
+    pub fn times(mut self, n: usize) -> Self {
+        self.times = Times::Exactly(n);
+        self
+    }
+
+    pub fn at_least(mut self, n: usize) -> Self {
+        self.times = Times::AtLeast(n);
+        self
+    }
+
+    pub fn at_most(mut self, n: usize) -> Self {
+        self.times = Times::AtMost(n);
+        self
+    }
+
+    pub fn never(self) -> Self {
+        self.times(0)
+    }
+
+    /// Every way `request` differs from this expectation
+    pub fn mismatches(&self, request: &ReceivedRequest) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if let Some(method) = &self.method
+            && *method != request.method
+        {
+            mismatches.push(format!(
+                "method: expected {}, got {}",
+                method, request.method
+            ));
+        }
+        if let Some(path) = &self.path
+            && *path != request.path
+        {
+            mismatches.push(format!("path: expected {}, got {}", path, request.path));
+        }
+        mismatches.extend(self.conditions.iter().filter_map(|c| c.mismatch(request)));
+        mismatches
+    }
+
+    pub fn matches(&self, request: &ReceivedRequest) -> bool {
+        self.mismatches(request).is_empty()
+    }
+
+    /// `None` when `requests` meet this expectation, otherwise a report of
+    /// how each request differs from it
+    pub fn check(&self, requests: &[ReceivedRequest]) -> Option<String> {
+        let matched = requests.iter().filter(|r| self.matches(r)).count();
+        if self.times.allows(matched) {
+            return None;
+        }
+        let mut report = format!(
+            "Expected {} request(s) matching {}, got {}",
+            self.times, self, matched
+        );
+        if requests.is_empty() {
+            report.push_str("\n  no requests were received");
+        }
+        for (i, request) in requests.iter().enumerate() {
+            let mismatches = self.mismatches(request);
+            let outcome = if mismatches.is_empty() {
+                "matched".to_string()
+            } else {
+                mismatches.join("; ")
+            };
+            report.push_str(&format!(
+                "\n  #{} {} {}: {}",
+                i, request.method, request.path, outcome
+            ));
+        }
+        Some(report)
+    }
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.method.as_deref().unwrap_or("*"),
+            self.path.as_deref().unwrap_or("*")
+        )?;
+        for (i, condition) in self.conditions.iter().enumerate() {
+            let separator = if i == 0 { " with" } else { "," };
+            write!(f, "{} {}", separator, condition)?;
+        }
+        Ok(())
+    }
+}
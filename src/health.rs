@@ -0,0 +1,95 @@
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+use crate::error::ApiError;
+
+/// Health set through `/admin/health`, to see how clients and load balancers react
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    #[default]
+    Healthy,
+    /// Still ready, but reported as degraded
+    Degraded,
+    /// Not ready
+    Unhealthy,
+}
+
+/// What `/health/ready` reports
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Readiness {
+    /// Still in the simulated model loading
+    Loading,
+    Ready,
+    Degraded,
+    Unhealthy,
+    Draining,
+}
+
+impl Readiness {
+    pub fn is_ready(self) -> bool {
+        matches!(self, Readiness::Ready | Readiness::Degraded)
+    }
+}
+
+/// Process health for the liveness and readiness endpoints
+pub struct Health {
+    started: Instant,
+    warmup: Duration,
+    status: Mutex<HealthStatus>,
+}
+
+impl Health {
+    /// Health of a server that reports itself as loading for `warmup` after starting
+    pub fn new(warmup: Duration) -> Self {
+        Health {
+            started: Instant::now(),
+            warmup,
+            status: Mutex::new(HealthStatus::default()),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn status(&self) -> HealthStatus {
+        *self.status.lock().unwrap()
+    }
+
+    pub fn set_status(&self, status: HealthStatus) {
+        *self.status.lock().unwrap() = status;
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.uptime() < self.warmup
+    }
+
+    pub fn readiness(&self, draining: bool) -> Readiness {
+        if draining {
+            return Readiness::Draining;
+        }
+        if self.is_loading() {
+            return Readiness::Loading;
+        }
+        match self.status() {
+            HealthStatus::Healthy => Readiness::Ready,
+            HealthStatus::Degraded => Readiness::Degraded,
+            HealthStatus::Unhealthy => Readiness::Unhealthy,
+        }
+    }
+
+    /// The error completions get while the model is still loading
+    pub fn loading_error(&self) -> Option<ApiError> {
+        self.is_loading().then(|| ApiError {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: "The model is still loading, please try again later.".to_string(),
+            kind: "server_error",
+            param: None,
+            code: Some("model_loading"),
+        })
+    }
+}
@@ -0,0 +1,194 @@
+use axum::{
+    body::{Body, to_bytes},
+    extract::{OriginalUri, Request, State},
+    http::{HeaderMap, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::ApiError;
+use crate::routes::AppState;
+
+/// Requests the journal keeps unless told otherwise
+pub const DEFAULT_JOURNAL_SIZE: usize = 1000;
+
+/// Largest body the journal buffers, a little over the largest body the
+/// routes accept, so their own limit is the one clients run into
+const MAX_BODY: usize = 32 * 1024 * 1024;
+
+/// Headers whose values are credentials, kept in the journal only by name
+const REDACTED: &[&str] = &["authorization", "api-key", "x-api-key"];
+
+/// A request as the client sent it
+#[derive(Serialize, Clone, Debug)]
+pub struct ReceivedRequest {
+    /// Unix time the request arrived, in seconds
+    pub timestamp: f64,
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    /// Header names in lowercase. Repeated headers are joined with `, `
+    /// and credentials are redacted.
+    pub headers: BTreeMap<String, String>,
+    /// The body as JSON, as a string when it isn't valid JSON, or null when
+    /// empty. Always null for requests rejected for their API key, whose
+    /// bodies aren't read.
+    pub body: Value,
+}
+
+impl ReceivedRequest {
+    /// A body field by name, or by JSON pointer such as `/stream_options/include_usage`
+    pub fn field(&self, field: &str) -> Option<&Value> {
+        if field.starts_with('/') {
+            self.body.pointer(field)
+        } else {
+            self.body.get(field)
+        }
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.field("model")?.as_str()
+    }
+
+    pub fn stream(&self) -> bool {
+        self.field("stream") == Some(&Value::Bool(true))
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+/// The most recent requests received on the API routes, oldest first, for
+/// tests to check what their client actually sent
+pub struct Journal {
+    capacity: usize,
+    requests: Mutex<VecDeque<ReceivedRequest>>,
+}
+
+impl Journal {
+    /// A journal keeping the last `capacity` requests, none when it's 0
+    pub fn new(capacity: usize) -> Self {
+        Journal {
+            capacity,
+            requests: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn push(&self, request: ReceivedRequest) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut requests = self.requests.lock().unwrap();
+        if requests.len() == self.capacity {
+            requests.pop_front();
+        }
+        requests.push_back(request);
+    }
+
+    pub fn list(&self) -> Vec<ReceivedRequest> {
+        self.requests.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.requests.lock().unwrap().clear();
+    }
+
+    /// Record a request turned away for its API key, without its body
+    pub(crate) fn push_rejected(&self, req: &Request) {
+        if self.is_enabled() {
+            self.push(received(
+                req.method(),
+                original_uri(req),
+                req.headers(),
+                Value::Null,
+            ));
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+}
+
+fn original_uri(req: &Request) -> &Uri {
+    req.extensions()
+        .get::<OriginalUri>()
+        .map_or(req.uri(), |OriginalUri(uri)| uri)
+}
+
+fn received(method: &Method, uri: &Uri, header_map: &HeaderMap, body: Value) -> ReceivedRequest {
+    let mut headers = BTreeMap::<String, String>::new();
+    for (name, value) in header_map {
+        let value = if REDACTED.contains(&name.as_str()) {
+            "<redacted>".into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+        headers
+            .entry(name.to_string())
+            .and_modify(|joined| {
+                joined.push_str(", ");
+                joined.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+    ReceivedRequest {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_secs_f64()),
+        method: method.to_string(),
+        path: uri.path().to_string(),
+        query: uri.query().map(str::to_string),
+        headers,
+        body,
+    }
+}
+
+/// Middleware that records every request it wraps in the [`Journal`]. It runs
+/// after authentication, so unauthenticated clients can't make the server
+/// buffer their bodies. Those are recorded by [`crate::auth::require_api_key`]
+/// instead, with headers only.
+pub async fn record(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if !state.journal.is_enabled() {
+        return next.run(req).await;
+    }
+    let (parts, body) = req.into_parts();
+    let bytes = match to_bytes(body, MAX_BODY).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return ApiError {
+                status: StatusCode::PAYLOAD_TOO_LARGE,
+                message: "Request body is too large".to_string(),
+                kind: "invalid_request_error",
+                param: None,
+                code: None,
+            }
+            .into_response();
+        }
+    };
+
+    let body = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+    };
+    let uri = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map_or(&parts.uri, |OriginalUri(uri)| uri);
+    state
+        .journal
+        .push(received(&parts.method, uri, &parts.headers, body));
+
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
@@ -1,79 +1,293 @@
+pub mod admin;
 pub mod args;
+pub mod auth;
+pub mod azure;
+pub mod bench;
+pub mod client;
+pub mod compat;
+pub mod config;
+pub mod conformance;
+pub mod corpus;
+pub mod error;
+pub mod expect;
+pub mod health;
+pub mod journal;
+pub mod listen;
+pub mod logprobs;
+pub mod media;
+pub mod metrics;
+pub mod mock_server;
+pub mod profile;
+pub mod prompt;
+pub mod reasoning;
+pub mod request_log;
 pub mod routes;
+pub mod shutdown;
 pub mod stream;
 pub mod template;
+pub mod tls;
+pub mod validate;
 
 pub mod generated {
     include!(concat!(env!("OUT_DIR"), "/generated.rs"));
 }
 
 use axum::{
-    Router,
-    http::StatusCode,
+    Json, Router,
+    extract::{DefaultBodyLimit, OriginalUri, State},
+    http::{Method, StatusCode, header},
+    middleware,
     response::IntoResponse,
     routing::{get, post},
 };
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use tokio_rustls::rustls::ServerConfig;
 use tower_http::trace::TraceLayer;
 
+use config::Config;
+use error::ApiError;
+use health::Readiness;
+use listen::{Bind, Bound, Ready};
+use shutdown::Shutdown;
+
+pub use mock_server::{MockServer, MockServerBuilder};
 pub use routes::{AppState, Request};
 
+/// Serve on every address in `binds`, over TLS when `tls` is given. All
+/// listeners share one router and state.
 pub async fn start_server(
-    address: &str,
-    port: u16,
-    token: Option<String>,
-    inter_token_latency: u64,
+    binds: &[Bind],
+    tls: Option<Arc<ServerConfig>>,
+    ready_file: Option<&Path>,
+    app_state: AppState,
 ) -> std::io::Result<()> {
     log::info!("Configuring application routes");
 
-    // Create application state
-    let app_state = AppState {
-        token,
-        inter_token_latency,
-    };
-
     // Log authentication configuration
-    if app_state.token.is_some() {
-        log::info!("Authentication: Bearer token authentication enabled");
+    let keys = app_state.config.load().keys.len();
+    if keys > 0 {
+        log::info!(
+            "Authentication: Bearer token authentication enabled with {} key(s)",
+            keys
+        );
     } else {
         log::info!("Authentication: No token configured - accepting all requests");
     }
 
-    // Build our application with routes
-    let app = Router::new()
+    let shutdown = app_state.shutdown.clone();
+    let request_log = app_state.request_log.clone();
+
+    let app = router(app_state);
+
+    let mut listeners = Vec::new();
+    for bind in binds {
+        log::info!("Binding server to {}", bind);
+        let bound = Bound::bind(bind).await.map_err(|e| {
+            log::error!("Failed to bind to {}: {}", bind, e);
+            e
+        })?;
+        log::info!("Server successfully bound to {}", bound.url(tls.is_some()));
+        listeners.push(bound);
+    }
+    if tls.is_some() {
+        log::info!("Serving HTTPS, with HTTP/2 negotiated via ALPN");
+    }
+    Ready::new(&listeners, tls.is_some()).report(ready_file)?;
+
+    {
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            shutdown::signal().await;
+            log::info!("Shutting down, no longer accepting connections");
+            // Listeners stop once draining starts, and each returns once its
+            // connections have closed
+            shutdown.drain().await
+        });
+    }
+
+    log::info!("Starting to accept incoming connections");
+    let mut servers = tokio::task::JoinSet::new();
+    for bound in listeners {
+        let (app, tls, shutdown) = (app.clone(), tls.clone(), shutdown.clone());
+        servers.spawn(serve(bound, app, tls, shutdown));
+    }
+    while let Some(result) = servers.join_next().await {
+        result.map_err(std::io::Error::other)??;
+    }
+    if let Some(request_log) = request_log {
+        request_log.flush().await;
+    }
+    log::info!("Server stopped");
+    Ok(())
+}
+
+/// The whole API for `config`, to call in-process without a socket, e.g. with
+/// tower's `ServiceExt::oneshot`. The server's background features, such as
+/// graceful shutdown and config reloading, are left out.
+pub fn build_router(config: Config) -> Result<Router, ApiError> {
+    Ok(router(AppState::new(config)?))
+}
+
+/// Every route, sharing `app_state`
+pub fn router(app_state: AppState) -> Router {
+    Router::new()
         .route("/health", get(health))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready))
         .route("/tokens", get(get_max_tokens))
-        .nest("/v1", v1_routes())
+        .route("/metrics", get(metrics))
+        .nest("/v1", v1_routes(app_state.clone()))
+        .nest("/openai", azure::azure_routes(app_state.clone()))
+        .nest("/admin", admin::admin_routes(app_state.clone()))
         .fallback(not_found)
         .layer(TraceLayer::new_for_http())
-        .with_state(app_state);
-
-    log::info!("Binding server to {}:{}", address, port);
-    // Start server
-    let listener = tokio::net::TcpListener::bind(format!("{}:{}", address, port))
-        .await
-        .map_err(|e| {
-            log::error!("Failed to bind to {}:{}: {}", address, port, e);
-            e
-        })?;
-    log::info!("Server successfully bound to {}:{}", address, port);
-    log::info!("Starting to accept incoming connections");
-    axum::serve(listener, app).await
+        .with_state(app_state)
+}
+
+async fn serve(
+    bound: Bound,
+    app: Router,
+    tls: Option<Arc<ServerConfig>>,
+    shutdown: Arc<Shutdown>,
+) -> std::io::Result<()> {
+    match bound {
+        Bound::Tcp(listener) => serve_on(listener, app, tls, shutdown).await,
+        #[cfg(unix)]
+        Bound::Unix(listener, path) => {
+            let result = serve_on(listener, app, tls, shutdown).await;
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::warn!("Failed to remove {}: {}", path.display(), e);
+            }
+            result
+        }
+    }
 }
 
-async fn health() -> impl IntoResponse {
-    StatusCode::OK
+async fn serve_on<L>(
+    listener: L,
+    app: Router,
+    tls: Option<Arc<ServerConfig>>,
+    shutdown: Arc<Shutdown>,
+) -> std::io::Result<()>
+where
+    L: axum::serve::Listener,
+    L::Addr: std::fmt::Debug + Clone + Sync,
+{
+    match tls {
+        Some(tls) => {
+            axum::serve(tls::TlsListener::new(listener, tls)?, app)
+                .with_graceful_shutdown(shutdown.stopped())
+                .await
+        }
+        None => {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown.stopped())
+                .await
+        }
+    }
+}
+
+async fn health(State(state): State<AppState>) -> impl IntoResponse {
+    if state.shutdown.is_draining() {
+        (StatusCode::SERVICE_UNAVAILABLE, "draining")
+    } else {
+        (StatusCode::OK, "")
+    }
+}
+
+#[derive(Serialize)]
+struct Liveness {
+    status: &'static str,
+    uptime_seconds: f64,
+}
+
+/// Liveness, which holds for as long as the process is serving requests
+async fn health_live(State(state): State<AppState>) -> Json<Liveness> {
+    Json(Liveness {
+        status: "alive",
+        uptime_seconds: state.health.uptime().as_secs_f64(),
+    })
+}
+
+#[derive(Serialize)]
+struct ReadinessBody {
+    status: Readiness,
+    uptime_seconds: f64,
+    /// Streams currently being served
+    queue_depth: usize,
+    models: Vec<ModelStatus>,
+}
+
+#[derive(Serialize)]
+struct ModelStatus {
+    id: String,
+    status: Readiness,
+}
+
+/// Readiness, 503 while loading, unhealthy or draining and 200 otherwise
+async fn health_ready(State(state): State<AppState>) -> impl IntoResponse {
+    let status = state.health.readiness(state.shutdown.is_draining());
+    let live = state.config.load();
+    let body = ReadinessBody {
+        status,
+        uptime_seconds: state.health.uptime().as_secs_f64(),
+        queue_depth: state.shutdown.in_flight(),
+        models: live
+            .catalog()
+            .map(|id| ModelStatus {
+                id: id.to_string(),
+                status,
+            })
+            .collect(),
+    };
+    let code = if status.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(body))
 }
 
 async fn get_max_tokens() -> impl IntoResponse {
     format!("Max tokens: {}", generated::MAX_TOKENS)
 }
 
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
+
 async fn not_found() -> impl IntoResponse {
     (StatusCode::NOT_FOUND, "Not Found")
 }
 
-fn v1_routes() -> Router<AppState> {
+/// OpenAI's 404 for paths under `/v1` that aren't an endpoint
+async fn invalid_url(method: Method, OriginalUri(uri): OriginalUri) -> ApiError {
+    ApiError::invalid_request(
+        format!("Invalid URL ({} {})", method, uri.path()),
+        None,
+        None,
+    )
+    .with_status(StatusCode::NOT_FOUND)
+}
+
+fn v1_routes(state: AppState) -> Router<AppState> {
     Router::new()
+        .route("/models", get(routes::list_models))
         .route("/completions", post(routes::common_completions))
         .route("/chat/completions", post(routes::common_completions))
+        // Layers run bottom to top, so only authenticated requests are journaled
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            journal::record,
+        ))
+        // Every /v1 route is authenticated, /health and /metrics stay open
+        .route_layer(middleware::from_fn_with_state(state, auth::require_api_key))
+        // axum's default of 2MB would turn away most images
+        .layer(DefaultBodyLimit::max(media::MAX_BODY_BYTES))
+        .fallback(invalid_url)
 }
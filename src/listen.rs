@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::net::TcpListener;
+
+/// An address to listen on, `host:port` or `unix:<path>`
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(from = "String")]
+pub enum Bind {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl From<String> for Bind {
+    fn from(bind: String) -> Self {
+        match bind.strip_prefix("unix:") {
+            Some(path) => Bind::Unix(PathBuf::from(path)),
+            None => Bind::Tcp(bind),
+        }
+    }
+}
+
+impl From<&str> for Bind {
+    fn from(bind: &str) -> Self {
+        Bind::from(bind.to_string())
+    }
+}
+
+impl fmt::Display for Bind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bind::Tcp(address) => f.write_str(address),
+            Bind::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A listener that is bound and ready to serve
+pub enum Bound {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, PathBuf),
+}
+
+impl Bound {
+    pub async fn bind(bind: &Bind) -> io::Result<Bound> {
+        match bind {
+            Bind::Tcp(address) => Ok(Bound::Tcp(TcpListener::bind(address).await?)),
+            #[cfg(unix)]
+            Bind::Unix(path) => {
+                remove_stale_socket(path)?;
+                let listener = tokio::net::UnixListener::bind(path)?;
+                Ok(Bound::Unix(listener, path.clone()))
+            }
+            #[cfg(not(unix))]
+            Bind::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            )),
+        }
+    }
+
+    /// The port the OS picked, for listeners bound to port 0
+    pub fn port(&self) -> Option<u16> {
+        match self {
+            Bound::Tcp(listener) => listener.local_addr().ok().map(|addr| addr.port()),
+            #[cfg(unix)]
+            Bound::Unix(..) => None,
+        }
+    }
+
+    /// Where clients reach this listener, e.g. `http://127.0.0.1:8000` or `unix:/tmp/mock.sock`
+    pub fn url(&self, tls: bool) -> String {
+        match self {
+            Bound::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => format!("{}://{}", if tls { "https" } else { "http" }, addr),
+                Err(_) => "unknown".to_string(),
+            },
+            #[cfg(unix)]
+            Bound::Unix(_, path) => format!("unix:{}", path.display()),
+        }
+    }
+}
+
+/// A socket left behind by a previous run would make binding fail, anything
+/// else at that path is left alone
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+/// Printed as one line on stdout, and written to `--ready-file`, once every
+/// listener is bound
+#[derive(Serialize)]
+pub struct Ready {
+    pub event: &'static str,
+    pub pid: u32,
+    /// Port of the first TCP listener
+    pub port: Option<u16>,
+    pub listeners: Vec<String>,
+}
+
+impl Ready {
+    pub fn new(bound: &[Bound], tls: bool) -> Self {
+        Ready {
+            event: "ready",
+            pid: std::process::id(),
+            port: bound.iter().find_map(Bound::port),
+            listeners: bound.iter().map(|bound| bound.url(tls)).collect(),
+        }
+    }
+
+    pub fn report(&self, ready_file: Option<&Path>) -> io::Result<()> {
+        let line = serde_json::to_string(self).map_err(io::Error::other)?;
+        println!("{}", line);
+        if let Some(path) = ready_file {
+            // Written to a temporary file first, so anything polling for the
+            // file never reads it half-written
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, format!("{}\n", line))?;
+            std::fs::rename(&tmp, path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::Write;
+
+/// Upper bound on `top_logprobs`, same as the OpenAI API
+pub const MAX_TOP_LOGPROBS: usize = 20;
+
+/// Generates plausible synthetic log probabilities for emitted tokens.
+///
+/// The sampler is seeded once per request, and tokens are always sampled in
+/// output order, so the same `seed` always produces the same values whether
+/// the response is streamed or not.
+pub struct LogprobSampler {
+    rng: StdRng,
+    top_logprobs: usize,
+    /// Characters of text rendered so far, with the legacy format
+    text_offset: Option<usize>,
+}
+
+/// One sampled token: its log probability and the distinct candidates for
+/// its `top_logprobs`, the token itself first
+struct Sample<'a> {
+    logprob: f64,
+    top: Vec<(&'a str, f64)>,
+}
+
+impl LogprobSampler {
+    pub fn new(seed: Option<i64>, top_logprobs: usize) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed as u64),
+            None => StdRng::from_os_rng(),
+        };
+        LogprobSampler {
+            rng,
+            top_logprobs,
+            text_offset: None,
+        }
+    }
+
+    /// Render the legacy `/v1/completions` format, parallel `tokens`,
+    /// `token_logprobs`, `top_logprobs` and `text_offset` arrays, in place of
+    /// `content` entries
+    pub fn legacy(mut self) -> Self {
+        self.text_offset = Some(0);
+        self
+    }
+
+    /// Render the logprobs object for a single token, as sent in each SSE chunk
+    pub fn render_chunk(&mut self, token: &str, vocab: &[&str]) -> String {
+        let sample = self.sample(token, vocab);
+        self.render(&[(token, sample)])
+    }
+
+    /// Render the logprobs object covering `count` tokens starting at
+    /// `offset`, as sent in a non-streaming response
+    pub fn render_content(&mut self, vocab: &[&str], offset: usize, count: usize) -> String {
+        let count = if vocab.is_empty() { 0 } else { count };
+        let samples: Vec<(&str, Sample)> = (0..count)
+            .map(|i| {
+                let token = vocab[(offset + i) % vocab.len()];
+                (token, self.sample(token, vocab))
+            })
+            .collect();
+        self.render(&samples)
+    }
+
+    fn render(&mut self, samples: &[(&str, Sample)]) -> String {
+        let mut out = String::with_capacity(16 + samples.len() * (64 + self.top_logprobs * 64));
+        if self.text_offset.is_some() {
+            self.write_legacy(&mut out, samples);
+            return out;
+        }
+        out.push_str(r#"{"content":["#);
+        for (i, (token, sample)) in samples.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_entry(&mut out, token, sample);
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Sample the log probability of `token` and its alternatives
+    fn sample<'a>(&mut self, token: &'a str, vocab: &[&'a str]) -> Sample<'a> {
+        // Skew towards 0 so most tokens look confident, but never certain so
+        // there's probability left for the alternatives
+        let sampled: f64 = self.rng.random();
+        let logprob = (-(sampled * sampled) * 2.0).min(-1e-4);
+        let mut top = Vec::with_capacity(self.top_logprobs);
+        if self.top_logprobs > 0 {
+            // The emitted token is always the most likely one
+            top.push((token, logprob));
+            // Each alternative takes a share of what the tokens before it left,
+            // so the probabilities never add up to more than 1
+            let mut remaining = 1.0 - logprob.exp();
+            let mut previous = logprob.exp();
+            let mut attempts = self.top_logprobs * 8;
+            while top.len() < self.top_logprobs && !vocab.is_empty() && attempts > 0 {
+                attempts -= 1;
+                let candidate = vocab[self.rng.random_range(0..vocab.len())];
+                if top.iter().any(|(seen, _)| *seen == candidate) {
+                    continue;
+                }
+                let probability = (remaining * self.rng.random_range(0.2..0.8)).min(previous);
+                remaining -= probability;
+                previous = probability;
+                top.push((candidate, probability.ln()));
+            }
+        }
+        Sample { logprob, top }
+    }
+
+    /// Write `{"tokens":[..],"token_logprobs":[..],"top_logprobs":[..],"text_offset":[..]}`.
+    /// Each `top_logprobs` entry maps tokens to log probabilities, and always
+    /// holds the emitted token.
+    fn write_legacy(&mut self, out: &mut String, samples: &[(&str, Sample)]) {
+        out.push_str(r#"{"tokens":["#);
+        for (i, (token, _)) in samples.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(token);
+        }
+        out.push_str(r#"],"token_logprobs":["#);
+        for (i, (_, sample)) in samples.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{:.6}", sample.logprob);
+        }
+        out.push_str(r#"],"top_logprobs":["#);
+        for (i, (token, sample)) in samples.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push('{');
+            if sample.top.is_empty() {
+                let _ = write!(out, "{}:{:.6}", token, sample.logprob);
+            }
+            for (j, (candidate, logprob)) in sample.top.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{}:{:.6}", candidate, logprob);
+            }
+            out.push('}');
+        }
+        out.push_str(r#"],"text_offset":["#);
+        let text_offset = self.text_offset.get_or_insert(0);
+        for (i, (token, _)) in samples.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}", text_offset);
+            *text_offset += decode(token).chars().count();
+        }
+        out.push_str("]}");
+    }
+}
+
+/// Write one entry of `logprobs.content`. `token` is a JSON string literal.
+fn write_entry(out: &mut String, token: &str, sample: &Sample) {
+    write_token(out, token, sample.logprob);
+    out.push_str(r#","top_logprobs":["#);
+    for (i, (candidate, logprob)) in sample.top.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_token(out, candidate, *logprob);
+        out.push('}');
+    }
+    out.push_str("]}");
+}
+
+/// The text of `token`, a JSON string literal
+fn decode(token: &str) -> String {
+    serde_json::from_str(token).unwrap_or_default()
+}
+
+/// Write `{"token":..,"logprob":..,"bytes":[..]` without the closing brace
+fn write_token(out: &mut String, token: &str, logprob: f64) {
+    out.push_str(r#"{"token":"#);
+    out.push_str(token);
+    let _ = write!(out, r#","logprob":{:.6},"bytes":["#, logprob);
+    for (i, byte) in decode(token).bytes().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(out, "{}", byte);
+    }
+    out.push(']');
+}
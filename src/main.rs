@@ -1,5 +1,15 @@
-use clap::Parser;
-use mock_openai::start_server;
+use mock_openai::args::Command;
+use mock_openai::auth::{KeyConfig, KeyStore};
+use mock_openai::config::{self, Config, SharedConfig};
+use mock_openai::health::Health;
+use mock_openai::journal::Journal;
+use mock_openai::metrics::Metrics;
+use mock_openai::request_log::{Cancellations, RequestLogger};
+use mock_openai::shutdown::Shutdown;
+use mock_openai::tls;
+use mock_openai::{AppState, start_server};
+use std::sync::Arc;
+use tokio_rustls::rustls::ServerConfig;
 
 fn init_logger() {
     env_logger::Builder::from_default_env()
@@ -7,24 +17,79 @@ fn init_logger() {
         .init();
 }
 
+fn invalid_config(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+/// TLS config from the --tls-* options, `None` to serve plain HTTP
+fn tls_config(args: &mock_openai::args::Args) -> std::io::Result<Option<Arc<ServerConfig>>> {
+    match (&args.tls_cert, &args.tls_key) {
+        (Some(_), _) | (_, Some(_)) if args.tls_self_signed => Err(invalid_config(
+            "--tls-self-signed can't be combined with --tls-cert and --tls-key".to_string(),
+        )),
+        (Some(cert), Some(key)) => {
+            log::info!("Loading TLS certificate {}", cert.display());
+            tls::load(cert, key).map(Some)
+        }
+        (Some(_), None) | (None, Some(_)) => Err(invalid_config(
+            "--tls-cert and --tls-key must be given together".to_string(),
+        )),
+        (None, None) if args.tls_self_signed => {
+            let mut hosts = vec![
+                "localhost".to_string(),
+                "127.0.0.1".to_string(),
+                "::1".to_string(),
+            ];
+            let unspecified = args
+                .address
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_unspecified());
+            if !unspecified && !hosts.contains(&args.address) {
+                hosts.push(args.address.clone());
+            }
+            log::info!(
+                "Generating a self-signed certificate for {}",
+                hosts.join(", ")
+            );
+            tls::self_signed(hosts, &args.tls_ca_file).map(Some)
+        }
+        (None, None) => Ok(None),
+    }
+}
+
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    let args = mock_openai::args::Args::parse();
+    let mut args = mock_openai::args::Args::parse_explicit();
 
     // Initialize logger
     init_logger();
 
+    match args.command.take() {
+        Some(Command::Bench(bench)) => return mock_openai::bench::run(bench).await,
+        Some(Command::Conformance(conformance)) => {
+            if !mock_openai::conformance::run(conformance).await? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
     log::info!("Starting mock-openai server v{}", env!("CARGO_PKG_VERSION"));
+    if let Some(path) = &args.config {
+        log::info!("Loading config file {}", path.display());
+        let (server, _) = config::read_file(path).map_err(invalid_config)?;
+        args.merge_server(server).map_err(invalid_config)?;
+    }
     let timeout: std::time::Duration = args.client_request_timeout.into();
 
     log::info!(
-        "Configuration: address={}, port={}, workers={}, max_connection_rate={}, timeout={:?}, inter_token_latency={}ms",
+        "Configuration: address={}, port={}, workers={}, max_connection_rate={}, timeout={:?}",
         args.address,
         args.port,
         args.workers,
         args.max_connection_rate,
         timeout,
-        args.inter_token_latency
     );
 
     log::info!(
@@ -32,12 +97,72 @@ async fn main() -> std::io::Result<()> {
         mock_openai::generated::MAX_TOKENS
     );
 
-    log::info!("Starting server on {}:{}", args.address, args.port);
-    start_server(
-        &args.address,
-        args.port,
-        args.token,
-        args.inter_token_latency,
-    )
-    .await
+    // Keys from --keys-file and --token come on top of the config file's
+    let mut extra_keys = match &args.keys_file {
+        Some(path) => KeyStore::load(path)?,
+        None => Vec::new(),
+    };
+    if let Some(token) = args.token.clone() {
+        extra_keys.push(KeyConfig {
+            key: token,
+            name: "default".to_string(),
+            models: Vec::new(),
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            token_quota: None,
+        });
+    }
+
+    let request_log = match &args.request_log {
+        Some(path) => {
+            log::info!("Writing request log to {}", path.display());
+            Some(RequestLogger::open(path).await?)
+        }
+        None => None,
+    };
+
+    let tls = tls_config(&args)?;
+    let binds = args.binds();
+    let ready_file = args.ready_file.clone();
+    let journal_size = args.journal_size;
+    let drain_timeout = args.drain_timeout.into();
+    let warmup = args.warmup.into();
+    let config_path = args.config.clone();
+    let admin_token = args.admin_token.clone().map(Arc::from);
+    let load_config = move || -> Result<Config, String> {
+        let mut config = match &args.config {
+            Some(path) => config::read_file(path)?.1,
+            None => Config::default(),
+        };
+        args.override_config(&mut config);
+        config.keys.extend(extra_keys.iter().cloned());
+        Ok(config)
+    };
+    let config = load_config().map_err(invalid_config)?;
+    log::info!(
+        "Runtime config: inter_token_latency={:?}, time_to_first_token={:?}, offset_mode={:?}, compat={}, profiles={}",
+        config.inter_token_latency,
+        config.time_to_first_token,
+        config.offset_mode,
+        config.compat.as_str(),
+        config.profiles.len()
+    );
+    let config = SharedConfig::new(config).map_err(|err| invalid_config(err.message))?;
+    let config = Arc::new(config);
+    if let Some(path) = config_path {
+        config::watch(path, config.clone(), load_config);
+    }
+
+    let app_state = AppState {
+        config,
+        metrics: Arc::new(Metrics::default()),
+        request_log,
+        journal: Arc::new(Journal::new(journal_size)),
+        history: None,
+        cancellations: Arc::new(Cancellations::default()),
+        admin_token,
+        shutdown: Arc::new(Shutdown::new(drain_timeout)),
+        health: Arc::new(Health::new(warmup)),
+    };
+    start_server(&binds, tls, ready_file.as_deref(), app_state).await
 }
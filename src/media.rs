@@ -0,0 +1,279 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+
+use crate::error::ApiError;
+
+/// Largest image OpenAI accepts
+pub const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+/// Largest request body: an image at the limit once base64 makes it a third
+/// bigger, with room to spare for the rest of the request
+pub const MAX_BODY_BYTES: usize = MAX_IMAGE_BYTES / 3 * 4 + 1024 * 1024;
+/// Tokens every image costs, and all a `detail: low` image costs
+const IMAGE_BASE_TOKENS: usize = 85;
+/// Tokens for each 512px tile of a `detail: high` image
+const IMAGE_TILE_TOKENS: usize = 170;
+const IMAGE_TILE_SIZE: f64 = 512.0;
+/// Side of a remote image, which is never downloaded, so it fills one tile
+const REMOTE_IMAGE_SIZE: u32 = 512;
+/// Input audio tokens per second, one for every 100ms
+const AUDIO_TOKENS_PER_SECOND: f64 = 10.0;
+
+const IMAGE_FORMATS: &str = "['png', 'jpeg', 'gif', 'webp']";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Detail {
+    Low,
+    High,
+}
+
+/// Prompt tokens of the image and audio parts in `messages`.
+///
+/// Data URLs are decoded and checked, and the first part that isn't a valid
+/// image or audio clip is rejected the way OpenAI rejects it.
+pub fn count_tokens(messages: &Value) -> Result<usize, ApiError> {
+    let Some(messages) = messages.as_array() else {
+        return Ok(0);
+    };
+    let mut tokens = 0;
+    for (i, message) in messages.iter().enumerate() {
+        let Some(parts) = message.get("content").and_then(Value::as_array) else {
+            continue;
+        };
+        for (j, part) in parts.iter().enumerate() {
+            let param = format!("messages.[{}].content.[{}]", i, j);
+            tokens += match part.get("type").and_then(Value::as_str) {
+                Some("image_url") => image_part_tokens(part, &param)?,
+                Some("input_audio") => audio_part_tokens(part, &param)?,
+                _ => 0,
+            };
+        }
+    }
+    Ok(tokens)
+}
+
+fn image_part_tokens(part: &Value, param: &str) -> Result<usize, ApiError> {
+    let image_url = part.get("image_url");
+    // Some clients send the URL on its own rather than in an object
+    let url = image_url
+        .and_then(|image_url| image_url.get("url").or(Some(image_url)))
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let detail = match image_url
+        .and_then(|image_url| image_url.get("detail"))
+        .and_then(Value::as_str)
+    {
+        Some("low") => Detail::Low,
+        // `auto` picks by the image, which comes out as `high` for most
+        _ => Detail::High,
+    };
+    let param = format!("{}.image_url.url", param);
+    let (width, height) = if url.starts_with("https://") || url.starts_with("http://") {
+        (REMOTE_IMAGE_SIZE, REMOTE_IMAGE_SIZE)
+    } else if let Some(data_url) = url.strip_prefix("data:") {
+        image_size(data_url, &param)?
+    } else {
+        return Err(ApiError::invalid_request(
+            "Invalid image URL. Provide an http(s) URL or a base64-encoded data URL.",
+            Some(param),
+            Some("invalid_image_url"),
+        ));
+    };
+    Ok(image_tokens(width, height, detail))
+}
+
+/// OpenAI's tile formula: a `high` image is scaled to fit in 2048x2048, then
+/// down to 768px on its shortest side, and costs a fixed amount per 512px tile
+fn image_tokens(width: u32, height: u32, detail: Detail) -> usize {
+    if detail == Detail::Low {
+        return IMAGE_BASE_TOKENS;
+    }
+    let (mut width, mut height) = (width as f64, height as f64);
+    let longest = width.max(height);
+    if longest > 2048.0 {
+        width *= 2048.0 / longest;
+        height *= 2048.0 / longest;
+    }
+    let shortest = width.min(height);
+    if shortest > 768.0 {
+        width *= 768.0 / shortest;
+        height *= 768.0 / shortest;
+    }
+    let tiles = (width / IMAGE_TILE_SIZE).ceil() * (height / IMAGE_TILE_SIZE).ceil();
+    IMAGE_BASE_TOKENS + IMAGE_TILE_TOKENS * tiles as usize
+}
+
+/// Width and height of the image in a data URL, after the `data:`
+fn image_size(data_url: &str, param: &str) -> Result<(u32, u32), ApiError> {
+    let invalid = |message: &str| {
+        ApiError::invalid_request(message, Some(param.to_string()), Some("invalid_image"))
+    };
+    let Some((media_type, data)) = data_url.split_once(";base64,") else {
+        return Err(invalid(
+            "Invalid image data URL. Expected data:image/<format>;base64,<data>.",
+        ));
+    };
+    if !matches!(
+        media_type,
+        "image/png" | "image/jpeg" | "image/jpg" | "image/gif" | "image/webp"
+    ) {
+        return Err(ApiError::invalid_request(
+            format!(
+                "You uploaded an unsupported image. Please make sure your image has of one the following formats: {}.",
+                IMAGE_FORMATS
+            ),
+            Some(param.to_string()),
+            Some("invalid_image_format"),
+        ));
+    }
+    // Base64 is a third bigger than the bytes, so this is caught before decoding
+    if data.len() / 4 * 3 > MAX_IMAGE_BYTES {
+        return Err(invalid(&format!(
+            "Image is too large. Images must be smaller than {} MB.",
+            MAX_IMAGE_BYTES / 1024 / 1024
+        )));
+    }
+    let bytes = STANDARD
+        .decode(data)
+        .map_err(|_| invalid("Invalid image. The base64 data could not be decoded."))?;
+    decode_size(&bytes).ok_or_else(|| {
+        invalid("You uploaded an unsupported image. Please make sure your image is valid.")
+    })
+}
+
+/// Width and height from a PNG, JPEG, GIF or WebP header
+fn decode_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    let le16 = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32);
+    let be32 = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let le24 = |at: usize| {
+        let b = bytes.get(at..at + 3)?;
+        Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+    };
+    let size = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        (be32(16)?, be32(20)?)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        (le16(6)?, le16(8)?)
+    } else if bytes.starts_with(b"\xff\xd8") {
+        jpeg_size(bytes)?
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        match bytes.get(12..16)? {
+            b"VP8 " => (le16(26)? & 0x3fff, le16(28)? & 0x3fff),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                ((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1)
+            }
+            b"VP8X" => (le24(24)? + 1, le24(27)? + 1),
+            _ => return None,
+        }
+    } else {
+        return None;
+    };
+    (size.0 > 0 && size.1 > 0).then_some(size)
+}
+
+/// Size from the first start-of-frame segment of a JPEG
+fn jpeg_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    let mut at = 2;
+    loop {
+        if *bytes.get(at)? != 0xff {
+            return None;
+        }
+        let marker = *bytes.get(at + 1)?;
+        let length = u16::from_be_bytes(bytes.get(at + 2..at + 4)?.try_into().ok()?) as usize;
+        // SOF0 to SOF15, except DHT, JPG and DAC which share the range
+        if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            let height = u16::from_be_bytes(bytes.get(at + 5..at + 7)?.try_into().ok()?);
+            let width = u16::from_be_bytes(bytes.get(at + 7..at + 9)?.try_into().ok()?);
+            return Some((width as u32, height as u32));
+        }
+        at += 2 + length;
+    }
+}
+
+fn audio_part_tokens(part: &Value, param: &str) -> Result<usize, ApiError> {
+    let audio = part.get("input_audio");
+    let format = audio
+        .and_then(|audio| audio.get("format"))
+        .and_then(Value::as_str);
+    if !matches!(format, Some("wav" | "mp3")) {
+        return Err(ApiError::invalid_request(
+            format!(
+                "Invalid value: {:?}. Supported values are: 'wav' and 'mp3'.",
+                format.unwrap_or_default()
+            ),
+            Some(format!("{}.input_audio.format", param)),
+            Some("invalid_value"),
+        ));
+    }
+    let invalid = || {
+        ApiError::invalid_request(
+            "The audio data could not be decoded. Provide base64-encoded wav or mp3 audio.",
+            Some(format!("{}.input_audio.data", param)),
+            Some("invalid_value"),
+        )
+    };
+    let data = audio
+        .and_then(|audio| audio.get("data"))
+        .and_then(Value::as_str)
+        .ok_or_else(invalid)?;
+    let bytes = STANDARD.decode(data).map_err(|_| invalid())?;
+    let seconds = match format {
+        Some("wav") => wav_seconds(&bytes),
+        _ => mp3_seconds(&bytes),
+    }
+    .ok_or_else(invalid)?;
+    Ok((seconds * AUDIO_TOKENS_PER_SECOND).ceil() as usize)
+}
+
+/// Length of a WAV file from its byte rate and the size of its data
+fn wav_seconds(bytes: &[u8]) -> Option<f64> {
+    if !bytes.starts_with(b"RIFF") || bytes.get(8..12)? != b"WAVE" {
+        return None;
+    }
+    let mut byte_rate = None;
+    let mut at = 12;
+    while let Some(id) = bytes.get(at..at + 4) {
+        let size = u32::from_le_bytes(bytes.get(at + 4..at + 8)?.try_into().ok()?) as usize;
+        match id {
+            b"fmt " => {
+                byte_rate = Some(u32::from_le_bytes(
+                    bytes.get(at + 16..at + 20)?.try_into().ok()?,
+                ));
+            }
+            // Streamed WAVs may leave the size unset, so trust the file length
+            b"data" => {
+                let size = size.min(bytes.len() - at - 8);
+                return Some(size as f64 / byte_rate.filter(|&rate| rate > 0)? as f64);
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size
+        at += 8 + size + size % 2;
+    }
+    None
+}
+
+/// Length of an MP3 file, estimated from the bitrate of its first frame
+fn mp3_seconds(bytes: &[u8]) -> Option<f64> {
+    // Skip an ID3v2 tag, whose size is stored 7 bits to a byte
+    let mut audio = bytes;
+    if bytes.starts_with(b"ID3") {
+        let size = bytes
+            .get(6..10)?
+            .iter()
+            .fold(0usize, |size, &b| size << 7 | (b & 0x7f) as usize);
+        audio = bytes.get(10 + size..)?;
+    }
+    let start = audio
+        .windows(2)
+        .position(|w| w[0] == 0xff && w[1] & 0xe0 == 0xe0)?;
+    let header = audio.get(start..start + 4)?;
+    // MPEG-1 Layer III bitrates in kbps, the most common by far
+    const BITRATES: [u32; 15] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ];
+    let kbps = *BITRATES
+        .get((header[2] >> 4) as usize)
+        .filter(|&&kbps| kbps > 0)?;
+    Some((audio.len() - start) as f64 * 8.0 / (kbps as f64 * 1000.0))
+}
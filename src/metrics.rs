@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Minimal counter registry rendered in the Prometheus text format.
+///
+/// Counters are keyed by name and a pre-rendered label set, which is plenty for
+/// a mock server and saves pulling in a metrics crate.
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<(&'static str, String), u64>>,
+}
+
+impl Metrics {
+    /// Add `by` to the counter `name` with the given labels
+    pub fn inc(&self, name: &'static str, labels: &[(&str, &str)], by: u64) {
+        let mut rendered = String::new();
+        for (i, (key, value)) in labels.iter().enumerate() {
+            if i > 0 {
+                rendered.push(',');
+            }
+            let _ = write!(rendered, "{}=\"{}\"", key, escape_label(value));
+        }
+        let mut counters = self.counters.lock().unwrap();
+        *counters.entry((name, rendered)).or_default() += by;
+    }
+
+    pub fn render(&self) -> String {
+        let counters = self.counters.lock().unwrap();
+        let mut out = String::new();
+        let mut last_name = "";
+        for ((name, labels), value) in counters.iter() {
+            if *name != last_name {
+                let _ = writeln!(out, "# TYPE {} counter", name);
+                last_name = name;
+            }
+            if labels.is_empty() {
+                let _ = writeln!(out, "{} {}", name, value);
+            } else {
+                let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+            }
+        }
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
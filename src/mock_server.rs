@@ -0,0 +1,319 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+use crate::auth::KeyConfig;
+use crate::compat::Compat;
+use crate::config::{Config, Faults};
+use crate::corpus::OffsetMode;
+use crate::expect::Expectation;
+use crate::health::Health;
+use crate::journal::{Journal, ReceivedRequest};
+use crate::listen::Bound;
+use crate::profile::{Latency, Profile};
+use crate::request_log::{RequestHistory, RequestRecord};
+use crate::routes::AppState;
+use crate::shutdown::Shutdown;
+
+/// A mock server running inside the current tokio runtime, for integration
+/// tests that want their own isolated server without spawning the binary.
+///
+/// ```no_run
+/// # use mock_openai::expect::Expectation;
+/// # async fn example() -> std::io::Result<()> {
+/// let server = mock_openai::MockServer::builder()
+///     .itl(0)
+///     .model("x")
+///     .start()
+///     .await?;
+/// let url = format!("{}/chat/completions", server.base_url());
+/// // ... send requests to `url` ...
+/// server.expect(Expectation::post("/v1/chat/completions").model("x").times(1));
+/// // ... send one request to `url` ...
+/// server.verify();
+/// server.shutdown().await
+/// # }
+/// ```
+///
+/// Dropping the handle stops the server and ends any streams still running,
+/// and panics if expectations haven't been met and weren't verified.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: AppState,
+    task: Option<JoinHandle<io::Result<()>>>,
+    expectations: Mutex<Vec<Expectation>>,
+    verified: AtomicBool,
+}
+
+impl MockServer {
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder::default()
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Base URL for OpenAI clients, ending in `/v1`
+    pub fn base_url(&self) -> String {
+        self.url("/v1")
+    }
+
+    /// URL of `path` on this server, e.g. `server.url("/health/ready")`
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// State shared by the server's handlers, to change the config or health while it runs
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// Requests received so far as the client sent them, oldest first. Requests
+    /// rejected for their API key are kept with their headers but no body.
+    pub fn requests(&self) -> Vec<ReceivedRequest> {
+        self.state.journal.list()
+    }
+
+    /// Received requests that match `expectation`, whatever its count
+    pub fn matching(&self, expectation: &Expectation) -> Vec<ReceivedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| expectation.matches(request))
+            .collect()
+    }
+
+    /// Outcomes of the requests completed so far, as written to the request log
+    pub fn records(&self) -> Vec<RequestRecord> {
+        self.history().list()
+    }
+
+    /// Forget the requests received so far
+    pub fn clear_requests(&self) {
+        self.state.journal.clear();
+        self.history().clear();
+    }
+
+    /// Expect requests to have been received by the time of [`MockServer::verify`]
+    pub fn expect(&self, expectation: Expectation) {
+        self.expectations.lock().unwrap().push(expectation);
+        self.verified.store(false, Ordering::Relaxed);
+    }
+
+    /// Check every expectation against the requests received so far, with a
+    /// report of how the requests differ from each unmet expectation
+    pub fn try_verify(&self) -> Result<(), String> {
+        self.verified.store(true, Ordering::Relaxed);
+        let requests = self.requests();
+        let failures: Vec<String> = self
+            .expectations
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|expectation| expectation.check(&requests))
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("\n\n"))
+        }
+    }
+
+    /// Panic unless every expectation is met
+    pub fn verify(&self) {
+        if let Err(report) = self.try_verify() {
+            panic!("Unmet expectations on the mock server:\n\n{}", report);
+        }
+    }
+
+    fn history(&self) -> &RequestHistory {
+        self.state
+            .history
+            .as_deref()
+            .expect("MockServer always keeps a request history")
+    }
+
+    /// Stop accepting connections, give running streams the drain timeout to
+    /// finish, and wait for the server to stop
+    pub async fn shutdown(mut self) -> io::Result<()> {
+        self.state.shutdown.drain().await;
+        match self.task.take() {
+            Some(task) => task.await.map_err(io::Error::other)?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if self.task.is_some() {
+            self.state.shutdown.close();
+        }
+        // A second panic while unwinding would abort the test run
+        if !self.verified.load(Ordering::Relaxed) && !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+/// Options for a [`MockServer`]. By default it listens on an ephemeral port on
+/// 127.0.0.1, with no latency, no authentication and the built-in corpus.
+pub struct MockServerBuilder {
+    address: String,
+    config: Config,
+    admin_token: Option<Arc<str>>,
+    warmup: Duration,
+    drain_timeout: Duration,
+}
+
+impl Default for MockServerBuilder {
+    fn default() -> Self {
+        MockServerBuilder {
+            address: "127.0.0.1:0".to_string(),
+            config: Config {
+                inter_token_latency: Latency::default(),
+                ..Config::default()
+            },
+            admin_token: None,
+            warmup: Duration::ZERO,
+            drain_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl MockServerBuilder {
+    /// Address to listen on, `127.0.0.1:0` by default
+    pub fn address(mut self, address: impl Into<String>) -> Self {
+        self.address = address.into();
+        self
+    }
+
+    /// Runtime config to start from, in place of everything set so far
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Fixed delay between streamed tokens in milliseconds
+    pub fn itl(self, millis: u64) -> Self {
+        self.inter_token_latency(Latency::Fixed(millis))
+    }
+
+    pub fn inter_token_latency(mut self, latency: Latency) -> Self {
+        self.config.inter_token_latency = latency;
+        self
+    }
+
+    /// Fixed delay before the first token in milliseconds
+    pub fn ttft(self, millis: u64) -> Self {
+        self.time_to_first_token(Latency::Fixed(millis))
+    }
+
+    pub fn time_to_first_token(mut self, latency: Latency) -> Self {
+        self.config.time_to_first_token = latency;
+        self
+    }
+
+    /// Add a model to `/v1/models`
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.config.models.push(model.into());
+        self
+    }
+
+    /// Give `model` its own behaviour, which also lists it in `/v1/models`
+    pub fn profile(mut self, model: impl Into<String>, profile: Profile) -> Self {
+        self.config.profiles.insert(model.into(), profile);
+        self
+    }
+
+    /// Require a bearer token, which can be called more than once
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        let name = format!("key-{}", self.config.keys.len());
+        self.config.keys.push(KeyConfig {
+            key: key.into(),
+            name,
+            models: Vec::new(),
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            token_quota: None,
+        });
+        self
+    }
+
+    /// Token for `/admin`, which is otherwise closed once a key is configured
+    pub fn admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(Arc::from(token.into()));
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    /// Imitate the responses of `compat` instead of vLLM
+    pub fn compat(mut self, compat: Compat) -> Self {
+        self.config.compat = compat;
+        self
+    }
+
+    /// Reject image and audio content parts sent to `model`, which can be
+    /// called more than once
+    pub fn text_only_model(mut self, model: impl Into<String>) -> Self {
+        self.config.text_only_models.push(model.into());
+        self
+    }
+
+    pub fn offset_mode(mut self, mode: OffsetMode) -> Self {
+        self.config.offset_mode = mode;
+        self
+    }
+
+    pub fn faults(mut self, faults: Faults) -> Self {
+        self.config.faults = faults;
+        self
+    }
+
+    /// Simulated model loading time after startup
+    pub fn warmup(mut self, warmup: Duration) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// How long [`MockServer::shutdown`] waits for running streams
+    pub fn drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    /// Bind and start serving in the background
+    pub async fn start(self) -> io::Result<MockServer> {
+        let mut state = AppState::new(self.config)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.message))?;
+        state.journal = Arc::new(Journal::new(usize::MAX));
+        state.history = Some(Arc::new(RequestHistory::default()));
+        state.admin_token = self.admin_token;
+        state.shutdown = Arc::new(Shutdown::new(self.drain_timeout));
+        state.health = Arc::new(Health::new(self.warmup));
+        let listener = TcpListener::bind(&self.address).await?;
+        let addr = listener.local_addr()?;
+        let app = crate::router(state.clone());
+        let task = tokio::spawn(crate::serve(
+            Bound::Tcp(listener),
+            app,
+            None,
+            state.shutdown.clone(),
+        ));
+        Ok(MockServer {
+            addr,
+            state,
+            task: Some(task),
+            expectations: Mutex::new(Vec::new()),
+            verified: AtomicBool::new(true),
+        })
+    }
+}
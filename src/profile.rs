@@ -0,0 +1,175 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::compat::Compat;
+use crate::config::{Config, Faults};
+use crate::corpus::Corpus;
+use crate::reasoning::ReasoningConfig;
+
+/// A delay in milliseconds, either fixed or drawn again for every token.
+///
+/// Written as a plain number, `{ min = 5, max = 20 }` or `{ mean = 10, stddev = 3 }`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Latency {
+    Fixed(u64),
+    Uniform { min: u64, max: u64 },
+    Normal { mean: f64, stddev: f64 },
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Latency::Fixed(0)
+    }
+}
+
+impl From<u64> for Latency {
+    fn from(millis: u64) -> Self {
+        Latency::Fixed(millis)
+    }
+}
+
+impl Latency {
+    pub fn sample(&self) -> u64 {
+        match *self {
+            Latency::Fixed(millis) => millis,
+            Latency::Uniform { min, max } => rand::rng().random_range(min..=max),
+            Latency::Normal { mean, stddev } => {
+                // Box-Muller, which saves pulling in rand_distr for one distribution
+                let mut rng = rand::rng();
+                let u1: f64 = 1.0 - rng.random::<f64>();
+                let u2: f64 = rng.random();
+                let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+                (mean + stddev * z).max(0.0).round() as u64
+            }
+        }
+    }
+
+    pub(crate) fn validate(&self, param: &str) -> Result<(), String> {
+        match *self {
+            Latency::Uniform { min, max } if min > max => Err(format!(
+                "{}: min ({}) must not be greater than max ({})",
+                param, min, max
+            )),
+            Latency::Normal { mean, stddev } if mean < 0.0 || stddev < 0.0 => {
+                Err(format!("{}: mean and stddev must not be negative", param))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Behaviour of one model. Fields left out take the top-level config's value.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub inter_token_latency: Option<Latency>,
+    pub time_to_first_token: Option<Latency>,
+    /// Longest prompt plus completion the model accepts, in tokens
+    pub context_length: Option<usize>,
+    /// Plain text file to draw this model's responses from
+    pub corpus: Option<PathBuf>,
+    /// Characters per token when estimating prompt tokens. There's no tokenizer
+    /// at runtime, so this stands in for the model's tokenizer.
+    pub chars_per_token: Option<usize>,
+    /// Output length when the request doesn't set `max_tokens`
+    pub default_max_tokens: Option<usize>,
+    /// Cap on the output length, whatever the request asks for
+    pub max_output_tokens: Option<usize>,
+    /// `ignore_eos` for requests that don't set it. `false` ends outputs at a sentence boundary.
+    pub ignore_eos: Option<bool>,
+    pub faults: Option<Faults>,
+    /// Reasoning tokens at `medium` effort. Setting this makes the model always reason.
+    pub reasoning_tokens: Option<usize>,
+    /// Whether requests may pass `tools`
+    pub tools: Option<bool>,
+    /// Whether messages may contain images
+    pub vision: Option<bool>,
+    /// Backend whose response fields to imitate
+    pub compat: Option<Compat>,
+}
+
+/// Everything a request needs to know about its model, the model's
+/// [`Profile`] filled in from the top-level config
+#[derive(Clone, Debug)]
+pub struct ModelProfile {
+    pub inter_token_latency: Latency,
+    pub time_to_first_token: Latency,
+    pub context_length: Option<usize>,
+    pub corpus: Corpus,
+    pub chars_per_token: usize,
+    pub default_max_tokens: Option<usize>,
+    pub max_output_tokens: usize,
+    pub ignore_eos: Option<bool>,
+    pub faults: Faults,
+    pub reasoning: ReasoningConfig,
+    pub tools: bool,
+    pub vision: bool,
+    /// Models that take text only, rejecting image and audio parts
+    pub text_only_models: Vec<String>,
+    pub compat: Compat,
+}
+
+impl ModelProfile {
+    /// The profile for models without one of their own
+    pub fn default_for(config: &Config, corpus: Corpus) -> Self {
+        ModelProfile {
+            inter_token_latency: config.inter_token_latency,
+            time_to_first_token: config.time_to_first_token,
+            context_length: None,
+            corpus,
+            chars_per_token: crate::prompt::CHARS_PER_TOKEN,
+            default_max_tokens: None,
+            max_output_tokens: corpus.max_tokens(),
+            ignore_eos: None,
+            faults: config.faults.clone(),
+            reasoning: config.reasoning.clone(),
+            tools: true,
+            vision: true,
+            text_only_models: config.text_only_models.clone(),
+            compat: config.compat,
+        }
+    }
+
+    /// `profile` for `model` on top of the default profile
+    pub fn resolve(default: &ModelProfile, model: &str, profile: &Profile, corpus: Corpus) -> Self {
+        let mut reasoning = default.reasoning.clone();
+        if let Some(tokens) = profile.reasoning_tokens {
+            reasoning.tokens = tokens;
+            reasoning.models = vec![model.to_string()];
+        }
+        ModelProfile {
+            inter_token_latency: profile
+                .inter_token_latency
+                .unwrap_or(default.inter_token_latency),
+            time_to_first_token: profile
+                .time_to_first_token
+                .unwrap_or(default.time_to_first_token),
+            context_length: profile.context_length,
+            corpus,
+            chars_per_token: profile.chars_per_token.unwrap_or(default.chars_per_token),
+            default_max_tokens: profile.default_max_tokens,
+            max_output_tokens: profile
+                .max_output_tokens
+                .unwrap_or(usize::MAX)
+                .min(corpus.max_tokens()),
+            ignore_eos: profile.ignore_eos,
+            faults: profile.faults.clone().unwrap_or(default.faults.clone()),
+            reasoning,
+            tools: profile.tools.unwrap_or(true),
+            vision: profile.vision.unwrap_or(true),
+            // A profile that sets `vision` decides for itself
+            text_only_models: match profile.vision {
+                Some(_) => Vec::new(),
+                None => default.text_only_models.clone(),
+            },
+            compat: profile.compat.unwrap_or(default.compat),
+        }
+    }
+
+    /// Whether `model` is listed as text-only
+    pub fn text_only(&self, model: Option<&str>) -> bool {
+        model.is_some_and(|model| self.text_only_models.iter().any(|m| m == model))
+    }
+}
@@ -0,0 +1,138 @@
+use serde_json::Value;
+use std::iter;
+
+use crate::corpus;
+
+/// Roughly how many characters make up a token, OpenAI's rule of thumb for English
+pub const CHARS_PER_TOKEN: usize = 4;
+/// Tokens added around every chat message for the role and separators
+const TOKENS_PER_MESSAGE: usize = 3;
+/// Tokens priming the assistant's reply
+const REPLY_PRIMING_TOKENS: usize = 3;
+
+/// Ids handed out to chunks of prompt text, below the special tokens
+const TEXT_IDS: u64 = 100_000;
+/// Special tokens around chat messages, as in cl100k
+const MESSAGE_START_ID: u32 = 100_264;
+const MESSAGE_SEP_ID: u32 = 100_266;
+/// Stands in for every image and audio token, which have no text to hash
+const MEDIA_ID: u32 = 100_267;
+
+/// Estimate the prompt tokens of a request from its `messages` or `prompt`.
+///
+/// There is no tokenizer at runtime, so text is counted at `chars_per_token`
+/// characters per token, [`CHARS_PER_TOKEN`] unless a model profile says
+/// otherwise. Token id arrays in legacy completion prompts are counted exactly.
+pub fn count_tokens(prompt: &Value, chars_per_token: usize) -> usize {
+    match prompt {
+        // chat messages
+        Value::Array(items) if items.iter().any(Value::is_object) => {
+            items
+                .iter()
+                .map(|message| count_message(message, chars_per_token))
+                .sum::<usize>()
+                + REPLY_PRIMING_TOKENS
+        }
+        // a list of prompts, a list of token ids or a list of token id lists
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Number(_) => 1,
+                other => count_tokens(other, chars_per_token),
+            })
+            .sum(),
+        Value::String(text) => count_text(text, chars_per_token),
+        _ => 0,
+    }
+}
+
+fn count_message(message: &Value, chars_per_token: usize) -> usize {
+    let content = match message.get("content") {
+        Some(Value::String(text)) => count_text(text, chars_per_token),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .map(|text| count_text(text, chars_per_token))
+            .sum(),
+        _ => 0,
+    };
+    let name = message
+        .get("name")
+        .and_then(Value::as_str)
+        .map_or(0, |name| count_text(name, chars_per_token) + 1);
+    TOKENS_PER_MESSAGE + content + name
+}
+
+fn count_text(text: &str, chars_per_token: usize) -> usize {
+    text.chars().count().div_ceil(chars_per_token)
+}
+
+/// Token ids for a prompt, as many as [`count_tokens`] counts.
+///
+/// With no tokenizer at runtime these are made up: every `chars_per_token`
+/// characters of text hash to an id, and each message is wrapped in special
+/// tokens. The same prompt always gets the same ids. Token id arrays in legacy
+/// completion prompts are returned as they are. The `media_tokens` of any
+/// images and audio follow as placeholder ids, so there are as many ids as
+/// `usage.prompt_tokens` counts.
+pub fn token_ids(prompt: &Value, chars_per_token: usize, media_tokens: usize) -> Vec<u32> {
+    let mut ids = Vec::new();
+    push_ids(&mut ids, prompt, chars_per_token);
+    ids.extend(iter::repeat_n(MEDIA_ID, media_tokens));
+    ids
+}
+
+fn push_ids(ids: &mut Vec<u32>, prompt: &Value, chars_per_token: usize) {
+    match prompt {
+        Value::Array(items) if items.iter().any(Value::is_object) => {
+            for message in items {
+                push_message_ids(ids, message, chars_per_token);
+            }
+            ids.extend([MESSAGE_START_ID, text_id("assistant"), MESSAGE_SEP_ID]);
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    Value::Number(n) => ids.push(n.as_u64().unwrap_or(0) as u32),
+                    other => push_ids(ids, other, chars_per_token),
+                }
+            }
+        }
+        Value::String(text) => push_text_ids(ids, text, chars_per_token),
+        _ => {}
+    }
+}
+
+fn push_message_ids(ids: &mut Vec<u32>, message: &Value, chars_per_token: usize) {
+    let role = message.get("role").and_then(Value::as_str).unwrap_or("");
+    ids.extend([MESSAGE_START_ID, text_id(role), MESSAGE_SEP_ID]);
+    match message.get("content") {
+        Some(Value::String(text)) => push_text_ids(ids, text, chars_per_token),
+        Some(Value::Array(parts)) => {
+            for text in parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(Value::as_str))
+            {
+                push_text_ids(ids, text, chars_per_token);
+            }
+        }
+        _ => {}
+    }
+    if let Some(name) = message.get("name").and_then(Value::as_str) {
+        push_text_ids(ids, name, chars_per_token);
+        ids.push(MESSAGE_SEP_ID);
+    }
+}
+
+fn push_text_ids(ids: &mut Vec<u32>, text: &str, chars_per_token: usize) {
+    let chars: Vec<char> = text.chars().collect();
+    ids.extend(
+        chars
+            .chunks(chars_per_token)
+            .map(|chunk| text_id(&chunk.iter().collect::<String>())),
+    );
+}
+
+fn text_id(text: &str) -> u32 {
+    (corpus::hash_of(&text) % TEXT_IDS) as u32
+}
@@ -0,0 +1,66 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Reasoning tokens at `medium` effort unless configured otherwise
+pub const DEFAULT_REASONING_TOKENS: usize = 128;
+
+/// Which field carries the reasoning text
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReasoningField {
+    /// `reasoning_content`, as used by DeepSeek-R1 and vLLM
+    #[default]
+    ReasoningContent,
+    /// `reasoning`, as used by OpenAI-style reasoning models
+    Reasoning,
+}
+
+impl ReasoningField {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReasoningField::ReasoningContent => "reasoning_content",
+            ReasoningField::Reasoning => "reasoning",
+        }
+    }
+}
+
+/// Server-wide reasoning emulation settings
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReasoningConfig {
+    /// Models that always reason before answering
+    pub models: Vec<String>,
+    /// Reasoning tokens emitted at `medium` effort
+    pub tokens: usize,
+    pub field: ReasoningField,
+}
+
+impl Default for ReasoningConfig {
+    fn default() -> Self {
+        ReasoningConfig {
+            models: Vec::new(),
+            tokens: DEFAULT_REASONING_TOKENS,
+            field: ReasoningField::default(),
+        }
+    }
+}
+
+impl ReasoningConfig {
+    /// Number of reasoning tokens to emit before the answer.
+    ///
+    /// An explicit `reasoning_effort` turns reasoning on for any model and
+    /// scales the configured budget, otherwise only the listed models reason.
+    pub fn tokens_for(&self, model: Option<&str>, effort: Option<&str>) -> usize {
+        match effort {
+            Some("none") => 0,
+            Some("minimal") => self.tokens / 4,
+            Some("low") => self.tokens / 2,
+            Some("high") => self.tokens * 2,
+            Some(_) => self.tokens,
+            None if model.is_some_and(|model| self.models.iter().any(|m| m == model)) => {
+                self.tokens
+            }
+            None => 0,
+        }
+    }
+}
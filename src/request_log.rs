@@ -0,0 +1,202 @@
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+/// One line of the request log
+#[derive(Serialize, Clone, Debug)]
+pub struct RequestRecord {
+    /// Unix time the request arrived, in seconds
+    pub timestamp: f64,
+    pub route: String,
+    pub model: Option<String>,
+    pub key: String,
+    pub prompt_tokens: usize,
+    pub requested_tokens: Option<usize>,
+    pub output_tokens: usize,
+    pub stream: bool,
+    /// Time to the first streamed chunk, or to the whole response when not streaming
+    pub ttft_ms: Option<f64>,
+    pub duration_ms: f64,
+    pub status: u16,
+    /// Whether the client went away before the response was complete
+    pub disconnected: bool,
+}
+
+impl RequestRecord {
+    pub fn new(route: &str, key: &str) -> Self {
+        RequestRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |d| d.as_secs_f64()),
+            route: route.to_string(),
+            model: None,
+            key: key.to_string(),
+            prompt_tokens: 0,
+            requested_tokens: None,
+            output_tokens: 0,
+            stream: false,
+            ttft_ms: None,
+            duration_ms: 0.0,
+            status: 0,
+            disconnected: false,
+        }
+    }
+
+    /// Fill in the outcome of a request that arrived at `started`
+    pub fn complete(
+        mut self,
+        started: Instant,
+        status: StatusCode,
+        output_tokens: usize,
+        first_token: Option<Instant>,
+        disconnected: bool,
+    ) -> Self {
+        self.status = status.as_u16();
+        self.output_tokens = output_tokens;
+        self.ttft_ms = first_token.map(|t| millis(t.duration_since(started)));
+        self.duration_ms = millis(started.elapsed());
+        self.disconnected = disconnected;
+        self
+    }
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Appends [`RequestRecord`]s to a JSONL file.
+///
+/// Records are handed to a background task over a channel and written through
+/// a buffer, so logging never blocks the request being measured.
+#[derive(Clone)]
+pub struct RequestLogger {
+    tx: mpsc::UnboundedSender<Message>,
+}
+
+enum Message {
+    Record(RequestRecord),
+    /// Answered once everything sent before it is written and flushed
+    Flush(oneshot::Sender<()>),
+}
+
+impl RequestLogger {
+    /// Open `path` for appending and start the writer task
+    pub async fn open(path: &Path) -> std::io::Result<Self> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(write_records(BufWriter::new(file), rx));
+        Ok(RequestLogger { tx })
+    }
+
+    pub fn log(&self, record: RequestRecord) {
+        if self.tx.send(Message::Record(record)).is_err() {
+            log::error!("Request log writer has stopped, dropping record");
+        }
+    }
+
+    /// Wait until every record logged so far is on disk, e.g. before exiting
+    pub async fn flush(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.tx.send(Message::Flush(done)).is_ok() {
+            let _ = flushed.await;
+        }
+    }
+}
+
+async fn write_records(
+    mut writer: BufWriter<tokio::fs::File>,
+    mut rx: mpsc::UnboundedReceiver<Message>,
+) {
+    let mut line = Vec::with_capacity(512);
+    let mut waiting = Vec::new();
+    while let Some(message) = rx.recv().await {
+        // Write whatever has queued up, then flush once per batch
+        let mut next = Some(message);
+        while let Some(message) = next {
+            match message {
+                Message::Record(record) => {
+                    line.clear();
+                    if serde_json::to_writer(&mut line, &record).is_ok() {
+                        line.push(b'\n');
+                        if let Err(e) = writer.write_all(&line).await {
+                            log::error!("Failed to write request log: {}", e);
+                        }
+                    }
+                }
+                Message::Flush(done) => waiting.push(done),
+            }
+            next = rx.try_recv().ok();
+        }
+        flush(&mut writer).await;
+        for done in waiting.drain(..) {
+            let _ = done.send(());
+        }
+    }
+    // Every logger is gone, write out anything still buffered
+    flush(&mut writer).await;
+}
+
+async fn flush(writer: &mut BufWriter<tokio::fs::File>) {
+    if let Err(e) = writer.flush().await {
+        log::error!("Failed to flush request log: {}", e);
+    }
+}
+
+/// How many cancelled requests [`Cancellations`] remembers
+const MAX_CANCELLATIONS: usize = 256;
+
+/// The most recent requests whose client disconnected before the response was
+/// complete, oldest first, kept so tests can check that a cancel reached the server
+#[derive(Default)]
+pub struct Cancellations {
+    records: Mutex<VecDeque<RequestRecord>>,
+}
+
+impl Cancellations {
+    pub fn push(&self, record: RequestRecord) {
+        let mut records = self.records.lock().unwrap();
+        if records.len() == MAX_CANCELLATIONS {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    pub fn list(&self) -> Vec<RequestRecord> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+}
+
+/// Every completed request, oldest first. Only kept by embedded servers, see
+/// [`crate::MockServer::requests`], since it grows without bound
+#[derive(Default)]
+pub struct RequestHistory {
+    records: Mutex<Vec<RequestRecord>>,
+}
+
+impl RequestHistory {
+    pub fn push(&self, record: RequestRecord) {
+        self.records.lock().unwrap().push(record);
+    }
+
+    pub fn list(&self) -> Vec<RequestRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+}
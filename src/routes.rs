@@ -1,36 +1,341 @@
 use axum::{
-    extract::{Json, State},
+    Extension, Json,
+    extract::{FromRequest, OriginalUri, State},
     http::{StatusCode, header},
-    response::{IntoResponse, sse::Event, sse::Sse},
+    response::{IntoResponse, Response, sse::Event, sse::Sse},
 };
-use axum_extra::headers::authorization::{Authorization, Bearer};
-use axum_extra::typed_header::TypedHeader;
 use futures_util::Stream;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::time::{Duration, Instant};
 use tokio_stream::StreamExt;
 
-use crate::generated;
-use crate::stream::StringsStream;
-use crate::template::render_chat_completion;
+use crate::auth::ApiKey;
+use crate::azure::Deployment;
+use crate::config::{Config, LiveConfig, SharedConfig};
+use crate::corpus::{self, Corpus, OffsetMode};
+use crate::error::{ApiError, ApiJson};
+use crate::health::Health;
+use crate::journal::{DEFAULT_JOURNAL_SIZE, Journal};
+use crate::logprobs::{LogprobSampler, MAX_TOP_LOGPROBS};
+use crate::media;
+use crate::metrics::Metrics;
+use crate::profile::{Latency, ModelProfile};
+use crate::prompt;
+use crate::reasoning::ReasoningConfig;
+use crate::request_log::{Cancellations, RequestHistory, RequestLogger, RequestRecord};
+use crate::shutdown::{DEFAULT_DRAIN_TIMEOUT, Shutdown};
+use crate::stream::{StringsStream, TrackedStream};
+use crate::template::{FinishReason, Templates, Values};
+use crate::validate::{self, Endpoint};
 
-// Application state for holding the optional token
+// Application state shared by all handlers
 #[derive(Clone)]
 pub struct AppState {
-    pub token: Option<String>,
-    pub inter_token_latency: u64,
+    pub config: Arc<SharedConfig>,
+    pub metrics: Arc<Metrics>,
+    pub request_log: Option<RequestLogger>,
+    /// Requests as received on the API routes
+    pub journal: Arc<Journal>,
+    /// Completed requests, kept for [`crate::MockServer`]
+    pub history: Option<Arc<RequestHistory>>,
+    pub cancellations: Arc<Cancellations>,
+    /// Token for `/admin`, which falls back to the API keys when unset
+    pub admin_token: Option<Arc<str>>,
+    pub shutdown: Arc<Shutdown>,
+    pub health: Arc<Health>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Request {
+    model: Option<String>,
     max_tokens: Option<usize>,
+    max_completion_tokens: Option<usize>,
+    min_tokens: Option<usize>,
+    ignore_eos: Option<bool>,
     stream: Option<bool>,
     stream_options: Option<StreamOptions>,
+    seed: Option<i64>,
+    logprobs: Option<Value>,
+    top_logprobs: Option<usize>,
+    reasoning_effort: Option<String>,
+    /// vLLM's `return_token_ids`, for the ids of the prompt and output tokens
+    return_token_ids: Option<bool>,
+    /// Prompt tokens of the image and audio parts, counted by `check_profile`
+    #[serde(skip)]
+    media_tokens: usize,
+    /// Endpoint the request came in on, from its path
+    #[serde(skip)]
+    endpoint: Endpoint,
     #[serde(flatten)]
     extra: serde_json::Map<String, Value>,
 }
 
+impl Request {
+    /// The prompt for either endpoint, `messages` for chat and `prompt` for completions
+    fn prompt(&self) -> Option<&Value> {
+        self.extra
+            .get("messages")
+            .or_else(|| self.extra.get("prompt"))
+    }
+
+    /// Templates for this request's endpoint, in the style of the profile's backend
+    fn templates(&self, profile: &ModelProfile) -> &'static Templates {
+        match self.endpoint {
+            Endpoint::ChatCompletions => profile.compat.templates(),
+            Endpoint::Completions => profile.compat.text_templates(),
+        }
+    }
+
+    /// Where in the corpus this request's output starts
+    fn start_offset(&self, mode: OffsetMode, corpus: &Corpus) -> usize {
+        corpus::start_offset(mode, self.seed, self.prompt(), corpus.tokens.len())
+    }
+
+    /// Sampler for the requested logprobs, if any.
+    ///
+    /// Chat takes `logprobs: true` plus `top_logprobs`, the legacy completions
+    /// endpoint takes `logprobs: <int>` as the number of alternatives and
+    /// gets them in its own format.
+    fn logprob_sampler(&self) -> Option<LogprobSampler> {
+        let top_logprobs = match &self.logprobs {
+            Some(Value::Bool(true)) => self.top_logprobs.unwrap_or(0),
+            Some(Value::Number(n)) => n.as_u64()? as usize,
+            _ => return None,
+        };
+        let sampler = LogprobSampler::new(self.seed, top_logprobs);
+        Some(match self.endpoint {
+            Endpoint::ChatCompletions => sampler,
+            Endpoint::Completions => sampler.legacy(),
+        })
+    }
+
+    /// Reject more alternatives than OpenAI returns, with OpenAI's error
+    fn check_logprobs(&self) -> Result<(), ApiError> {
+        let (param, top_logprobs) = match &self.logprobs {
+            Some(Value::Bool(true)) => ("top_logprobs", self.top_logprobs.unwrap_or(0)),
+            Some(Value::Number(n)) => ("logprobs", n.as_u64().unwrap_or(0) as usize),
+            _ => return Ok(()),
+        };
+        if top_logprobs > MAX_TOP_LOGPROBS {
+            return Err(ApiError::invalid_request(
+                format!(
+                    "Invalid '{}': integer above maximum value. Expected a value <= {}, but got {} instead.",
+                    param, MAX_TOP_LOGPROBS, top_logprobs
+                ),
+                Some(param.into()),
+                Some("integer_above_max_value"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Start of this request's log record, completed once the response is done
+    fn request_record(&self, route: &str, key: &str, profile: &ModelProfile) -> RequestRecord {
+        let mut record = RequestRecord::new(route, key);
+        record.model = self.model.clone();
+        record.prompt_tokens = self.prompt_tokens(profile);
+        record.requested_tokens = self.max_completion_tokens.or(self.max_tokens);
+        record.stream = self.stream == Some(true);
+        record
+    }
+
+    /// Made-up ids of the prompt tokens as a JSON array, if `return_token_ids` is set
+    fn prompt_token_ids(&self, profile: &ModelProfile) -> Option<String> {
+        if self.return_token_ids != Some(true) {
+            return None;
+        }
+        let ids = prompt::token_ids(
+            self.prompt().unwrap_or(&Value::Null),
+            profile.chars_per_token,
+            self.media_tokens,
+        );
+        Some(serde_json::to_string(&ids).expect("ids serialize"))
+    }
+
+    fn prompt_tokens(&self, profile: &ModelProfile) -> usize {
+        self.prompt().map_or(0, |prompt| {
+            prompt::count_tokens(prompt, profile.chars_per_token)
+        }) + self.media_tokens
+    }
+
+    /// Requested output length. `max_completion_tokens` replaces the deprecated `max_tokens`
+    fn requested_max_tokens(&self, profile: &ModelProfile) -> usize {
+        self.max_completion_tokens
+            .or(self.max_tokens)
+            .or(profile.default_max_tokens)
+            .unwrap_or(profile.max_output_tokens)
+    }
+
+    /// Reject requests the model can't serve: too long for its context, or
+    /// using tools, images or audio it doesn't support. Image and audio parts
+    /// are decoded here and their tokens kept for the prompt's count.
+    fn check_profile(&mut self, profile: &ModelProfile) -> Result<(), ApiError> {
+        let text_only = profile.text_only(self.model.as_deref());
+        for (kind, accepted) in [("image_url", profile.vision), ("input_audio", true)] {
+            if (text_only || !accepted)
+                && let Some(param) = self.content_part(kind)
+            {
+                return Err(ApiError::invalid_request(
+                    format!(
+                        "Invalid content type. {} is only supported by certain models.",
+                        kind
+                    ),
+                    Some(param),
+                    Some("invalid_content_type"),
+                ));
+            }
+        }
+        // Plain text messages, by far the most common, have no parts to count
+        if let Some(messages) = self.extra.get("messages")
+            && messages.as_array().is_some_and(|messages| {
+                messages
+                    .iter()
+                    .any(|message| message.get("content").is_some_and(Value::is_array))
+            })
+        {
+            self.media_tokens = media::count_tokens(messages)?;
+        }
+        if let Some(context_length) = profile.context_length {
+            let prompt_tokens = self.prompt_tokens(profile);
+            // Reasoning counts towards the requested limit, and on its own without one
+            let completion_tokens = self
+                .max_completion_tokens
+                .or(self.max_tokens)
+                .unwrap_or_else(|| self.reasoning_budget(&profile.reasoning));
+            let requested = prompt_tokens + completion_tokens;
+            if requested > context_length {
+                return Err(ApiError::invalid_request(
+                    format!(
+                        "This model's maximum context length is {} tokens. However, you requested {} tokens ({} in the messages, {} in the completion). Please reduce the length of the messages or completion.",
+                        context_length, requested, prompt_tokens, completion_tokens
+                    ),
+                    Some("messages".into()),
+                    Some("context_length_exceeded"),
+                ));
+            }
+        }
+        if !profile.tools
+            && self
+                .extra
+                .get("tools")
+                .and_then(Value::as_array)
+                .is_some_and(|tools| !tools.is_empty())
+        {
+            return Err(ApiError::invalid_request(
+                "Unsupported parameter: 'tools' is not supported with this model.".to_string(),
+                Some("tools".into()),
+                Some("unsupported_parameter"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Path of the first content part of type `kind` in `messages`, if any
+    fn content_part(&self, kind: &str) -> Option<String> {
+        let messages = self.extra.get("messages")?.as_array()?;
+        messages.iter().enumerate().find_map(|(i, message)| {
+            let parts = message.get("content")?.as_array()?;
+            let j = parts
+                .iter()
+                .position(|part| part.get("type").and_then(Value::as_str) == Some(kind))?;
+            Some(format!("messages.[{}].content.[{}].type", i, j))
+        })
+    }
+
+    /// Output length and finish reason for content starting at `offset`.
+    ///
+    /// Like vLLM, `ignore_eos: false` ends at the first sentence boundary
+    /// after `min_tokens`. Otherwise the output runs to the token limit.
+    /// Reasoning comes out of the same limit, as `reasoning_tokens` ahead of
+    /// the answer, which is cut short to make room for it.
+    fn output_length(
+        &self,
+        offset: usize,
+        reasoning_tokens: usize,
+        profile: &ModelProfile,
+    ) -> (usize, FinishReason) {
+        let max_tokens = self.output_limit(profile) - reasoning_tokens;
+        let corpus = &profile.corpus;
+        if self.ignore_eos.or(profile.ignore_eos) == Some(false)
+            && let Some(length) = corpus::natural_length(
+                corpus.sentence_ends,
+                corpus.tokens.len(),
+                offset,
+                self.min_tokens.unwrap_or(0),
+                max_tokens,
+            )
+        {
+            return (length, FinishReason::Stop);
+        }
+        (max_tokens, FinishReason::Length)
+    }
+
+    /// Reasoning and answer tokens together, never more than the model's cap
+    /// and never less than the one token that carries the finish reason
+    fn output_limit(&self, profile: &ModelProfile) -> usize {
+        std::cmp::min(
+            self.requested_max_tokens(profile),
+            profile.max_output_tokens,
+        )
+        .max(1)
+    }
+
+    /// Reasoning budget for this request, before the output limit
+    fn reasoning_budget(&self, config: &ReasoningConfig) -> usize {
+        config.tokens_for(self.model.as_deref(), self.reasoning_effort.as_deref())
+    }
+
+    /// How many reasoning tokens to emit before the answer. Like OpenAI they
+    /// count towards the output limit, which always leaves one for the answer.
+    fn reasoning_tokens(&self, profile: &ModelProfile) -> usize {
+        self.reasoning_budget(&profile.reasoning)
+            .min(self.output_limit(profile).saturating_sub(1))
+    }
+}
+
+impl FromRequest<AppState> for Request {
+    type Rejection = ApiError;
+
+    async fn from_request(
+        req: axum::extract::Request,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        // Azure names the model in the URL rather than the body
+        let deployment = req.extensions().get::<Deployment>().cloned();
+        let endpoint = Endpoint::from_path(req.uri().path());
+        let mut payload = if !state.config.load().config.strict {
+            let ApiJson(payload) = ApiJson::<Request>::from_request(req, state).await?;
+            payload
+        } else {
+            // Strict mode goes through a Value so every field can be checked,
+            // including the ones that only live in `extra`
+            let ApiJson(body) = ApiJson::<Value>::from_request(req, state).await?;
+            validate::validate(endpoint, &body, deployment.is_none()).inspect_err(|err| {
+                log::warn!(
+                    "Rejected invalid request: param={:?}, {}",
+                    err.param,
+                    err.message
+                )
+            })?;
+            serde_json::from_value(body).map_err(|err| {
+                ApiError::invalid_request(
+                    format!("Invalid request body: {}", err),
+                    None,
+                    Some("invalid_type"),
+                )
+            })?
+        };
+        if let Some(Deployment(deployment)) = deployment {
+            payload.model = Some(deployment);
+        }
+        payload.endpoint = endpoint;
+        Ok(payload)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 struct StreamOptions {
     include_usage: bool,
@@ -43,63 +348,118 @@ pub struct Usage {
     pub total_tokens: i32,
 }
 
-// Validate Bearer token against the configured token
-fn validate_bearer_token(provided_token: &str, expected_token: &Option<String>) -> bool {
-    match expected_token {
-        Some(expected) => {
-            // For a simple toy project, we'll use direct string comparison
-            // In production, you might want to use constant-time comparison
-            provided_token == expected
+impl AppState {
+    /// State for serving `config`, with no request log, a journal of the
+    /// default size, and no warm-up
+    pub fn new(config: Config) -> Result<Self, ApiError> {
+        Ok(AppState {
+            config: Arc::new(SharedConfig::new(config)?),
+            metrics: Arc::new(Metrics::default()),
+            request_log: None,
+            journal: Arc::new(Journal::new(DEFAULT_JOURNAL_SIZE)),
+            history: None,
+            cancellations: Arc::new(Cancellations::default()),
+            admin_token: None,
+            shutdown: Arc::new(Shutdown::new(DEFAULT_DRAIN_TIMEOUT)),
+            health: Arc::new(Health::new(Duration::ZERO)),
+        })
+    }
+
+    pub(crate) fn log_request(&self, record: RequestRecord) {
+        if let Some(history) = &self.history {
+            history.push(record.clone());
         }
-        None => {
-            // No token configured, so no authentication required
-            true
+        if let Some(logger) = &self.request_log {
+            logger.log(record);
         }
     }
+
+    /// Count tokens served to `key` against its quota and in the metrics
+    fn charge(&self, key: &ApiKey, completion_tokens: usize) {
+        key.charge(completion_tokens as u64);
+        self.metrics.inc(
+            "mock_openai_completion_tokens_total",
+            &[("key", key.name())],
+            completion_tokens as u64,
+        );
+    }
 }
 
 pub async fn common_completions(
     State(state): State<AppState>,
-    auth_header: Option<TypedHeader<Authorization<Bearer>>>,
-    Json(payload): Json<Request>,
-) -> impl IntoResponse {
-    // Check authentication if token is configured
-    if let Some(expected_token) = &state.token {
-        match auth_header {
-            Some(TypedHeader(auth)) => {
-                if !validate_bearer_token(auth.token(), &Some(expected_token.clone())) {
-                    log::warn!("Authentication failed: invalid token");
-                    return (
-                        StatusCode::UNAUTHORIZED,
-                        [(header::CONTENT_TYPE, "application/json")],
-                        crate::template::ERROR_INVALID_API_KEY,
-                    )
-                        .into_response();
-                }
-            }
-            None => {
-                log::warn!("Authentication failed: missing Authorization header");
-                return (
-                    StatusCode::UNAUTHORIZED,
-                    [(header::CONTENT_TYPE, "application/json")],
-                    crate::template::ERROR_MISSING_API_KEY,
-                )
-                    .into_response();
-            }
+    Extension(key): Extension<Arc<ApiKey>>,
+    OriginalUri(uri): OriginalUri,
+    payload: Result<Request, ApiError>,
+) -> Response {
+    let started = Instant::now();
+    let mut payload = match payload {
+        Ok(payload) => payload,
+        Err(err) => {
+            // Bodies that fail to parse never reach the checks below
+            let record = RequestRecord::new(uri.path(), key.name());
+            state.log_request(record.complete(started, err.status, 0, None, false));
+            return err.into_response();
+        }
+    };
+    // Settings are read once so a change never applies half-way through a request
+    let live = state.config.load();
+    let profile = match state
+        .health
+        .loading_error()
+        .map_or_else(|| live.profile(payload.model.as_deref()), Err)
+    {
+        Ok(profile) => profile,
+        Err(err) => {
+            log::warn!(
+                "Rejected request from key '{}': {}",
+                key.name(),
+                err.message
+            );
+            let record = RequestRecord::new(uri.path(), key.name());
+            state.log_request(record.complete(started, err.status, 0, None, false));
+            return err.into_response();
         }
+    };
+    let checked = payload
+        .check_logprobs()
+        .and_then(|_| payload.check_profile(profile));
+    // Recorded after the check, which counts the image and audio tokens
+    let record = payload.request_record(uri.path(), key.name(), profile);
+
+    let admitted = checked.and_then(|_| key.admit(payload.model.as_deref()));
+    if let Err(err) = admitted {
+        log::warn!(
+            "Rejected request from key '{}': {}",
+            key.name(),
+            err.message
+        );
+        state.log_request(record.complete(started, err.status, 0, None, false));
+        return err.into_response();
+    }
+    if let Some(err) = profile.faults.injected_error() {
+        log::info!("Injecting a {} error", err.status);
+        state
+            .metrics
+            .inc("mock_openai_injected_faults_total", &[("kind", "error")], 1);
+        state.log_request(record.complete(started, err.status, 0, None, false));
+        return err.into_response();
     }
 
     log::info!(
-        "Received completion request: stream={:?}, stream_options={:?}, max_tokens={:?}",
+        "Received completion request: key={}, stream={:?}, stream_options={:?}, max_tokens={:?}, max_completion_tokens={:?}",
+        key.name(),
         payload.stream,
         payload.stream_options,
-        payload.max_tokens
+        payload.max_tokens,
+        payload.max_completion_tokens
     );
 
     match payload.stream {
         Some(true) => {
             log::debug!("Processing streaming completion request");
-            match streaming_completions(State(state.clone()), payload).await {
+            match streaming_completions(&state, &live, profile, &key, payload, started, record)
+                .await
+            {
                 Ok(stream) => {
                     log::debug!("Successfully created streaming completion");
                     Sse::new(stream).into_response()
@@ -112,10 +472,23 @@ pub async fn common_completions(
         }
         _ => {
             log::debug!("Processing non-streaming completion request");
-            match normal_completions(payload).await {
-                Ok(response) => {
+            match normal_completions(&state, &live, profile, &key, payload).await {
+                Ok((response, completion_tokens)) => {
                     log::debug!("Successfully created non-streaming completion");
-                    (StatusCode::OK, response).into_response()
+                    let now = Some(Instant::now());
+                    state.log_request(record.complete(
+                        started,
+                        StatusCode::OK,
+                        completion_tokens,
+                        now,
+                        false,
+                    ));
+                    (
+                        StatusCode::OK,
+                        [(header::CONTENT_TYPE, "application/json")],
+                        response,
+                    )
+                        .into_response()
                 }
                 Err(_) => {
                     log::error!("Failed to create non-streaming completion");
@@ -126,60 +499,162 @@ pub async fn common_completions(
     }
 }
 
-async fn normal_completions(payload: Request) -> Result<String, ()> {
-    let response = if let Some(max_tokens) = payload.max_tokens {
-        log::debug!("Generating completion with {} max tokens", max_tokens);
+async fn normal_completions(
+    state: &AppState,
+    live: &LiveConfig,
+    profile: &ModelProfile,
+    key: &ApiKey,
+    payload: Request,
+) -> Result<(String, usize), ()> {
+    let corpus = &profile.corpus;
+    let offset = payload.start_offset(live.config.offset_mode, corpus);
+    let reasoning_tokens = payload.reasoning_tokens(profile);
+    // Reasoning is taken from the corpus first, the answer follows on from it
+    let content_offset = offset + reasoning_tokens;
+    let (max_tokens, finish_reason) =
+        payload.output_length(content_offset, reasoning_tokens, profile);
+    log::debug!(
+        "Generating completion: requested={}, actual={}, offset={}, finish_reason={:?}",
+        payload.requested_max_tokens(profile),
+        max_tokens,
+        offset,
+        finish_reason
+    );
 
-        let return_string = if max_tokens >= generated::MAX_TOKENS {
-            log::debug!("Requested tokens exceed available, using full output");
-            generated::MAX_OUTPUT.to_string()
-        } else {
-            log::debug!("Using partial output with {} tokens", max_tokens);
-            generated::TOKENIZED_OUTPUT[..max_tokens].join("")
-        };
-        render_chat_completion(&return_string, max_tokens, max_tokens)
-    } else {
-        log::debug!("No max_tokens specified, using full output");
-        render_chat_completion(
-            generated::MAX_OUTPUT,
-            generated::MAX_TOKENS,
-            generated::MAX_TOKENS,
-        )
-    };
+    let logprob_sampler = payload.logprob_sampler();
+    let return_string = corpus::join_tokens(corpus.tokens, content_offset, max_tokens);
+    let completion_tokens = max_tokens + reasoning_tokens;
+    let prompt_tokens = payload.prompt_tokens(profile);
+    state.charge(key, completion_tokens);
+    let reasoning = (reasoning_tokens > 0)
+        .then(|| corpus::join_tokens(corpus.tokens, offset, reasoning_tokens));
+    let logprobs = logprob_sampler
+        .map(|mut sampler| sampler.render_content(corpus.tokens, content_offset, max_tokens));
+    let prompt_token_ids = payload.prompt_token_ids(profile);
+    // Reasoning and answer are one run of the corpus, and so one run of ids
+    let token_ids = prompt_token_ids
+        .is_some()
+        .then(|| corpus::join_ids(corpus.ids, offset, completion_tokens));
+    let response = payload.templates(profile).completion(&Values {
+        content: &return_string,
+        reasoning: reasoning
+            .as_deref()
+            .map(|reasoning| (profile.reasoning.field, reasoning)),
+        logprobs: logprobs.as_deref(),
+        token_ids: token_ids.as_deref(),
+        prompt_token_ids: prompt_token_ids.as_deref(),
+        finish_reason,
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+        reasoning_tokens,
+    });
 
+    let time_to_first_token = profile.time_to_first_token.sample();
+    if time_to_first_token > 0 {
+        tokio::time::sleep(Duration::from_millis(time_to_first_token)).await;
+    }
     log::debug!("Generated response of {} characters", response.len());
-    Ok(response)
+    Ok((response, completion_tokens))
 }
 
 async fn streaming_completions(
-    State(state): State<AppState>,
+    state: &AppState,
+    live: &LiveConfig,
+    profile: &ModelProfile,
+    key: &ApiKey,
     payload: Request,
-) -> Result<impl Stream<Item = Result<Event, Infallible>>, ()> {
-    let requested_max_tokens = payload.max_tokens.unwrap_or(generated::MAX_TOKENS);
-    let max_tokens = std::cmp::min(requested_max_tokens, generated::MAX_TOKENS);
+    started: Instant,
+    record: RequestRecord,
+) -> Result<impl Stream<Item = Result<Event, Infallible>> + use<>, ()> {
+    let corpus = &profile.corpus;
+    let offset = payload.start_offset(live.config.offset_mode, corpus);
+    let reasoning_tokens = payload.reasoning_tokens(profile);
+    let (max_tokens, finish_reason) =
+        payload.output_length(offset + reasoning_tokens, reasoning_tokens, profile);
     log::debug!(
-        "Streaming completion: requested={}, actual={}, latency={}ms",
-        requested_max_tokens,
+        "Streaming completion: requested={}, actual={}, offset={}, finish_reason={:?}, latency={:?}",
+        payload.requested_max_tokens(profile),
         max_tokens,
-        state.inter_token_latency
+        offset,
+        finish_reason,
+        profile.inter_token_latency
     );
 
+    state.charge(key, max_tokens + reasoning_tokens);
+
     let log_usage: bool = payload
         .stream_options
-        .unwrap_or(StreamOptions {
-            include_usage: false,
-        })
-        .include_usage;
+        .as_ref()
+        .is_some_and(|options| options.include_usage);
     log::debug!("Stream usage logging: {}", log_usage);
 
-    let stream = StringsStream::new(
-        generated::TOKENIZED_OUTPUT,
-        Some(max_tokens),
-        log_usage,
-        state.inter_token_latency,
-    )
-    .map(|data| Ok(Event::default().data(data)));
+    let mut stream = match profile.inter_token_latency {
+        Latency::Fixed(millis) => {
+            StringsStream::new(corpus.tokens, Some(max_tokens), offset, log_usage, millis)
+        }
+        latency => StringsStream::new(corpus.tokens, Some(max_tokens), offset, log_usage, 0)
+            .with_token_latency(latency),
+    }
+    .with_templates(payload.templates(profile))
+    .with_prompt_tokens(payload.prompt_tokens(profile))
+    .with_finish_reason(finish_reason)
+    .with_first_token_delay(profile.time_to_first_token.sample());
+    if let Some(sampler) = payload.logprob_sampler() {
+        stream = stream.with_logprobs(sampler);
+    }
+    if let Some(prompt_token_ids) = payload.prompt_token_ids(profile) {
+        stream = stream.with_token_ids(corpus.ids, prompt_token_ids);
+    }
+    if reasoning_tokens > 0 {
+        log::debug!("Streaming {} reasoning tokens first", reasoning_tokens);
+        stream = stream.with_reasoning(profile.reasoning.field, reasoning_tokens);
+    }
+    if profile.faults.disconnects() {
+        let cutoff = rand::random_range(0..=max_tokens + reasoning_tokens);
+        log::info!("Injecting a disconnect after {} tokens", cutoff);
+        state.metrics.inc(
+            "mock_openai_injected_faults_total",
+            &[("kind", "disconnect")],
+            1,
+        );
+        stream = stream.with_cutoff(cutoff);
+    }
+    let stream = TrackedStream::new(stream, started, record, state.clone())
+        .map(|data| Ok(Event::default().data(data)));
 
     log::debug!("Created streaming completion with {} tokens", max_tokens);
     Ok(stream)
 }
+
+/// Models in the catalog, as listed by `GET /v1/models`
+pub async fn list_models(State(state): State<AppState>) -> Json<ModelList> {
+    let live = state.config.load();
+    let data = live
+        .catalog()
+        .map(|id| Model {
+            id: id.to_string(),
+            object: "model",
+            created: 0,
+            owned_by: "mock-openai",
+        })
+        .collect();
+    Json(ModelList {
+        object: "list",
+        data,
+    })
+}
+
+#[derive(Serialize)]
+pub struct ModelList {
+    object: &'static str,
+    data: Vec<Model>,
+}
+
+#[derive(Serialize)]
+struct Model {
+    id: String,
+    object: &'static str,
+    created: u64,
+    owned_by: &'static str,
+}
@@ -0,0 +1,134 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tokio::sync::{Notify, watch};
+use tokio::time::{self, Duration, Instant};
+
+/// Time in-flight streams get to finish unless told otherwise
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Graceful shutdown state shared by the server and its streams.
+///
+/// On SIGINT or SIGTERM the server stops accepting connections and reports
+/// itself as not ready, streams already running get `drain_timeout` to finish,
+/// and whatever is left after that is ended with an error event.
+pub struct Shutdown {
+    drain_timeout: Duration,
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+    stop: watch::Sender<bool>,
+    abort: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    pub fn new(drain_timeout: Duration) -> Self {
+        Shutdown {
+            drain_timeout,
+            draining: AtomicBool::new(false),
+            in_flight: AtomicUsize::new(0),
+            idle: Notify::new(),
+            stop: watch::Sender::new(false),
+            abort: watch::Sender::new(false),
+        }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Streams currently being served
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn stream_started(&self) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Must be called exactly once for every [`Shutdown::stream_started`]
+    pub fn stream_finished(&self) {
+        if self.in_flight.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.idle.notify_waiters();
+        }
+    }
+
+    /// Resolves once draining starts and listeners must stop accepting connections
+    pub fn stopped(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut stop = self.stop.subscribe();
+        async move {
+            let _ = stop.wait_for(|stopped| *stopped).await;
+        }
+    }
+
+    /// Resolves once the drain timeout has run out and streams must end
+    pub fn aborted(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut abort = self.abort.subscribe();
+        async move {
+            let _ = abort.wait_for(|aborted| *aborted).await;
+        }
+    }
+
+    /// Stop accepting connections and end running streams straight away
+    pub fn close(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+        self.stop.send_replace(true);
+        self.abort.send_replace(true);
+    }
+
+    /// Stop reporting ready, wait for in-flight streams, and abort any that
+    /// are still running once the drain timeout runs out
+    pub async fn drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+        self.stop.send_replace(true);
+        let deadline = Instant::now() + self.drain_timeout;
+        loop {
+            let idle = self.idle.notified();
+            let in_flight = self.in_flight();
+            if in_flight == 0 {
+                log::info!("All streams finished");
+                return;
+            }
+            log::info!("Waiting for {} in-flight stream(s) to finish", in_flight);
+            tokio::select! {
+                _ = idle => {}
+                _ = time::sleep_until(deadline) => {
+                    log::warn!(
+                        "Drain timeout of {:?} reached, ending {} stream(s)",
+                        self.drain_timeout,
+                        self.in_flight()
+                    );
+                    self.abort.send_replace(true);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Resolves on SIGINT, or on SIGTERM where there is one
+pub async fn signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                log::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = interrupt => log::info!("Received SIGINT"),
+        _ = terminate => log::info!("Received SIGTERM"),
+    }
+}
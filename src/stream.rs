@@ -3,13 +3,36 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 use tokio::time::{self, Duration, Instant};
 
-use crate::template;
+use crate::compat::Compat;
+use crate::logprobs::LogprobSampler;
+use crate::profile::Latency;
+use crate::reasoning::ReasoningField;
+use crate::request_log::RequestRecord;
+use crate::routes::AppState;
+use crate::template::{self, FinishReason, Templates};
 pub struct StringsStream<'a> {
     tokens: &'a [&'a str],
+    templates: &'static Templates,
+    role_sent: bool,
+    /// Chunk to send straight away, without waiting for a token
+    pending: Option<String>,
+    /// Ids of `tokens`, sent with each token when requested
+    ids: Option<&'a [u32]>,
+    prompt_token_ids: Option<String>,
+    prompt_tokens: usize,
     index: usize,
+    offset: usize,
     max_tokens: usize,
     log_usage: bool,
+    logprobs: Option<LogprobSampler>,
+    reasoning_field: ReasoningField,
+    reasoning_tokens: usize,
+    finish_reason: FinishReason,
     interval: Option<time::Interval>,
+    /// Sleep before the next chunk, for the first token or a random inter-token latency
+    delay: Option<Pin<Box<time::Sleep>>>,
+    token_latency: Option<Latency>,
+    cutoff: Option<usize>,
     usage_sent: bool,
     done_sent: bool,
 }
@@ -18,13 +41,12 @@ impl<'a> StringsStream<'a> {
     pub fn new(
         tokens: &'a [&'a str],
         max_tokens: Option<usize>,
+        offset: usize,
         log_usage: bool,
         inter_token_latency: u64,
     ) -> Self {
-        // reserve one token for finish reason
-        let max_tokens = max_tokens.unwrap_or(tokens.len()) - 1;
-        // also need to make sure max_tokens is at least 1 to send finish reason
-        let max_tokens = std::cmp::max(1, max_tokens);
+        // reserve one token for finish reason, which is always sent
+        let max_tokens = max_tokens.unwrap_or(tokens.len()).saturating_sub(1);
         let interval = if inter_token_latency > 0 {
             Some(time::interval_at(
                 Instant::now() + Duration::from_millis(inter_token_latency),
@@ -36,64 +58,370 @@ impl<'a> StringsStream<'a> {
 
         StringsStream {
             tokens,
+            templates: Compat::default().templates(),
+            role_sent: false,
+            pending: None,
+            ids: None,
+            prompt_token_ids: None,
+            prompt_tokens: 0,
             index: 0,
+            offset,
             max_tokens,
             log_usage,
+            logprobs: None,
+            reasoning_field: ReasoningField::default(),
+            reasoning_tokens: 0,
+            finish_reason: FinishReason::Length,
             interval,
+            delay: None,
+            token_latency: None,
+            cutoff: None,
             usage_sent: false,
             done_sent: false,
         }
     }
+
+    /// Render the chunks the way `compat` does
+    pub fn with_compat(mut self, compat: Compat) -> Self {
+        self.templates = compat.templates();
+        self
+    }
+
+    /// Render the chunks with `templates`, such as a backend's
+    /// [`Compat::text_templates`] for the legacy completions endpoint
+    pub fn with_templates(mut self, templates: &'static Templates) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Send the id of every token, from `ids`, and the prompt's ids up front,
+    /// as a JSON array, for backends with room for them
+    pub fn with_token_ids(mut self, ids: &'a [u32], prompt_token_ids: String) -> Self {
+        self.ids = Some(ids);
+        self.prompt_token_ids = Some(prompt_token_ids);
+        self
+    }
+
+    /// Report `tokens` prompt tokens in the usage chunk
+    pub fn with_prompt_tokens(mut self, tokens: usize) -> Self {
+        self.prompt_tokens = tokens;
+        self
+    }
+
+    /// Attach synthetic logprobs to every content chunk
+    pub fn with_logprobs(mut self, sampler: LogprobSampler) -> Self {
+        self.logprobs = Some(sampler);
+        self
+    }
+
+    /// Stream `tokens` reasoning tokens in `field` before the answer.
+    /// These come on top of `max_tokens`, so the caller takes them out of the
+    /// request's limit.
+    pub fn with_reasoning(mut self, field: ReasoningField, tokens: usize) -> Self {
+        self.reasoning_field = field;
+        self.reasoning_tokens = tokens;
+        self.max_tokens += tokens;
+        self
+    }
+
+    /// Report `finish_reason` on the last content chunk, `length` by default
+    pub fn with_finish_reason(mut self, finish_reason: FinishReason) -> Self {
+        self.finish_reason = finish_reason;
+        self
+    }
+
+    /// Wait `millis` before the first token instead of one inter-token gap
+    pub fn with_first_token_delay(mut self, millis: u64) -> Self {
+        if millis > 0 {
+            let deadline = Instant::now() + Duration::from_millis(millis);
+            if let Some(interval) = &mut self.interval {
+                interval.reset_at(deadline);
+            }
+            self.delay = Some(Box::pin(time::sleep_until(deadline)));
+        }
+        self
+    }
+
+    /// Wait a fresh sample of `latency` before every chunk, in place of the
+    /// fixed inter-token latency
+    pub fn with_token_latency(mut self, latency: Latency) -> Self {
+        self.interval = None;
+        self.delay = Some(Box::pin(time::sleep(Duration::from_millis(
+            latency.sample(),
+        ))));
+        self.token_latency = Some(latency);
+        self
+    }
+
+    /// End the stream abruptly after `tokens` chunks, as if the server died
+    pub fn with_cutoff(mut self, tokens: usize) -> Self {
+        self.cutoff = Some(tokens);
+        self
+    }
+
+    /// Reasoning and content tokens sent so far
+    pub fn tokens_sent(&self) -> usize {
+        self.index.min(self.max_tokens + 1)
+    }
+
     fn get_token(&self) -> Option<&'a str> {
         if self.tokens.is_empty() {
             None
         } else {
-            Some(self.tokens[self.index % self.tokens.len()])
+            Some(self.tokens[(self.offset + self.index) % self.tokens.len()])
         }
     }
+    fn render_token_ids(&self) -> Option<String> {
+        let ids = self.ids.filter(|ids| !ids.is_empty())?;
+        Some(format!("[{}]", ids[(self.offset + self.index) % ids.len()]))
+    }
+
+    fn render_logprobs(&mut self, token: &str) -> Option<String> {
+        let tokens = self.tokens;
+        self.logprobs
+            .as_mut()
+            .map(|sampler| sampler.render_chunk(token, tokens))
+    }
 }
 
 impl Stream for StringsStream<'_> {
     type Item = String;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if let Some(interval) = &mut self.interval
-            && interval.poll_tick(cx).is_pending()
+        if !self.role_sent {
+            self.role_sent = true;
+            let prompt_token_ids = self.prompt_token_ids.as_deref();
+            if let Some(chunk) = self.templates.role_chunk(prompt_token_ids) {
+                return Poll::Ready(Some(chunk));
+            }
+        }
+        if let Some(chunk) = self.pending.take() {
+            return Poll::Ready(Some(chunk));
+        }
+        loop {
+            if let Some(delay) = &mut self.delay {
+                if delay.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.delay = None;
+            }
+            if let Some(interval) = &mut self.interval
+                && interval.poll_tick(cx).is_pending()
+            {
+                return Poll::Pending;
+            }
+            // Hidden reasoning takes as long as streamed reasoning, without the chunks
+            let silent = self.thinking_silently();
+            let chunk = if silent {
+                self.index += 1;
+                None
+            } else {
+                self.next_chunk()
+            };
+            if (silent || chunk.is_some())
+                && let Some(latency) = self.token_latency
+            {
+                let delay = Duration::from_millis(latency.sample());
+                self.delay = Some(Box::pin(time::sleep(delay)));
+            }
+            if !silent {
+                return Poll::Ready(chunk);
+            }
+        }
+    }
+}
+
+impl StringsStream<'_> {
+    fn cut_off(&self) -> bool {
+        self.cutoff.is_some_and(|cutoff| self.index >= cutoff)
+    }
+
+    fn thinking_silently(&self) -> bool {
+        self.index < self.reasoning_tokens
+            && self.templates.hides_reasoning()
+            && !self.tokens.is_empty()
+            && !self.cut_off()
+    }
+
+    fn next_chunk(&mut self) -> Option<String> {
+        if self.cut_off() {
+            return None;
+        }
+        // 0. Reasoning comes before the answer
+        if self.index < self.reasoning_tokens
+            && let Some(token) = self.get_token()
+            && let Some(chunk) = self.templates.reasoning_chunk(
+                self.reasoning_field,
+                token,
+                self.render_token_ids().as_deref(),
+            )
         {
-            return Poll::Pending;
+            self.index += 1;
+            return Some(chunk);
         }
         if self.index < self.max_tokens {
             if let Some(token) = self.get_token() {
+                let token_ids = self.render_token_ids();
+                let logprobs = self.render_logprobs(token);
                 self.index += 1;
-                return Poll::Ready(Some(template::render_sse_chunk(token)));
+                return Some(self.templates.chunk(
+                    token,
+                    logprobs.as_deref(),
+                    token_ids.as_deref(),
+                ));
             }
             // If get_token returns None (empty array), just fall through to end
             self.index = self.max_tokens;
         }
 
-        // Send the finish reason
+        // 1. Send the finish reason, with the last token or right after it
         if self.index == self.max_tokens {
-            if let Some(token) = self.get_token() {
-                self.index += 1;
-                return Poll::Ready(Some(template::render_sse_finish(token)));
+            let token = self.get_token();
+            let token_ids = self.render_token_ids();
+            let logprobs = token.and_then(|token| self.render_logprobs(token));
+            self.index += 1;
+            if self.templates.finishes_with_content() {
+                return Some(self.templates.finish_chunk(
+                    token,
+                    logprobs.as_deref(),
+                    token_ids.as_deref(),
+                    self.finish_reason,
+                ));
             }
-            return Poll::Ready(Some(template::render_sse_finish("")));
+            let finish = self
+                .templates
+                .finish_chunk(None, None, None, self.finish_reason);
+            let Some(token) = token else {
+                return Some(finish);
+            };
+            self.pending = Some(finish);
+            return Some(
+                self.templates
+                    .chunk(token, logprobs.as_deref(), token_ids.as_deref()),
+            );
         }
 
         // 2. Send usage, usually second last message
         if self.log_usage && !self.usage_sent {
             self.usage_sent = true;
-            let usage = template::render_sse_usage(self.max_tokens + 1, self.max_tokens + 1);
-            return Poll::Ready(Some(usage));
+            let completion_tokens = self.max_tokens + 1;
+            return Some(self.templates.usage_chunk(
+                self.prompt_tokens,
+                completion_tokens,
+                self.prompt_tokens + completion_tokens,
+                self.reasoning_tokens,
+            ));
         }
 
         // 3. Send the done message
         if !self.done_sent {
             self.done_sent = true;
-            return Poll::Ready(Some(template::render_sse_done()));
+            return Some(template::render_sse_done());
         }
 
         // Finally, eos
-        Poll::Ready(None)
+        None
+    }
+}
+
+/// Wraps a [`StringsStream`] to time it and notice when the client goes away.
+///
+/// The request record is written once the stream ends, or when it is dropped
+/// early because the client disconnected.
+pub struct TrackedStream {
+    inner: StringsStream<'static>,
+    started: Instant,
+    first_token: Option<Instant>,
+    finished: bool,
+    record: Option<RequestRecord>,
+    /// Resolves when the server's drain timeout runs out during shutdown
+    aborted: Pin<Box<dyn Future<Output = ()> + Send>>,
+    state: AppState,
+}
+
+impl TrackedStream {
+    pub fn new(
+        inner: StringsStream<'static>,
+        started: Instant,
+        record: RequestRecord,
+        state: AppState,
+    ) -> Self {
+        state.shutdown.stream_started();
+        TrackedStream {
+            inner,
+            started,
+            first_token: None,
+            finished: false,
+            record: Some(record),
+            aborted: Box::pin(state.shutdown.aborted()),
+            state,
+        }
+    }
+
+    fn finish(&mut self) {
+        let Some(record) = self.record.take() else {
+            return;
+        };
+        self.state.shutdown.stream_finished();
+        let record = record.complete(
+            self.started,
+            axum::http::StatusCode::OK,
+            self.inner.tokens_sent(),
+            self.first_token,
+            !self.finished,
+        );
+        if record.disconnected {
+            log::info!(
+                "Client disconnected from {} after {} tokens in {:.0}ms: key={}",
+                record.route,
+                record.output_tokens,
+                record.duration_ms,
+                record.key
+            );
+            self.state.metrics.inc(
+                "mock_openai_client_disconnects_total",
+                &[("key", &record.key), ("route", &record.route)],
+                1,
+            );
+            self.state.cancellations.push(record.clone());
+        }
+        self.state.log_request(record);
+    }
+}
+
+impl Stream for TrackedStream {
+    type Item = String;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.record.is_some() && self.aborted.as_mut().poll(cx).is_ready() {
+            log::warn!(
+                "Ending stream after {} tokens for shutdown",
+                self.inner.tokens_sent()
+            );
+            self.finished = true;
+            self.finish();
+            return Poll::Ready(Some(template::ERROR_SHUTTING_DOWN.to_string()));
+        }
+        if self.record.is_none() {
+            return Poll::Ready(None);
+        }
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(_)) if self.first_token.is_none() && self.inner.tokens_sent() > 0 => {
+                self.first_token = Some(Instant::now());
+            }
+            Poll::Ready(None) => {
+                self.finished = true;
+                self.finish();
+            }
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl Drop for TrackedStream {
+    fn drop(&mut self) {
+        self.finish();
     }
 }
@@ -1,44 +1,499 @@
-/// Absolutely pointless optimisation
-///
-/// Rather that using format or replace, we know how the template looks like
-///
-/// So we can just split it into parts and concatenate them
-///
-/// Start chunk + content + end chunk
-/// Rather than format!("{}{}{}", start, content, end) or SSE_TEMPLATE.replace("{content}", content)
-const SSE_CHUNK_PREFIX: &str = r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[{"index":0,"delta":{"content":"#;
-const SSE_CHUNK_SUFFIX: &str =
-    r#","reasoning_content":null},"logprobs":null,"finish_reason":null,"token_ids":null}]}"#;
+use crate::compat::Samples;
+use crate::reasoning::ReasoningField;
 
-// start chunk is the same as above
-const SSE_FINISH_SUFFIX: &str =
-    r#","reasoning_content":null},"logprobs":null,"finish_reason":"length","token_ids":null}]}"#;
-
-// can be faster if we don't need the numbers
-const SSE_USAGE_PREFIX: &str = r#"{"id":"chatcmpl-xxx","object":"chat.completion.chunk","created":1770187171,"model":"sonnet-mock-model","choices":[],"usage":{"prompt_tokens":0,"completion_tokens":"#;
-const SSE_USAGE_MID: &str = r#","total_tokens":"#;
-const SSE_USAGE_SUFFIX: &str = r#"}}"#;
+// Absolutely pointless optimisation
+//
+// Rather that using format or replace, we know how the template looks like
+//
+// So each template is split into its fixed parts once, and a response is
+// just those parts with the values in between concatenated
+// Rather than format!("{}{}{}", start, content, end) or TEMPLATE.replace("{content}", content)
 
 // does this even need a template?
 const SSE_TEMPLATE_DONE: &str = "[DONE]";
 
 // error messages, honestly they don't trigger much but its fine
-pub const ERROR_INVALID_API_KEY: &str = r#"{"error":{"message":"Invalid API key","type":"invalid_request_error","code":"invalid_api_key"}}"#;
-pub const ERROR_MISSING_API_KEY: &str = r#"{"error":{"message":"Missing Authorization header","type":"invalid_request_error","code":"missing_api_key"}}"#;
-
-/// Pre-split chat completion template
-const CHAT_PREFIX: &str = r#"{"id":"chatcmpl-xxx","object":"chat.completion","created":1770188771,"model":"sonnet-mock-model","choices":[{"index":0,"message":{"role":"assistant","content":"#;
-const CHAT_MID1: &str = r#","refusal":null,"annotations":null,"audio":null,"function_call":null,"tool_calls":[],"reasoning":null,"reasoning_content":null},"logprobs":null,"finish_reason":"length","stop_reason":null,"token_ids":null}],"usage":{"prompt_tokens":0,"total_tokens":"#;
-const CHAT_MID2: &str = r#","completion_tokens":"#;
-const CHAT_SUFFIX: &str = r#","prompt_tokens_details":null}}"#;
-
-// Pre-compute constant lengths so we don't re-evaluate .len() on every call
-const SSE_CHUNK_FIXED_LEN: usize = SSE_CHUNK_PREFIX.len() + SSE_CHUNK_SUFFIX.len();
-const SSE_FINISH_FIXED_LEN: usize = SSE_CHUNK_PREFIX.len() + SSE_FINISH_SUFFIX.len();
-const SSE_USAGE_FIXED_LEN: usize =
-    SSE_USAGE_PREFIX.len() + SSE_USAGE_MID.len() + SSE_USAGE_SUFFIX.len();
-const CHAT_FIXED_LEN: usize =
-    CHAT_PREFIX.len() + CHAT_MID1.len() + CHAT_MID2.len() + CHAT_SUFFIX.len();
+pub const ERROR_INVALID_API_KEY: &str = r#"{"error":{"message":"Invalid API key","type":"invalid_request_error","param":null,"code":"invalid_api_key"}}"#;
+pub const ERROR_SHUTTING_DOWN: &str = r#"{"error":{"message":"The server is shutting down and ended this stream early","type":"server_error","param":null,"code":"server_shutting_down"}}"#;
+pub const ERROR_MISSING_API_KEY: &str = r#"{"error":{"message":"Missing API key. Provide it in an Authorization: Bearer, api-key or x-api-key header","type":"invalid_request_error","param":null,"code":"missing_api_key"}}"#;
+
+/// Content of a finish chunk that comes without a token
+const EMPTY_CONTENT: &str = r#""""#;
+
+/// Why generation stopped
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FinishReason {
+    /// Ran into `max_tokens`
+    #[default]
+    Length,
+    /// Reached a natural end of the text
+    Stop,
+}
+
+impl FinishReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FinishReason::Length => "length",
+            FinishReason::Stop => "stop",
+        }
+    }
+
+    /// As a JSON string, quotes included
+    #[inline(always)]
+    fn json(self) -> &'static str {
+        match self {
+            FinishReason::Length => r#""length""#,
+            FinishReason::Stop => r#""stop""#,
+        }
+    }
+}
+
+/// What fills the slots of a [`Template`]
+#[derive(Clone, Copy, Default)]
+pub struct Values<'a> {
+    /// Answer as a JSON string literal
+    pub content: &'a str,
+    /// Reasoning field and its text as a JSON string literal
+    pub reasoning: Option<(ReasoningField, &'a str)>,
+    /// Rendered `logprobs` object
+    pub logprobs: Option<&'a str>,
+    /// Ids of the output tokens as a JSON array
+    pub token_ids: Option<&'a str>,
+    /// Ids of the prompt tokens as a JSON array
+    pub prompt_token_ids: Option<&'a str>,
+    pub finish_reason: FinishReason,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub total_tokens: usize,
+    pub reasoning_tokens: usize,
+}
+
+/// A `{...}` placeholder in a template
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Slot {
+    /// `{content}`
+    Content,
+    /// `{logprobs}`, null unless requested
+    Logprobs,
+    /// `{token_ids}`, null unless requested
+    TokenIds,
+    /// `{prompt_token_ids}`, null unless requested
+    PromptTokenIds,
+    /// `{prompt_token_ids_field}`, a `prompt_token_ids` field left out unless requested
+    PromptTokenIdsField,
+    /// `{finish_reason}`
+    FinishReason,
+    /// `{reasoning}`, the reasoning text
+    Reasoning,
+    /// `{reasoning_key}`, the name of the field carrying the reasoning, quoted
+    ReasoningKey,
+    /// `{reasoning:<field>}`, the reasoning text if it goes in `field`, otherwise null
+    ReasoningIn(ReasoningField),
+    /// `{prompt_tokens}`
+    PromptTokens,
+    /// `{completion_tokens}`
+    CompletionTokens,
+    /// `{total_tokens}`
+    TotalTokens,
+    /// `{reasoning_tokens}`
+    ReasoningTokens,
+    /// `{reasoning_details}`, a `completion_tokens_details` field with the
+    /// reasoning tokens, left out when there are none
+    ReasoningDetails,
+}
+
+const REASONING_DETAILS_PREFIX: &str = r#","completion_tokens_details":{"reasoning_tokens":"#;
+const PROMPT_TOKEN_IDS_PREFIX: &str = r#","prompt_token_ids":"#;
+
+/// What goes in a slot
+enum Fill<'a> {
+    Text(&'a str),
+    Number(usize),
+    ReasoningDetails(usize),
+    /// Text after the given field name
+    Field(&'static str, &'a str),
+}
+
+impl Slot {
+    fn parse(name: &str) -> Option<Slot> {
+        Some(match name {
+            "content" => Slot::Content,
+            "logprobs" => Slot::Logprobs,
+            "token_ids" => Slot::TokenIds,
+            "prompt_token_ids" => Slot::PromptTokenIds,
+            "prompt_token_ids_field" => Slot::PromptTokenIdsField,
+            "finish_reason" => Slot::FinishReason,
+            "reasoning" => Slot::Reasoning,
+            "reasoning_key" => Slot::ReasoningKey,
+            "reasoning:reasoning" => Slot::ReasoningIn(ReasoningField::Reasoning),
+            "reasoning:reasoning_content" => Slot::ReasoningIn(ReasoningField::ReasoningContent),
+            "prompt_tokens" => Slot::PromptTokens,
+            "completion_tokens" => Slot::CompletionTokens,
+            "total_tokens" => Slot::TotalTokens,
+            "reasoning_tokens" => Slot::ReasoningTokens,
+            "reasoning_details" => Slot::ReasoningDetails,
+            _ => return None,
+        })
+    }
+
+    #[inline(always)]
+    fn fill<'a>(self, values: &Values<'a>) -> Fill<'a> {
+        match self {
+            Slot::Content => Fill::Text(values.content),
+            Slot::Logprobs => Fill::Text(values.logprobs.unwrap_or("null")),
+            Slot::TokenIds => Fill::Text(values.token_ids.unwrap_or("null")),
+            Slot::PromptTokenIds => Fill::Text(values.prompt_token_ids.unwrap_or("null")),
+            Slot::PromptTokenIdsField => match values.prompt_token_ids {
+                Some(ids) => Fill::Field(PROMPT_TOKEN_IDS_PREFIX, ids),
+                None => Fill::Text(""),
+            },
+            Slot::FinishReason => Fill::Text(values.finish_reason.json()),
+            Slot::Reasoning => Fill::Text(values.reasoning.map_or("null", |(_, text)| text)),
+            Slot::ReasoningKey => Fill::Text(match values.reasoning {
+                Some((ReasoningField::Reasoning, _)) => r#""reasoning""#,
+                _ => r#""reasoning_content""#,
+            }),
+            Slot::ReasoningIn(field) => Fill::Text(match values.reasoning {
+                Some((selected, text)) if selected == field => text,
+                _ => "null",
+            }),
+            Slot::PromptTokens => Fill::Number(values.prompt_tokens),
+            Slot::CompletionTokens => Fill::Number(values.completion_tokens),
+            Slot::TotalTokens => Fill::Number(values.total_tokens),
+            Slot::ReasoningTokens => Fill::Number(values.reasoning_tokens),
+            Slot::ReasoningDetails if values.reasoning_tokens > 0 => {
+                Fill::ReasoningDetails(values.reasoning_tokens)
+            }
+            Slot::ReasoningDetails => Fill::Text(""),
+        }
+    }
+}
+
+impl Fill<'_> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        match self {
+            Fill::Text(text) => text.len(),
+            Fill::Number(n) => num_digits(*n),
+            Fill::ReasoningDetails(n) => REASONING_DETAILS_PREFIX.len() + num_digits(*n) + 1,
+            Fill::Field(name, text) => name.len() + text.len(),
+        }
+    }
+
+    #[inline(always)]
+    fn write(&self, ptr: *mut u8, pos: usize) -> usize {
+        match self {
+            Fill::Text(text) => copy_advance(ptr, pos, text),
+            Fill::Number(n) => write_usize(ptr, *n, pos),
+            Fill::ReasoningDetails(n) => {
+                let pos = copy_advance(ptr, pos, REASONING_DETAILS_PREFIX);
+                let pos = write_usize(ptr, *n, pos);
+                copy_advance(ptr, pos, "}")
+            }
+            Fill::Field(name, text) => copy_advance(ptr, copy_advance(ptr, pos, name), text),
+        }
+    }
+
+    /// Slow path for baking a fill into a template
+    fn push_to(&self, out: &mut String) {
+        match self {
+            Fill::Text(text) => out.push_str(text),
+            Fill::Number(n) => out.push_str(&n.to_string()),
+            Fill::ReasoningDetails(n) => {
+                out.push_str(REASONING_DETAILS_PREFIX);
+                out.push_str(&n.to_string());
+                out.push('}');
+            }
+            Fill::Field(name, text) => {
+                out.push_str(name);
+                out.push_str(text);
+            }
+        }
+    }
+}
+
+/// A response pre-split around its slots, the text before each slot and then
+/// whatever text follows the last one
+pub struct Template {
+    parts: Vec<(Box<str>, Slot)>,
+    tail: Box<str>,
+    /// Length of all the text, which is the same for every response
+    fixed_len: usize,
+}
+
+impl Template {
+    /// Split `sample` around its `{slot}`s. JSON objects are left alone, but
+    /// anything else that looks like a slot has to be one.
+    pub fn parse(sample: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = sample;
+        while let Some(start) = rest.find('{') {
+            let after = &rest[start + 1..];
+            let name = after.find('}').map(|end| &after[..end]);
+            let slot = name.and_then(Slot::parse);
+            match (slot, name) {
+                (Some(slot), Some(name)) => {
+                    text.push_str(&rest[..start]);
+                    parts.push((std::mem::take(&mut text).into_boxed_str(), slot));
+                    rest = &after[name.len() + 1..];
+                }
+                (None, Some(name))
+                    if !name.is_empty()
+                        && name
+                            .bytes()
+                            .all(|b| b.is_ascii_lowercase() || b"_:".contains(&b)) =>
+                {
+                    panic!("Unknown slot {{{}}} in template {}", name, sample)
+                }
+                _ => {
+                    text.push_str(&rest[..=start]);
+                    rest = after;
+                }
+            }
+        }
+        text.push_str(rest);
+        Self::from_parts(parts, text)
+    }
+
+    fn from_parts(parts: Vec<(Box<str>, Slot)>, tail: String) -> Self {
+        let fixed_len = parts.iter().map(|(text, _)| text.len()).sum::<usize>() + tail.len();
+        Template {
+            parts,
+            tail: tail.into_boxed_str(),
+            fixed_len,
+        }
+    }
+
+    /// This template with every slot but the `live` ones filled in from
+    /// `values` for good, so rendering only has to copy what changes
+    fn bake(&self, values: &Values, live: &[Slot]) -> Self {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        for (before, slot) in &self.parts {
+            text.push_str(before);
+            if live.contains(slot) {
+                parts.push((std::mem::take(&mut text).into_boxed_str(), *slot));
+            } else {
+                slot.fill(values).push_to(&mut text);
+            }
+        }
+        text.push_str(&self.tail);
+        Self::from_parts(parts, text)
+    }
+
+    fn has(&self, slot: Slot) -> bool {
+        self.parts.iter().any(|&(_, s)| s == slot)
+    }
+
+    #[inline(always)]
+    pub fn render(&self, values: &Values) -> String {
+        let mut total = self.fixed_len;
+        for (_, slot) in &self.parts {
+            total += slot.fill(values).len();
+        }
+        let mut buf = Vec::<u8>::with_capacity(total);
+        let ptr = buf.as_mut_ptr();
+
+        let mut pos = 0;
+        for (text, slot) in &self.parts {
+            pos = copy_advance(ptr, pos, text);
+            pos = slot.fill(values).write(ptr, pos);
+        }
+        let pos = copy_advance(ptr, pos, &self.tail);
+
+        unsafe { buf.set_len(pos) };
+        // Safety: we only write ASCII bytes and &str content, all valid UTF-8
+        unsafe { String::from_utf8_unchecked(buf) }
+    }
+}
+
+/// Slots that change between plain responses, without reasoning, logprobs or
+/// token ids and stopped by `max_tokens`
+const PLAIN_SLOTS: &[Slot] = &[
+    Slot::Content,
+    Slot::PromptTokens,
+    Slot::CompletionTokens,
+    Slot::TotalTokens,
+];
+
+/// Every response of one backend, see [`crate::compat::Compat`].
+///
+/// Each template comes baked for the common case, as only a slot or two
+/// changes between chunks. The rest are slower but can fill anything in.
+pub struct Templates {
+    completion: Template,
+    plain_completion: Template,
+    role_chunk: Option<Template>,
+    chunk: Template,
+    plain_chunk: Template,
+    finish_chunk: Template,
+    /// Finish chunks without logprobs or token ids, by [`FinishReason`]
+    plain_finish_chunks: [Template; 2],
+    reasoning_chunk: Option<Template>,
+    usage_chunk: Template,
+    plain_usage_chunk: Template,
+}
+
+impl Templates {
+    pub fn new(samples: &Samples) -> Self {
+        let stream_usage = samples.stream_usage.unwrap_or(samples.usage);
+        let completion = Template::parse(&samples.completion.replace("{usage}", samples.usage));
+        let chunk = Template::parse(samples.chunk);
+        let finish_chunk = Template::parse(samples.finish_chunk);
+        let plain_finish = |finish_reason| {
+            let values = Values {
+                finish_reason,
+                ..Values::default()
+            };
+            finish_chunk.bake(&values, &[Slot::Content])
+        };
+        let reasoning_slots = [Slot::Reasoning, Slot::ReasoningKey, Slot::TokenIds];
+        let usage_slots = [
+            Slot::PromptTokens,
+            Slot::CompletionTokens,
+            Slot::TotalTokens,
+            Slot::ReasoningTokens,
+            Slot::ReasoningDetails,
+        ];
+        let plain_usage_slots = &usage_slots[..3];
+        let usage_chunk = Template::parse(&samples.usage_chunk.replace("{usage}", stream_usage));
+        Templates {
+            plain_completion: completion.bake(&Values::default(), PLAIN_SLOTS),
+            completion,
+            role_chunk: samples.role_chunk.map(Template::parse),
+            plain_chunk: chunk.bake(&Values::default(), &[Slot::Content]),
+            chunk,
+            plain_finish_chunks: [
+                plain_finish(FinishReason::Length),
+                plain_finish(FinishReason::Stop),
+            ],
+            finish_chunk,
+            reasoning_chunk: samples
+                .reasoning_chunk
+                .map(|sample| Template::parse(sample).bake(&Values::default(), &reasoning_slots)),
+            plain_usage_chunk: usage_chunk.bake(&Values::default(), plain_usage_slots),
+            usage_chunk: usage_chunk.bake(&Values::default(), &usage_slots),
+        }
+    }
+
+    /// Non-streaming chat completion body
+    #[inline(always)]
+    pub fn completion(&self, values: &Values) -> String {
+        if values.reasoning.is_none()
+            && values.logprobs.is_none()
+            && values.token_ids.is_none()
+            && values.prompt_token_ids.is_none()
+            && values.reasoning_tokens == 0
+            && values.finish_reason == FinishReason::Length
+        {
+            self.plain_completion.render(values)
+        } else {
+            self.completion.render(values)
+        }
+    }
+
+    /// Chunk announcing the assistant role, for backends that send one first.
+    /// It carries the prompt token ids when they're requested.
+    pub fn role_chunk(&self, prompt_token_ids: Option<&str>) -> Option<String> {
+        let template = self.role_chunk.as_ref()?;
+        Some(template.render(&Values {
+            prompt_token_ids,
+            ..Values::default()
+        }))
+    }
+
+    #[inline(always)]
+    pub fn chunk(&self, content: &str, logprobs: Option<&str>, token_ids: Option<&str>) -> String {
+        let values = Values {
+            content,
+            logprobs,
+            token_ids,
+            ..Values::default()
+        };
+        match (logprobs, token_ids) {
+            (None, None) => self.plain_chunk.render(&values),
+            _ => self.chunk.render(&values),
+        }
+    }
+
+    /// Whether the last token comes with the finish reason. Otherwise it's
+    /// sent as a normal chunk, and the finish chunk follows without content.
+    pub fn finishes_with_content(&self) -> bool {
+        self.finish_chunk.has(Slot::Content)
+    }
+
+    /// Chunk with the finish reason, and `content` if [`Self::finishes_with_content`]
+    #[inline(always)]
+    pub fn finish_chunk(
+        &self,
+        content: Option<&str>,
+        logprobs: Option<&str>,
+        token_ids: Option<&str>,
+        finish_reason: FinishReason,
+    ) -> String {
+        let values = Values {
+            content: content.unwrap_or(EMPTY_CONTENT),
+            logprobs,
+            token_ids,
+            finish_reason,
+            ..Values::default()
+        };
+        match (logprobs, token_ids) {
+            (None, None) => self.plain_finish_chunks[finish_reason as usize].render(&values),
+            _ => self.finish_chunk.render(&values),
+        }
+    }
+
+    /// Whether the backend keeps reasoning to itself, streaming nothing until the answer
+    pub fn hides_reasoning(&self) -> bool {
+        self.reasoning_chunk.is_none()
+    }
+
+    /// Streamed reasoning token, sent before any content
+    #[inline(always)]
+    pub fn reasoning_chunk(
+        &self,
+        field: ReasoningField,
+        reasoning: &str,
+        token_ids: Option<&str>,
+    ) -> Option<String> {
+        let template = self.reasoning_chunk.as_ref()?;
+        Some(template.render(&Values {
+            reasoning: Some((field, reasoning)),
+            token_ids,
+            ..Values::default()
+        }))
+    }
+
+    /// Final chunk with the usage and no choices
+    #[inline(always)]
+    pub fn usage_chunk(
+        &self,
+        prompt_tokens: usize,
+        completion_tokens: usize,
+        total_tokens: usize,
+        reasoning_tokens: usize,
+    ) -> String {
+        let values = Values {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens,
+            reasoning_tokens,
+            ..Values::default()
+        };
+        match reasoning_tokens {
+            0 => self.plain_usage_chunk.render(&values),
+            _ => self.usage_chunk.render(&values),
+        }
+    }
+}
+
+/// Number of decimal digits needed to print `n`
+#[inline(always)]
+fn num_digits(n: usize) -> usize {
+    if n == 0 { 1 } else { n.ilog10() as usize + 1 }
+}
 
 /// Fast integer-to-string formatting via raw pointer write.
 /// Writes digits at `ptr.add(pos)` and returns the position after the last digit.
@@ -78,100 +533,6 @@ fn copy_advance(ptr: *mut u8, pos: usize, src: &str) -> usize {
     pos + src_bytes.len()
 }
 
-#[inline(always)]
-pub fn render_sse_chunk(content: &str) -> String {
-    let total = SSE_CHUNK_FIXED_LEN + content.len();
-    let mut buf = Vec::<u8>::with_capacity(total);
-    let ptr = buf.as_mut_ptr();
-
-    let pos = 0;
-    let pos = copy_advance(ptr, pos, SSE_CHUNK_PREFIX);
-    let pos = copy_advance(ptr, pos, content);
-    let pos = copy_advance(ptr, pos, SSE_CHUNK_SUFFIX);
-
-    unsafe { buf.set_len(pos) };
-    // Safety: we only write ASCII bytes and &str content, all valid UTF-8
-    unsafe { String::from_utf8_unchecked(buf) }
-}
-
-#[inline(always)]
-pub fn render_sse_finish(content: &str) -> String {
-    let total = SSE_FINISH_FIXED_LEN + content.len();
-    let mut buf = Vec::<u8>::with_capacity(total);
-    let ptr = buf.as_mut_ptr();
-
-    let pos = 0;
-    let pos = copy_advance(ptr, pos, SSE_CHUNK_PREFIX);
-    let pos = copy_advance(ptr, pos, content);
-    let pos = copy_advance(ptr, pos, SSE_FINISH_SUFFIX);
-
-    unsafe { buf.set_len(pos) };
-    // Safety: we only write ASCII bytes and &str content, all valid UTF-8
-    unsafe { String::from_utf8_unchecked(buf) }
-}
-
-#[inline(always)]
-pub fn render_sse_usage(completion_tokens: usize, total_tokens: usize) -> String {
-    let ct_digits = if completion_tokens == 0 {
-        1
-    } else {
-        completion_tokens.ilog10() as usize + 1
-    };
-    let tt_digits = if total_tokens == 0 {
-        1
-    } else {
-        total_tokens.ilog10() as usize + 1
-    };
-
-    let mut buf = Vec::<u8>::with_capacity(SSE_USAGE_FIXED_LEN + ct_digits + tt_digits);
-    let ptr = buf.as_mut_ptr();
-
-    let mut pos = 0;
-    pos = copy_advance(ptr, pos, SSE_USAGE_PREFIX);
-    pos = write_usize(ptr, completion_tokens, pos);
-    pos = copy_advance(ptr, pos, SSE_USAGE_MID);
-    pos = write_usize(ptr, total_tokens, pos);
-    pos = copy_advance(ptr, pos, SSE_USAGE_SUFFIX);
-
-    unsafe { buf.set_len(pos) };
-    // Safety: we only write ASCII bytes and &str content, all valid UTF-8
-    unsafe { String::from_utf8_unchecked(buf) }
-}
-
-#[inline(always)]
-pub fn render_chat_completion(
-    content: &str,
-    completion_tokens: usize,
-    total_tokens: usize,
-) -> String {
-    let ct_digits = if completion_tokens == 0 {
-        1
-    } else {
-        completion_tokens.ilog10() as usize + 1
-    };
-    let tt_digits = if total_tokens == 0 {
-        1
-    } else {
-        total_tokens.ilog10() as usize + 1
-    };
-
-    let mut buf = Vec::<u8>::with_capacity(CHAT_FIXED_LEN + content.len() + ct_digits + tt_digits);
-    let ptr = buf.as_mut_ptr();
-
-    let mut pos = 0;
-    pos = copy_advance(ptr, pos, CHAT_PREFIX);
-    pos = copy_advance(ptr, pos, content);
-    pos = copy_advance(ptr, pos, CHAT_MID1);
-    pos = write_usize(ptr, total_tokens, pos);
-    pos = copy_advance(ptr, pos, CHAT_MID2);
-    pos = write_usize(ptr, completion_tokens, pos);
-    pos = copy_advance(ptr, pos, CHAT_SUFFIX);
-
-    unsafe { buf.set_len(pos) };
-    // Safety: we only write ASCII bytes and &str content, all valid UTF-8
-    unsafe { String::from_utf8_unchecked(buf) }
-}
-
 #[inline]
 pub fn render_sse_done() -> String {
     SSE_TEMPLATE_DONE.into()
@@ -0,0 +1,149 @@
+use axum::serve::Listener;
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair, KeyUsagePurpose};
+use std::fmt::Debug;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::server::TlsStream;
+
+/// Connections whose handshake is done but which axum hasn't picked up yet
+const ACCEPT_BACKLOG: usize = 64;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Server config for a PEM certificate chain and private key
+pub fn load(cert: &Path, key: &Path) -> io::Result<Arc<ServerConfig>> {
+    let chain = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(format!("Invalid certificate {}: {}", cert.display(), e)))?;
+    if chain.is_empty() {
+        return Err(invalid(format!("No certificate in {}", cert.display())));
+    }
+    let key = PrivateKeyDer::from_pem_file(key)
+        .map_err(|e| invalid(format!("Invalid private key {}: {}", key.display(), e)))?;
+    server_config(chain, key)
+}
+
+/// Server config for a throwaway certificate covering `hosts`, signed by a
+/// throwaway CA whose certificate is written to `ca_file` for clients to trust
+pub fn self_signed(hosts: Vec<String>, ca_file: &Path) -> io::Result<Arc<ServerConfig>> {
+    let generate =
+        |e: rcgen::Error| io::Error::other(format!("Failed to generate certificate: {}", e));
+
+    let ca_key = KeyPair::generate().map_err(generate)?;
+    let mut ca_params = CertificateParams::default();
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "mock-openai CA");
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    let ca = ca_params.self_signed(&ca_key).map_err(generate)?;
+    let issuer = Issuer::new(ca_params, ca_key);
+
+    let key = KeyPair::generate().map_err(generate)?;
+    let mut params = CertificateParams::new(hosts).map_err(generate)?;
+    params
+        .distinguished_name
+        .push(DnType::CommonName, "mock-openai");
+    let cert = params.signed_by(&key, &issuer).map_err(generate)?;
+
+    std::fs::write(ca_file, ca.pem()).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to write {}: {}", ca_file.display(), e),
+        )
+    })?;
+    log::info!(
+        "Wrote the self-signed CA certificate to {}",
+        ca_file.display()
+    );
+
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der()));
+    server_config(vec![cert.der().clone(), ca.der().clone()], key)
+}
+
+fn server_config(
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> io::Result<Arc<ServerConfig>> {
+    let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(chain, key))
+        .map_err(|e: rustls::Error| invalid(format!("Invalid TLS config: {}", e)))?;
+    // HTTP/2 for clients that offer it, HTTP/1.1 otherwise
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// A listener that hands axum connections once their TLS handshake is done.
+///
+/// Handshakes run in their own tasks, so a slow or broken client doesn't hold
+/// up the others.
+pub struct TlsListener<L: Listener> {
+    local_addr: L::Addr,
+    accepted: mpsc::Receiver<(TlsStream<L::Io>, L::Addr)>,
+}
+
+impl<L> TlsListener<L>
+where
+    L: Listener,
+    L::Addr: Debug + Clone,
+{
+    pub fn new(mut listener: L, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (sender, accepted) = mpsc::channel(ACCEPT_BACKLOG);
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = tokio::select! {
+                    // Dropped along with the server
+                    _ = sender.closed() => return,
+                    accepted = listener.accept() => accepted,
+                };
+                let acceptor = acceptor.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    match acceptor.accept(stream).await {
+                        Ok(stream) => {
+                            let _ = sender.send((stream, addr)).await;
+                        }
+                        Err(e) => log::debug!("TLS handshake with {:?} failed: {}", addr, e),
+                    }
+                });
+            }
+        });
+        Ok(TlsListener {
+            local_addr,
+            accepted,
+        })
+    }
+}
+
+impl<L> Listener for TlsListener<L>
+where
+    L: Listener,
+    L::Addr: Clone + Sync,
+{
+    type Io = TlsStream<L::Io>;
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.accepted.recv().await {
+            Some(accepted) => accepted,
+            // The accept task only stops once this listener is gone
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr.clone())
+    }
+}
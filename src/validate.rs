@@ -0,0 +1,459 @@
+//! Strict request validation, enabled with `--strict`.
+//!
+//! Checks chat and completion payloads against the OpenAI parameter
+//! constraints so that client bugs surface as the same 400 errors the real
+//! API would return, with the offending `param`.
+
+use serde_json::{Map, Value};
+
+use crate::error::ApiError;
+
+/// Which OpenAI endpoint a payload was sent to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endpoint {
+    #[default]
+    ChatCompletions,
+    Completions,
+}
+
+impl Endpoint {
+    /// Work out the endpoint from the request path
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with("/chat/completions") {
+            Endpoint::ChatCompletions
+        } else {
+            Endpoint::Completions
+        }
+    }
+}
+
+const ROLES: &[&str] = &[
+    "system",
+    "developer",
+    "user",
+    "assistant",
+    "tool",
+    "function",
+];
+const CONTENT_PART_TYPES: &[&str] = &["text", "image_url", "input_audio", "file", "refusal"];
+const REASONING_EFFORTS: &[&str] = &["none", "minimal", "low", "medium", "high", "xhigh"];
+const RESPONSE_FORMATS: &[&str] = &["text", "json_object", "json_schema"];
+const TOOL_CHOICES: &[&str] = &["none", "auto", "required"];
+
+type Result<T = ()> = std::result::Result<T, ApiError>;
+
+/// Validate a request body. `require_model` is false for routes that take the
+/// model from the URL, such as Azure deployments.
+pub fn validate(endpoint: Endpoint, body: &Value, require_model: bool) -> Result {
+    let Some(body) = body.as_object() else {
+        return Err(ApiError::invalid_request(
+            "Invalid request body: expected a JSON object.",
+            None,
+            Some("invalid_type"),
+        ));
+    };
+
+    match body.get("model") {
+        None | Some(Value::Null) if !require_model => {}
+        None | Some(Value::Null) => {
+            return Err(ApiError::invalid_request(
+                "you must provide a model parameter",
+                None,
+                None,
+            ));
+        }
+        Some(model) => expect_string("model", model)?,
+    }
+
+    optional(body, "stream", expect_bool)?;
+    if let Some(options) = body.get("stream_options").filter(|v| !v.is_null()) {
+        if body.get("stream") != Some(&Value::Bool(true)) {
+            return Err(ApiError::invalid_request(
+                "The 'stream_options' parameter is only allowed when 'stream' is enabled.",
+                Some("stream_options".into()),
+                None,
+            ));
+        }
+        let options = expect_object("stream_options", options)?;
+        optional(options, "include_usage", |_, v| {
+            expect_bool("stream_options.include_usage", v)
+        })?;
+    }
+
+    optional(body, "temperature", |p, v| expect_number(p, v, 0.0, 2.0))?;
+    optional(body, "top_p", |p, v| expect_number(p, v, 0.0, 1.0))?;
+    optional(body, "presence_penalty", |p, v| {
+        expect_number(p, v, -2.0, 2.0)
+    })?;
+    optional(body, "frequency_penalty", |p, v| {
+        expect_number(p, v, -2.0, 2.0)
+    })?;
+    optional(body, "n", |p, v| expect_integer(p, v, 1, 128))?;
+    optional(body, "max_tokens", |p, v| expect_integer(p, v, 1, i64::MAX))?;
+    optional(body, "min_tokens", |p, v| expect_integer(p, v, 0, i64::MAX))?;
+    optional(body, "ignore_eos", expect_bool)?;
+    optional(body, "return_token_ids", expect_bool)?;
+    validate_min_tokens(body)?;
+    optional(body, "seed", |p, v| {
+        expect_integer(p, v, i64::MIN, i64::MAX)
+    })?;
+    optional(body, "user", expect_string)?;
+    optional(body, "stop", validate_stop)?;
+    optional(body, "logit_bias", validate_logit_bias)?;
+
+    match endpoint {
+        Endpoint::ChatCompletions => validate_chat(body),
+        Endpoint::Completions => validate_completion(body),
+    }
+}
+
+fn validate_chat(body: &Map<String, Value>) -> Result {
+    let messages = match body.get("messages") {
+        None | Some(Value::Null) => {
+            return Err(ApiError::invalid_request(
+                "Missing required parameter: 'messages'.",
+                Some("messages".into()),
+                Some("missing_required_parameter"),
+            ));
+        }
+        Some(messages) => expect_array("messages", messages)?,
+    };
+    if messages.is_empty() {
+        return Err(ApiError::invalid_request(
+            "Invalid 'messages': empty array. Expected an array with minimum length 1, but got an empty array instead.",
+            Some("messages".into()),
+            Some("empty_array"),
+        ));
+    }
+    for (i, message) in messages.iter().enumerate() {
+        validate_message(&format!("messages.[{}]", i), message)?;
+    }
+
+    optional(body, "max_completion_tokens", |p, v| {
+        expect_integer(p, v, 1, i64::MAX)
+    })?;
+    optional(body, "logprobs", expect_bool)?;
+    if let Some(top) = body.get("top_logprobs").filter(|v| !v.is_null()) {
+        expect_integer("top_logprobs", top, 0, 20)?;
+        if body.get("logprobs") != Some(&Value::Bool(true)) {
+            return Err(ApiError::invalid_request(
+                "Invalid value for 'logprobs': 'logprobs' must be set to true when 'top_logprobs' is used.",
+                Some("logprobs".into()),
+                Some("invalid_value"),
+            ));
+        }
+    }
+    optional(body, "reasoning_effort", |p, v| {
+        expect_one_of(p, v, REASONING_EFFORTS)
+    })?;
+    optional(body, "response_format", |p, v| {
+        let format = expect_object(p, v)?;
+        let kind = required(format, p, "type")?;
+        expect_one_of(&format!("{}.type", p), kind, RESPONSE_FORMATS)
+    })?;
+    optional(body, "tools", validate_tools)?;
+    optional(body, "tool_choice", |p, v| match v {
+        Value::String(_) => expect_one_of(p, v, TOOL_CHOICES),
+        Value::Object(_) => Ok(()),
+        _ => Err(invalid_type(p, "a string or object", v)),
+    })?;
+    optional(body, "parallel_tool_calls", expect_bool)?;
+    Ok(())
+}
+
+fn validate_completion(body: &Map<String, Value>) -> Result {
+    optional(body, "prompt", |p, v| match v {
+        Value::String(_) => Ok(()),
+        Value::Array(items) => {
+            // string[], int[] or int[][]
+            for (i, item) in items.iter().enumerate() {
+                let param = format!("{}.[{}]", p, i);
+                match item {
+                    Value::String(_) | Value::Number(_) => {}
+                    Value::Array(ids) => {
+                        for (j, id) in ids.iter().enumerate() {
+                            expect_integer(&format!("{}.[{}]", param, j), id, 0, i64::MAX)?;
+                        }
+                    }
+                    _ => return Err(invalid_type(&param, "a string or token array", item)),
+                }
+            }
+            Ok(())
+        }
+        _ => Err(invalid_type(p, "a string or array", v)),
+    })?;
+    optional(body, "logprobs", |p, v| expect_integer(p, v, 0, 5))?;
+    optional(body, "echo", expect_bool)?;
+    optional(body, "best_of", |p, v| expect_integer(p, v, 1, 20))?;
+    optional(body, "suffix", expect_string)?;
+    Ok(())
+}
+
+fn validate_message(param: &str, message: &Value) -> Result {
+    let message = expect_object(param, message)?;
+    let role_param = format!("{}.role", param);
+    let role = required(message, param, "role")?;
+    expect_one_of(&role_param, role, ROLES)?;
+    let role = role.as_str().unwrap_or_default();
+
+    let content_param = format!("{}.content", param);
+    match message.get("content") {
+        None | Some(Value::Null) => {
+            // assistant messages may carry only tool calls
+            let has_tool_calls = message.get("tool_calls").is_some_and(|v| !v.is_null())
+                || message.get("function_call").is_some_and(|v| !v.is_null());
+            if role != "assistant" || !has_tool_calls {
+                return Err(ApiError::invalid_request(
+                    format!("Missing required parameter: '{}'.", content_param),
+                    Some(content_param),
+                    Some("missing_required_parameter"),
+                ));
+            }
+        }
+        Some(Value::String(_)) => {}
+        Some(Value::Array(parts)) => {
+            for (i, part) in parts.iter().enumerate() {
+                let part_param = format!("{}.[{}]", content_param, i);
+                let part = expect_object(&part_param, part)?;
+                let kind = required(part, &part_param, "type")?;
+                expect_one_of(&format!("{}.type", part_param), kind, CONTENT_PART_TYPES)?;
+            }
+        }
+        Some(other) => return Err(invalid_type(&content_param, "a string or array", other)),
+    }
+
+    if role == "tool" {
+        let tool_call_id = required(message, param, "tool_call_id")?;
+        expect_string(&format!("{}.tool_call_id", param), tool_call_id)?;
+    }
+    optional(message, "name", |_, v| {
+        expect_string(&format!("{}.name", param), v)
+    })?;
+    Ok(())
+}
+
+fn validate_tools(param: &str, tools: &Value) -> Result {
+    for (i, tool) in expect_array(param, tools)?.iter().enumerate() {
+        let tool_param = format!("{}.[{}]", param, i);
+        let tool = expect_object(&tool_param, tool)?;
+        let kind = required(tool, &tool_param, "type")?;
+        expect_one_of(
+            &format!("{}.type", tool_param),
+            kind,
+            &["function", "custom"],
+        )?;
+        if kind == "function" {
+            let function_param = format!("{}.function", tool_param);
+            let function =
+                expect_object(&function_param, required(tool, &tool_param, "function")?)?;
+            let name = required(function, &function_param, "name")?;
+            expect_string(&format!("{}.name", function_param), name)?;
+        }
+    }
+    Ok(())
+}
+
+/// vLLM rejects `min_tokens` above the output limit
+fn validate_min_tokens(body: &Map<String, Value>) -> Result {
+    let Some(min_tokens) = body.get("min_tokens").and_then(Value::as_i64) else {
+        return Ok(());
+    };
+    let max_tokens = body
+        .get("max_completion_tokens")
+        .or_else(|| body.get("max_tokens"))
+        .and_then(Value::as_i64);
+    match max_tokens {
+        Some(max_tokens) if min_tokens > max_tokens => Err(ApiError::invalid_request(
+            format!(
+                "min_tokens must be less than or equal to max_tokens={}, got {}.",
+                max_tokens, min_tokens
+            ),
+            Some("min_tokens".into()),
+            Some("invalid_value"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn validate_stop(param: &str, stop: &Value) -> Result {
+    match stop {
+        Value::String(_) => Ok(()),
+        Value::Array(items) => {
+            if items.len() > 4 {
+                return Err(ApiError::invalid_request(
+                    format!(
+                        "Invalid '{}': array too long. Expected an array with maximum length 4, but got an array with length {} instead.",
+                        param,
+                        items.len()
+                    ),
+                    Some(param.into()),
+                    Some("array_above_max_length"),
+                ));
+            }
+            for (i, item) in items.iter().enumerate() {
+                expect_string(&format!("{}.[{}]", param, i), item)?;
+            }
+            Ok(())
+        }
+        _ => Err(invalid_type(param, "a string or array", stop)),
+    }
+}
+
+fn validate_logit_bias(param: &str, bias: &Value) -> Result {
+    for (token, value) in expect_object(param, bias)? {
+        expect_number(&format!("{}.{}", param, token), value, -100.0, 100.0)?;
+    }
+    Ok(())
+}
+
+/// Run `check` on `key` if it is present and not null
+fn optional<F>(object: &Map<String, Value>, key: &str, check: F) -> Result
+where
+    F: FnOnce(&str, &Value) -> Result,
+{
+    match object.get(key) {
+        None | Some(Value::Null) => Ok(()),
+        Some(value) => check(key, value),
+    }
+}
+
+fn required<'a>(object: &'a Map<String, Value>, parent: &str, key: &str) -> Result<&'a Value> {
+    match object.get(key) {
+        None | Some(Value::Null) => {
+            let param = format!("{}.{}", parent, key);
+            Err(ApiError::invalid_request(
+                format!("Missing required parameter: '{}'.", param),
+                Some(param),
+                Some("missing_required_parameter"),
+            ))
+        }
+        Some(value) => Ok(value),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(n) if n.is_f64() => "a decimal",
+        Value::Number(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn invalid_type(param: &str, expected: &str, got: &Value) -> ApiError {
+    ApiError::invalid_request(
+        format!(
+            "Invalid type for '{}': expected {}, but got {} instead.",
+            param,
+            expected,
+            type_name(got)
+        ),
+        Some(param.into()),
+        Some("invalid_type"),
+    )
+}
+
+fn expect_bool(param: &str, value: &Value) -> Result {
+    match value {
+        Value::Bool(_) => Ok(()),
+        _ => Err(invalid_type(param, "a boolean", value)),
+    }
+}
+
+fn expect_string(param: &str, value: &Value) -> Result {
+    match value {
+        Value::String(_) => Ok(()),
+        _ => Err(invalid_type(param, "a string", value)),
+    }
+}
+
+fn expect_object<'a>(param: &str, value: &'a Value) -> Result<&'a Map<String, Value>> {
+    value
+        .as_object()
+        .ok_or_else(|| invalid_type(param, "an object", value))
+}
+
+fn expect_array<'a>(param: &str, value: &'a Value) -> Result<&'a Vec<Value>> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid_type(param, "an array", value))
+}
+
+fn expect_number(param: &str, value: &Value, min: f64, max: f64) -> Result {
+    let Some(n) = value.as_f64() else {
+        return Err(invalid_type(param, "a decimal", value));
+    };
+    if n < min {
+        return Err(ApiError::invalid_request(
+            format!(
+                "Invalid '{}': decimal below minimum value. Expected a value >= {}, but got {} instead.",
+                param, min, value
+            ),
+            Some(param.into()),
+            Some("decimal_below_min_value"),
+        ));
+    }
+    if n > max {
+        return Err(ApiError::invalid_request(
+            format!(
+                "Invalid '{}': decimal above maximum value. Expected a value <= {}, but got {} instead.",
+                param, max, value
+            ),
+            Some(param.into()),
+            Some("decimal_above_max_value"),
+        ));
+    }
+    Ok(())
+}
+
+fn expect_integer(param: &str, value: &Value, min: i64, max: i64) -> Result {
+    let Some(n) = value.as_i64() else {
+        return Err(invalid_type(param, "an integer", value));
+    };
+    if n < min {
+        return Err(ApiError::invalid_request(
+            format!(
+                "Invalid '{}': integer below minimum value. Expected a value >= {}, but got {} instead.",
+                param, min, n
+            ),
+            Some(param.into()),
+            Some("integer_below_min_value"),
+        ));
+    }
+    if n > max {
+        return Err(ApiError::invalid_request(
+            format!(
+                "Invalid '{}': integer above maximum value. Expected a value <= {}, but got {} instead.",
+                param, max, n
+            ),
+            Some(param.into()),
+            Some("integer_above_max_value"),
+        ));
+    }
+    Ok(())
+}
+
+fn expect_one_of(param: &str, value: &Value, allowed: &[&str]) -> Result {
+    let Some(s) = value.as_str() else {
+        return Err(invalid_type(param, "a string", value));
+    };
+    if allowed.contains(&s) {
+        return Ok(());
+    }
+    let quoted: Vec<String> = allowed.iter().map(|a| format!("'{}'", a)).collect();
+    let supported = match quoted.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{}, and {}", rest.join(", "), last),
+        _ => quoted.join(""),
+    };
+    Err(ApiError::invalid_request(
+        format!(
+            "Invalid value: '{}'. Supported values are: {}.",
+            s, supported
+        ),
+        Some(param.into()),
+        Some("invalid_value"),
+    ))
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use common::Client;
+use mock_openai::MockServer;
+use serde_json::{Value, json};
+
+async fn start() -> MockServer {
+    MockServer::builder()
+        .key("tenant-secret")
+        .admin_token("admin-secret")
+        .start()
+        .await
+        .unwrap()
+}
+
+/// Send `body` to `/admin/config` with `method`, as the admin
+async fn admin_config(
+    client: &Client,
+    method: reqwest::Method,
+    body: Option<&Value>,
+) -> (u16, Value) {
+    let mut request = client
+        .http()
+        .request(method, client.url("/admin/config"))
+        .bearer_auth("admin-secret");
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+    common::json_of(request.send().await.unwrap()).await
+}
+
+async fn chat_status(server: &MockServer, key: &str) -> u16 {
+    Client::new(server)
+        .key(key)
+        .post_json("/chat/completions", &common::chat("mock-model", 2))
+        .await
+        .0
+}
+
+#[tokio::test]
+async fn config_round_trip_keeps_the_keys() {
+    let server = start().await;
+    let client = Client::new(&server);
+    let (status, mut config) = admin_config(&client, reqwest::Method::GET, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(config["keys"][0]["name"], "key-0");
+    assert!(config["keys"][0].get("key").is_none());
+    assert!(!config.to_string().contains("tenant-secret"));
+
+    config["inter_token_latency"] = json!(1);
+    config["keys"][0]["requests_per_minute"] = json!(100);
+    let (status, updated) = admin_config(&client, reqwest::Method::PUT, Some(&config)).await;
+    assert_eq!(status, 200, "{}", updated);
+    assert_eq!(updated["keys"][0]["requests_per_minute"], 100);
+    assert_eq!(chat_status(&server, "tenant-secret").await, 200);
+    assert_eq!(chat_status(&server, "wrong").await, 401);
+
+    // Leaving `keys` out keeps them too
+    let (status, _) = admin_config(
+        &client,
+        reqwest::Method::PUT,
+        Some(&json!({ "inter_token_latency": 0 })),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(chat_status(&server, "tenant-secret").await, 200);
+    assert_eq!(chat_status(&server, "wrong").await, 401);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn new_key_without_a_secret_is_rejected() {
+    let server = start().await;
+    let client = Client::new(&server);
+    let body = json!({ "keys": [{ "name": "key-0" }, { "name": "new" }] });
+    let (status, body) = admin_config(&client, reqwest::Method::PATCH, Some(&body)).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["param"], "keys");
+    assert_eq!(chat_status(&server, "tenant-secret").await, 200);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn patch_changes_only_the_fields_given() {
+    let server = start().await;
+    let client = Client::new(&server);
+    let patch = json!({ "faults": { "error_rate": 1.0, "error_status": 503 } });
+    let (status, config) = admin_config(&client, reqwest::Method::PATCH, Some(&patch)).await;
+    assert_eq!(status, 200, "{}", config);
+    assert_eq!(config["faults"]["error_status"], 503);
+    assert_eq!(chat_status(&server, "tenant-secret").await, 503);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn admin_is_closed_to_tenants() {
+    let server = start().await;
+    let client = Client::new(&server).key("tenant-secret");
+    let (status, _) = client.get("/admin/config").await;
+    assert_eq!(status, 401);
+
+    // Without an admin token, /admin closes once keys are configured
+    let keyed = MockServer::builder()
+        .key("tenant-secret")
+        .start()
+        .await
+        .unwrap();
+    let (status, body) = Client::new(&keyed)
+        .key("tenant-secret")
+        .get("/admin/config")
+        .await;
+    assert_eq!(status, 403);
+    assert_eq!(body["error"]["code"], "admin_disabled");
+    keyed.shutdown().await.unwrap();
+    server.shutdown().await.unwrap();
+}
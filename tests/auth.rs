@@ -0,0 +1,155 @@
+mod common;
+
+use common::Client;
+use mock_openai::MockServer;
+use mock_openai::auth::KeyConfig;
+use mock_openai::config::Config;
+use mock_openai::profile::Latency;
+use serde_json::{Value, json};
+
+fn key(key: &str) -> KeyConfig {
+    KeyConfig {
+        key: key.to_string(),
+        name: key.to_string(),
+        models: Vec::new(),
+        requests_per_minute: None,
+        tokens_per_minute: None,
+        token_quota: None,
+    }
+}
+
+async fn start(keys: Vec<KeyConfig>) -> MockServer {
+    MockServer::builder()
+        .config(Config {
+            inter_token_latency: Latency::Fixed(0),
+            models: vec!["a".into(), "b".into()],
+            keys,
+            ..Config::default()
+        })
+        .start()
+        .await
+        .unwrap()
+}
+
+/// Status and error code of a chat completion for `model` sent with `key`
+async fn chat(server: &MockServer, key: Option<&str>, model: &str) -> (u16, Value) {
+    let mut client = Client::new(server);
+    if let Some(key) = key {
+        client = client.key(key);
+    }
+    let (status, body) = client
+        .post_json("/chat/completions", &common::chat(model, 5))
+        .await;
+    (status, body["error"]["code"].clone())
+}
+
+#[tokio::test]
+async fn unknown_and_missing_keys_are_unauthorized() {
+    let server = start(vec![key("one"), key("two")]).await;
+    assert_eq!(chat(&server, Some("one"), "a").await.0, 200);
+    assert_eq!(chat(&server, Some("two"), "a").await.0, 200);
+    assert_eq!(
+        chat(&server, Some("three"), "a").await,
+        (401, json!("invalid_api_key"))
+    );
+    assert_eq!(
+        chat(&server, None, "a").await,
+        (401, json!("missing_api_key"))
+    );
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn keys_are_limited_to_their_models() {
+    let scoped = KeyConfig {
+        models: vec!["a".into()],
+        ..key("scoped")
+    };
+    let server = start(vec![scoped, key("open")]).await;
+    assert_eq!(chat(&server, Some("scoped"), "a").await.0, 200);
+    assert_eq!(
+        chat(&server, Some("scoped"), "b").await,
+        (403, json!("model_not_found"))
+    );
+    assert_eq!(chat(&server, Some("open"), "b").await.0, 200);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn rate_limits_are_counted_per_key() {
+    let limited = KeyConfig {
+        requests_per_minute: Some(2),
+        ..key("limited")
+    };
+    let server = start(vec![limited, key("other")]).await;
+    assert_eq!(chat(&server, Some("limited"), "a").await.0, 200);
+    assert_eq!(chat(&server, Some("limited"), "a").await.0, 200);
+    assert_eq!(
+        chat(&server, Some("limited"), "a").await,
+        (429, json!("rate_limit_exceeded"))
+    );
+    assert_eq!(chat(&server, Some("other"), "a").await.0, 200);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn token_quota_runs_out() {
+    let quota = KeyConfig {
+        token_quota: Some(8),
+        ..key("quota")
+    };
+    let server = start(vec![quota]).await;
+    // 5 tokens each, so the quota is used up by the second
+    assert_eq!(chat(&server, Some("quota"), "a").await.0, 200);
+    assert_eq!(chat(&server, Some("quota"), "a").await.0, 200);
+    assert_eq!(
+        chat(&server, Some("quota"), "a").await,
+        (429, json!("insufficient_quota"))
+    );
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn every_v1_route_needs_a_key_and_health_does_not() {
+    let server = start(vec![key("one")]).await;
+    let client = Client::new(&server);
+    for path in ["/health", "/health/live", "/health/ready", "/metrics"] {
+        let response = client.http().get(client.url(path)).send().await.unwrap();
+        assert_eq!(response.status(), 200, "{}", path);
+    }
+
+    // Every route answers with the same OpenAI-shaped body
+    let (status, models) = client.get("/v1/models").await;
+    assert_eq!(status, 401);
+    let (status, completions) = client
+        .post_json("/completions", &json!({ "model": "a", "prompt": "Hi" }))
+        .await;
+    assert_eq!(status, 401);
+    assert_eq!(models, completions);
+    assert_eq!(models["error"]["type"], "invalid_request_error");
+    assert_eq!(models["error"]["code"], "missing_api_key");
+    assert_eq!(models["error"]["param"], Value::Null);
+
+    let (status, _) = Client::new(&server).key("one").get("/v1/models").await;
+    assert_eq!(status, 200);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn azure_and_anthropic_key_headers_are_accepted() {
+    let server = start(vec![key("one")]).await;
+    let client = Client::new(&server);
+    for header in ["api-key", "x-api-key"] {
+        for (secret, status) in [("one", 200), ("two", 401)] {
+            let response = client
+                .http()
+                .get(client.url("/v1/models"))
+                .header(header, secret)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{}: {}", header, secret);
+        }
+    }
+    server.shutdown().await.unwrap();
+}
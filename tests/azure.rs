@@ -0,0 +1,94 @@
+mod common;
+
+use common::{Client, json_of};
+use mock_openai::MockServer;
+use mock_openai::auth::KeyConfig;
+use mock_openai::config::Config;
+use mock_openai::profile::Latency;
+use serde_json::{Value, json};
+
+async fn start() -> MockServer {
+    let config = Config {
+        inter_token_latency: Latency::Fixed(0),
+        keys: vec![KeyConfig {
+            key: "secret".into(),
+            name: "azure".into(),
+            models: vec!["gpt-4o".into()],
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            token_quota: None,
+        }],
+        ..Config::default()
+    };
+    MockServer::builder().config(config).start().await.unwrap()
+}
+
+/// POST a chat completion to `deployment` with the key in the `api-key` header
+async fn chat(server: &MockServer, deployment: &str, query: &str, body: &Value) -> (u16, Value) {
+    let client = Client::new(server);
+    let url = client.url(&format!(
+        "/openai/deployments/{}/chat/completions{}",
+        deployment, query
+    ));
+    let response = client
+        .http()
+        .post(url)
+        .header("api-key", "secret")
+        .json(body)
+        .send()
+        .await
+        .unwrap();
+    json_of(response).await
+}
+
+fn hello() -> Value {
+    json!({ "max_tokens": 3, "messages": [{ "role": "user", "content": "Hi" }] })
+}
+
+#[tokio::test]
+async fn deployment_stands_in_for_the_model() {
+    let server = start().await;
+    let (status, body) = chat(&server, "gpt-4o", "?api-version=2024-06-01", &hello()).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["usage"]["completion_tokens"], 3);
+    assert_eq!(server.records()[0].model.as_deref(), Some("gpt-4o"));
+    assert_eq!(
+        server.requests()[0].path,
+        "/openai/deployments/gpt-4o/chat/completions"
+    );
+
+    // The key may only use gpt-4o, whatever the body says
+    let (status, body) = chat(
+        &server,
+        "gpt-4o-mini",
+        "?api-version=2024-06-01",
+        &json!({ "model": "gpt-4o", "max_tokens": 3, "messages": [{ "role": "user", "content": "Hi" }] }),
+    )
+    .await;
+    assert_eq!(status, 403, "{}", body);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn api_version_is_checked_with_azure_errors() {
+    let server = start().await;
+    let (status, body) = chat(&server, "gpt-4o", "", &hello()).await;
+    assert_eq!(status, 404);
+    assert_eq!(
+        body,
+        json!({ "error": { "code": "404", "message": "Resource not found" } })
+    );
+
+    let (status, body) = chat(&server, "gpt-4o", "?api-version=1999-01-01", &hello()).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["code"], "BadRequest");
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("1999-01-01"),
+        "{}",
+        body
+    );
+    server.shutdown().await.unwrap();
+}
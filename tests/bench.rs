@@ -0,0 +1,76 @@
+use mock_openai::MockServer;
+use serde_json::Value;
+use tokio::process::Command;
+
+/// Run `mock-openai bench` against `server` and parse its JSON report
+async fn bench(server: &MockServer, args: &[&str]) -> Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_mock-openai"))
+        .arg("bench")
+        .arg("--target")
+        .arg(server.base_url())
+        .args(["--json", "--max-tokens", "10"])
+        .args(args)
+        .output()
+        .await
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[tokio::test]
+async fn streaming_runs_report_latencies_and_throughput() {
+    let server = MockServer::builder().itl(10).start().await.unwrap();
+    let report = bench(&server, &["-n", "12", "-c", "4"]).await;
+    assert_eq!(report["requests"], 12);
+    assert_eq!(report["succeeded"], 12);
+    assert_eq!(report["failed"], 0);
+    assert_eq!(report["output_tokens"], 120);
+    assert!(report["output_tokens_per_second"].as_f64().unwrap() > 0.0);
+    for latency in ["ttft_ms", "itl_ms", "e2e_ms"] {
+        let percentiles = &report[latency];
+        let p50 = percentiles["p50"].as_f64().unwrap();
+        assert!(p50 <= percentiles["p99"].as_f64().unwrap(), "{}", latency);
+        assert!(
+            percentiles["p99"].as_f64().unwrap() <= percentiles["max"].as_f64().unwrap(),
+            "{}",
+            latency
+        );
+    }
+    assert!(report["itl_ms"]["mean"].as_f64().unwrap() >= 5.0);
+    assert_eq!(server.requests().len(), 12);
+    assert!(server.requests().iter().all(|request| request.stream()));
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn non_streaming_runs_have_no_inter_token_latency() {
+    let server = MockServer::builder().start().await.unwrap();
+    let report = bench(&server, &["-n", "5", "--no-stream"]).await;
+    assert_eq!(report["succeeded"], 5);
+    assert_eq!(report["output_tokens"], 50);
+    assert_eq!(report["itl_ms"], Value::Null);
+    assert!(report["e2e_ms"].is_object());
+    assert!(server.requests().iter().all(|request| !request.stream()));
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn failed_requests_are_counted_by_error() {
+    let server = MockServer::builder().key("secret").start().await.unwrap();
+    let report = bench(&server, &["-n", "3"]).await;
+    assert_eq!(report["succeeded"], 0);
+    assert_eq!(report["failed"], 3);
+    let errors = report["errors"].as_object().unwrap();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors.values().next().unwrap(), 3);
+    assert!(
+        errors.keys().next().unwrap().contains("401"),
+        "{:?}",
+        errors
+    );
+    server.shutdown().await.unwrap();
+}
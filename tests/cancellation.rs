@@ -0,0 +1,74 @@
+mod common;
+
+use common::{Client, with};
+use mock_openai::MockServer;
+use std::time::Duration;
+
+#[tokio::test]
+async fn disconnects_are_listed_in_cancellations() {
+    let server = MockServer::builder().itl(20).start().await.unwrap();
+    let client = Client::new(&server);
+    let body = with(common::chat("mock-model", 100), "stream", true);
+    let mut response = client.post("/chat/completions", &body).await;
+    assert_eq!(response.status(), 200);
+    // Read a few chunks, then go away
+    for _ in 0..3 {
+        response.chunk().await.unwrap().unwrap();
+    }
+    drop(response);
+
+    let mut cancelled = Vec::new();
+    for _ in 0..100 {
+        let (status, body) = client.get("/admin/cancellations").await;
+        assert_eq!(status, 200);
+        cancelled = body["data"].as_array().unwrap().clone();
+        if !cancelled.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(cancelled.len(), 1, "no cancellation was recorded");
+    let record = &cancelled[0];
+    assert_eq!(record["route"], "/v1/chat/completions");
+    assert_eq!(record["disconnected"], true);
+    assert_eq!(record["stream"], true);
+    let sent = record["output_tokens"].as_u64().unwrap();
+    assert!(sent > 0 && sent < 100, "output_tokens = {}", sent);
+    assert!(record["duration_ms"].as_f64().unwrap() > 0.0);
+    assert!(server.records()[0].disconnected);
+
+    let metrics = client
+        .http()
+        .get(client.url("/metrics"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        metrics.contains("mock_openai_client_disconnects_total"),
+        "{}",
+        metrics
+    );
+
+    let response = client
+        .send(client.http().delete(client.url("/admin/cancellations")))
+        .await;
+    assert_eq!(response.status(), 204);
+    let (_, body) = client.get("/admin/cancellations").await;
+    assert_eq!(body["data"], serde_json::json!([]));
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn finished_streams_are_not_cancellations() {
+    let server = MockServer::builder().start().await.unwrap();
+    let client = Client::new(&server);
+    let body = with(common::chat("mock-model", 10), "stream", true);
+    client.stream("/chat/completions", &body).await;
+    let (_, body) = client.get("/admin/cancellations").await;
+    assert_eq!(body["data"], serde_json::json!([]));
+    assert!(!server.records()[0].disconnected);
+    server.shutdown().await.unwrap();
+}
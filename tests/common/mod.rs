@@ -0,0 +1,121 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use mock_openai::MockServer;
+use mock_openai::client::Target;
+use serde_json::{Value, json};
+
+/// An HTTP client for one [`MockServer`], sending an API key once one is set
+pub struct Client {
+    target: Target,
+    root: String,
+    http: reqwest::Client,
+}
+
+impl Client {
+    pub fn new(server: &MockServer) -> Self {
+        let target = Target::new(server.base_url());
+        let http = target.client(1).unwrap();
+        Client {
+            target,
+            root: server.url(""),
+            http,
+        }
+    }
+
+    pub fn key(mut self, key: &str) -> Self {
+        self.target.api_key = Some(key.to_string());
+        self
+    }
+
+    /// POST `body` to `path` under `/v1`
+    pub async fn post(&self, path: &str, body: &Value) -> reqwest::Response {
+        self.target
+            .post(&self.http, path)
+            .json(body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    /// POST `body` to `path` under `/v1`, returning the status and JSON body
+    pub async fn post_json(&self, path: &str, body: &Value) -> (u16, Value) {
+        json_of(self.post(path, body).await).await
+    }
+
+    /// POST a streaming request to `path` under `/v1`, returning every `data:`
+    /// payload before `[DONE]`
+    pub async fn stream(&self, path: &str, body: &Value) -> Vec<Value> {
+        let response = self.post(path, body).await;
+        assert_eq!(response.status(), 200);
+        let text = response.text().await.unwrap();
+        assert!(text.ends_with("data: [DONE]\n\n"), "{}", text);
+        data_lines(&text)
+    }
+
+    /// GET `path` from the root of the server, such as `/health/ready`
+    pub async fn get(&self, path: &str) -> (u16, Value) {
+        json_of(
+            self.send(self.http.get(format!("{}{}", self.root, path)))
+                .await,
+        )
+        .await
+    }
+
+    /// Send `request` with the API key, if any
+    pub async fn send(&self, request: reqwest::RequestBuilder) -> reqwest::Response {
+        let request = match &self.target.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        };
+        request.send().await.unwrap()
+    }
+
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    /// URL of `path` from the root of the server
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.root, path)
+    }
+}
+
+/// Status and JSON body of `response`, with a null body when it isn't JSON
+pub async fn json_of(response: reqwest::Response) -> (u16, Value) {
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap_or_default())
+}
+
+/// Every `data:` payload of an SSE body before `[DONE]`
+pub fn data_lines(text: &str) -> Vec<Value> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .take_while(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect()
+}
+
+/// A chat completion request for `model` with a one word prompt
+pub fn chat(model: &str, max_tokens: usize) -> Value {
+    json!({
+        "model": model,
+        "max_tokens": max_tokens,
+        "messages": [{ "role": "user", "content": "Hello" }],
+    })
+}
+
+/// `body` with `field` set to `value`
+pub fn with(mut body: Value, field: &str, value: impl Into<Value>) -> Value {
+    body[field] = value.into();
+    body
+}
+
+/// Text of the first choice, from a chat or legacy completion
+pub fn content(response: &Value) -> &str {
+    let choice = &response["choices"][0];
+    choice["message"]["content"]
+        .as_str()
+        .or_else(|| choice["text"].as_str())
+        .unwrap()
+}
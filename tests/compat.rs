@@ -0,0 +1,197 @@
+mod common;
+
+use common::{Client, with};
+use mock_openai::MockServer;
+use mock_openai::compat::Compat;
+use mock_openai::profile::Profile;
+use serde_json::{Value, json};
+
+async fn start(compat: Compat) -> MockServer {
+    MockServer::builder().compat(compat).start().await.unwrap()
+}
+
+fn has(value: &Value, field: &str) -> bool {
+    value
+        .as_object()
+        .is_some_and(|object| object.contains_key(field))
+}
+
+/// A chat completion and the chunks of the same request streamed
+async fn chat(server: &MockServer) -> (Value, Vec<Value>) {
+    let client = Client::new(server);
+    let body = common::chat("mock-model", 3);
+    let (status, response) = client.post_json("/chat/completions", &body).await;
+    assert_eq!(status, 200);
+    let chunks = client
+        .stream("/chat/completions", &with(body, "stream", true))
+        .await;
+    (response, chunks)
+}
+
+/// The streamed chunks that carry a token
+fn token_chunks(chunks: &[Value]) -> Vec<&Value> {
+    chunks
+        .iter()
+        .filter(|chunk| {
+            chunk["choices"][0]["delta"]["content"]
+                .as_str()
+                .is_some_and(|content| !content.is_empty())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn openai_sets_its_fingerprint_and_finishes_in_a_chunk_of_its_own() {
+    let server = start(Compat::OpenAi).await;
+    let (response, chunks) = chat(&server).await;
+    assert_eq!(response["service_tier"], "default");
+    assert!(
+        response["system_fingerprint"]
+            .as_str()
+            .unwrap()
+            .starts_with("fp_")
+    );
+    let message = &response["choices"][0]["message"];
+    assert_eq!(message["refusal"], Value::Null);
+    assert_eq!(message["annotations"], json!([]));
+    assert!(!has(&response["choices"][0], "stop_reason"));
+    assert!(has(&response["usage"], "completion_tokens_details"));
+
+    assert_eq!(chunks.len(), 5);
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    assert_eq!(token_chunks(&chunks).len(), 3);
+    let finish = &chunks[4]["choices"][0];
+    assert_eq!(finish["delta"], json!({}));
+    assert_eq!(finish["finish_reason"], "length");
+    assert!(
+        chunks
+            .iter()
+            .all(|chunk| chunk["service_tier"] == "default")
+    );
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn vllm_writes_out_every_field_and_finishes_on_the_last_token() {
+    let server = start(Compat::Vllm).await;
+    let (response, chunks) = chat(&server).await;
+    assert_eq!(response["service_tier"], Value::Null);
+    assert_eq!(response["system_fingerprint"], Value::Null);
+    for field in ["prompt_token_ids", "kv_transfer_params", "prompt_logprobs"] {
+        assert!(has(&response, field), "{}", field);
+    }
+    let choice = &response["choices"][0];
+    assert!(has(choice, "stop_reason") && has(choice, "token_ids"));
+    assert!(has(&choice["message"], "reasoning_content"));
+
+    assert_eq!(chunks.len(), 4);
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    let last = &chunks[3]["choices"][0];
+    assert!(!last["delta"]["content"].as_str().unwrap().is_empty());
+    assert_eq!(last["finish_reason"], "length");
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn sglang_uses_a_hex_id_and_null_delta_fields() {
+    let server = start(Compat::Sglang).await;
+    let (response, chunks) = chat(&server).await;
+    let id = response["id"].as_str().unwrap();
+    assert_eq!(id.len(), 32);
+    assert!(id.chars().all(|c| c.is_ascii_hexdigit()), "{}", id);
+    assert!(has(&response["choices"][0], "matched_stop"));
+
+    assert_eq!(chunks.len(), 5);
+    for chunk in &chunks {
+        assert!(has(chunk, "usage") && chunk["usage"].is_null());
+        let delta = &chunk["choices"][0]["delta"];
+        for field in ["role", "content", "reasoning_content", "tool_calls"] {
+            assert!(has(delta, field), "{}: {}", field, delta);
+        }
+    }
+    let finish = &chunks[4]["choices"][0];
+    assert_eq!(finish["delta"]["content"], Value::Null);
+    assert_eq!(finish["finish_reason"], "length");
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn tgi_has_the_role_in_every_delta_and_no_role_chunk() {
+    let server = start(Compat::Tgi).await;
+    let (response, chunks) = chat(&server).await;
+    assert_eq!(response["id"], "");
+    assert!(response["system_fingerprint"].is_string());
+    assert_eq!(
+        response["choices"][0]["message"].as_object().unwrap().len(),
+        2
+    );
+
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(token_chunks(&chunks).len(), 3);
+    assert!(
+        chunks
+            .iter()
+            .all(|chunk| chunk["choices"][0]["delta"]["role"] == "assistant")
+    );
+    assert_eq!(chunks[2]["choices"][0]["finish_reason"], "length");
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn text_completions_have_no_role_chunk_in_any_backend() {
+    for compat in [Compat::OpenAi, Compat::Vllm, Compat::Sglang, Compat::Tgi] {
+        let server = start(compat).await;
+        let client = Client::new(&server);
+        let body = json!({ "model": "mock-model", "max_tokens": 3, "prompt": "Hello" });
+        let (status, response) = client.post_json("/completions", &body).await;
+        assert_eq!(status, 200);
+        assert_eq!(response["object"], "text_completion", "{:?}", compat);
+        assert!(!common::content(&response).is_empty(), "{:?}", compat);
+        let chunks = client
+            .stream("/completions", &with(body, "stream", true))
+            .await;
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk["object"] == "text_completion")
+        );
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| !has(&chunk["choices"][0], "delta"))
+        );
+        let text: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["text"].as_str())
+            .collect();
+        assert!(!text.is_empty(), "{:?}", compat);
+        server.shutdown().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn profiles_pick_their_own_backend() {
+    let server = MockServer::builder()
+        .model("mock-model")
+        .profile(
+            "tgi-model",
+            Profile {
+                compat: Some(Compat::Tgi),
+                ..Profile::default()
+            },
+        )
+        .start()
+        .await
+        .unwrap();
+    let client = Client::new(&server);
+    let (_, vllm) = client
+        .post_json("/chat/completions", &common::chat("mock-model", 2))
+        .await;
+    let (_, tgi) = client
+        .post_json("/chat/completions", &common::chat("tgi-model", 2))
+        .await;
+    assert!(has(&vllm, "kv_transfer_params"));
+    assert_eq!(tgi["id"], "");
+    assert!(!has(&tgi, "kv_transfer_params"));
+    server.shutdown().await.unwrap();
+}
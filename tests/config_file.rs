@@ -0,0 +1,133 @@
+mod common;
+
+use common::{Client, data_lines, with};
+use mock_openai::MockServer;
+use mock_openai::config;
+use mock_openai::profile::Latency;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn config_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mock-openai-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn model_ids(models: &Value) -> Vec<&str> {
+    models["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|model| model["id"].as_str().unwrap())
+        .collect()
+}
+
+/// Wait for the watcher to pick up a change, which it polls for every second
+async fn wait_for(client: &Client, expected: &[&str]) -> Value {
+    let mut models = Value::Null;
+    for _ in 0..40 {
+        models = client.get("/v1/models").await.1;
+        if model_ids(&models) == expected {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    models
+}
+
+fn write(path: &Path, text: &str) {
+    std::fs::write(path, text).unwrap();
+}
+
+#[test]
+fn toml_yaml_and_json_files_are_read_alike() {
+    let dir = config_path("formats");
+    let files = [
+        (
+            "config.toml",
+            "inter_token_latency = 5\nmodels = [\"a\"]\n\n[reasoning]\ntokens = 7\n\n[server]\nport = 9000\n",
+        ),
+        (
+            "config.yaml",
+            "inter_token_latency: 5\nmodels: [a]\nreasoning:\n  tokens: 7\nserver:\n  port: 9000\n",
+        ),
+        (
+            "config.json",
+            r#"{"inter_token_latency": 5, "models": ["a"], "reasoning": {"tokens": 7}, "server": {"port": 9000}}"#,
+        ),
+    ];
+    for (name, text) in files {
+        let path = dir.join(name);
+        write(&path, text);
+        let (server, config) = config::read_file(&path).unwrap();
+        assert_eq!(server.port, Some(9000), "{}", name);
+        assert_eq!(config.inter_token_latency, Latency::Fixed(5), "{}", name);
+        assert_eq!(config.models, ["a"], "{}", name);
+        assert_eq!(config.reasoning.tokens, 7, "{}", name);
+    }
+
+    let path = dir.join("config.toml");
+    write(&path, "inter_token_latancy = 5\n");
+    let err = config::read_file(&path).unwrap_err();
+    assert!(err.contains("inter_token_latancy"), "{}", err);
+    assert!(config::read_file(&dir.join("config.ini")).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn changes_are_reloaded_and_bad_ones_ignored() {
+    let dir = config_path("reload");
+    let path = dir.join("config.toml");
+    write(&path, "inter_token_latency = 10\nmodels = [\"a\"]\n");
+    let config = config::read_file(&path).unwrap().1;
+    let server = MockServer::builder().config(config).start().await.unwrap();
+    let load = {
+        let path = path.clone();
+        move || config::read_file(&path).map(|(_, config)| config)
+    };
+    config::watch(path.clone(), server.state().config.clone(), load);
+    let client = Client::new(&server);
+    assert_eq!(model_ids(&client.get("/v1/models").await.1), ["a"]);
+
+    // A stream that started before the reload finishes with the old config
+    let running = {
+        let body = with(common::chat("a", 300), "stream", true);
+        let response = client.post("/chat/completions", &body).await;
+        assert_eq!(response.status(), 200);
+        tokio::spawn(response.text())
+    };
+    write(&path, "inter_token_latency = 0\nmodels = [\"b\"]\n");
+    let models = wait_for(&client, &["b"]).await;
+    assert_eq!(model_ids(&models), ["b"]);
+    assert!(!running.is_finished());
+    let (status, _) = client
+        .post_json("/chat/completions", &common::chat("a", 2))
+        .await;
+    assert_eq!(status, 404);
+    let text = running.await.unwrap().unwrap();
+    assert!(text.trim_end().ends_with("data: [DONE]"));
+    // vLLM's role chunk, then one chunk per token
+    assert_eq!(data_lines(&text).len(), 301);
+
+    // Neither a file that doesn't parse nor one that doesn't validate replaces
+    // the config
+    write(&path, "models = [\"c\"\n");
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    write(
+        &path,
+        "models = [\"c\"]\n[[keys]]\nkey = \"\"\nname = \"empty\"\n",
+    );
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(model_ids(&client.get("/v1/models").await.1), ["b"]);
+    assert_eq!(
+        server.state().config.load().config.inter_token_latency,
+        Latency::Fixed(0)
+    );
+
+    // A good file after a bad one is picked up again
+    write(&path, "models = [\"c\"]\n");
+    assert_eq!(model_ids(&wait_for(&client, &["c"]).await), ["c"]);
+    server.shutdown().await.unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
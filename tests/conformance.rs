@@ -0,0 +1,24 @@
+use mock_openai::MockServer;
+use mock_openai::client::Target;
+use mock_openai::compat::Compat;
+use mock_openai::conformance::{self, Status};
+
+#[tokio::test]
+async fn openai_compat_passes_conformance() {
+    let server = MockServer::builder()
+        .compat(Compat::OpenAi)
+        .strict(true)
+        .start()
+        .await
+        .unwrap();
+    let report = conformance::check(&Target::new(server.base_url()), "mock-model", &[], true)
+        .await
+        .unwrap();
+    let failed: Vec<_> = report
+        .checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .collect();
+    assert!(failed.is_empty(), "failed checks: {:#?}", failed);
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use common::Client;
+use mock_openai::MockServer;
+use mock_openai::expect::Expectation;
+use serde_json::{Value, json};
+
+fn hello(stream: bool) -> Value {
+    json!({
+        "model": "x",
+        "max_tokens": 4,
+        "stream": stream,
+        "messages": [{ "role": "user", "content": "Hello" }],
+    })
+}
+
+#[tokio::test]
+async fn met_expectations_verify() {
+    let server = MockServer::builder().model("x").start().await.unwrap();
+    server.expect(
+        Expectation::post("/v1/chat/completions")
+            .model("x")
+            .stream(false)
+            .times(2),
+    );
+    server.expect(Expectation::post("/v1/completions").never());
+    let client = Client::new(&server);
+    client.post("/chat/completions", &hello(false)).await;
+    client.post("/chat/completions", &hello(false)).await;
+    server.verify();
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn unmet_expectations_report_each_request() {
+    let server = MockServer::builder().model("x").start().await.unwrap();
+    server.expect(
+        Expectation::post("/v1/chat/completions")
+            .stream(true)
+            .times(2),
+    );
+    let client = Client::new(&server);
+    client.stream("/chat/completions", &hello(true)).await;
+    client.post("/chat/completions", &hello(false)).await;
+    let report = server.try_verify().unwrap_err();
+    assert!(report.contains("got 1"), "{}", report);
+    assert!(
+        report.contains("#0 POST /v1/chat/completions: matched"),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains("#1 POST /v1/chat/completions: stream: expected true, got false"),
+        "{}",
+        report
+    );
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+#[should_panic(expected = "Unmet expectations on the mock server")]
+async fn unmet_expectations_panic_on_drop() {
+    let server = MockServer::builder().start().await.unwrap();
+    server.expect(Expectation::post("/v1/chat/completions").at_least(1));
+    drop(server);
+}
+
+#[tokio::test]
+async fn requests_with_a_rejected_key_are_recorded_without_a_body() {
+    let server = MockServer::builder()
+        .model("x")
+        .key("secret")
+        .start()
+        .await
+        .unwrap();
+    let (status, _) = Client::new(&server)
+        .post_json("/chat/completions", &hello(false))
+        .await;
+    assert_eq!(status, 401);
+    let (status, _) = Client::new(&server)
+        .key("wrong")
+        .post_json("/chat/completions", &hello(false))
+        .await;
+    assert_eq!(status, 401);
+    let (status, _) = Client::new(&server)
+        .key("secret")
+        .post_json("/chat/completions", &hello(false))
+        .await;
+    assert_eq!(status, 200);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0].header("authorization"), None);
+    assert_eq!(requests[0].body, Value::Null);
+    assert_eq!(requests[1].header("authorization"), Some("<redacted>"));
+    assert_eq!(requests[1].body, Value::Null);
+    assert_eq!(requests[2].model(), Some("x"));
+
+    // The request without a key is told apart by its headers, the one with a
+    // wrong key by its missing body
+    server.expect(
+        Expectation::post("/v1/chat/completions")
+            .header("authorization", "<redacted>")
+            .times(2),
+    );
+    server.expect(
+        Expectation::post("/v1/chat/completions")
+            .model("x")
+            .times(1),
+    );
+    server.verify();
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::Client;
+use mock_openai::MockServer;
+use mock_openai::health::HealthStatus;
+use serde_json::json;
+use std::time::Duration;
+
+#[tokio::test]
+async fn not_ready_during_warmup() {
+    let server = MockServer::builder()
+        .warmup(Duration::from_millis(300))
+        .start()
+        .await
+        .unwrap();
+    let client = Client::new(&server);
+    let chat = common::chat("mock-model", 2);
+    let (status, body) = client.get("/health/ready").await;
+    assert_eq!(status, 503);
+    assert_eq!(body["status"], "loading");
+    let (status, body) = client.post_json("/chat/completions", &chat).await;
+    assert_eq!(status, 503);
+    assert_eq!(body["error"]["code"], "model_loading");
+    // Alive all along
+    let (status, body) = client.get("/health/live").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["status"], "alive");
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let (status, body) = client.get("/health/ready").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["status"], "ready");
+    assert_eq!(client.post_json("/chat/completions", &chat).await.0, 200);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn readiness_follows_the_health_set() {
+    let server = MockServer::builder().start().await.unwrap();
+    let client = Client::new(&server);
+    let health = &server.state().health;
+    health.set_status(HealthStatus::Degraded);
+    let (status, body) = client.get("/health/ready").await;
+    assert_eq!((status, body["status"].clone()), (200, json!("degraded")));
+    health.set_status(HealthStatus::Unhealthy);
+    let (status, body) = client.get("/health/ready").await;
+    assert_eq!((status, body["status"].clone()), (503, json!("unhealthy")));
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,107 @@
+use mock_openai::AppState;
+use mock_openai::client::Target;
+use mock_openai::config::Config;
+use mock_openai::listen::Bind;
+use mock_openai::profile::Latency;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mock-openai-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The ready line, once the server has written it
+async fn ready(path: &Path) -> Value {
+    for _ in 0..100 {
+        if let Ok(line) = std::fs::read_to_string(path) {
+            return serde_json::from_str(&line).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("no ready file at {}", path.display());
+}
+
+#[test]
+fn binds_parse_addresses_and_socket_paths() {
+    assert_eq!(
+        Bind::from("127.0.0.1:0"),
+        Bind::Tcp("127.0.0.1:0".to_string())
+    );
+    assert_eq!(
+        Bind::from("unix:/tmp/mock.sock"),
+        Bind::Unix(PathBuf::from("/tmp/mock.sock"))
+    );
+    assert_eq!(
+        Bind::from("unix:/tmp/mock.sock").to_string(),
+        "unix:/tmp/mock.sock"
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn listeners_share_one_state_and_report_ready() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let dir = temp_dir("listen");
+    let socket = dir.join("mock.sock");
+    let ready_file = dir.join("ready.json");
+    // Left behind by an earlier run, which binding replaces
+    drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+    let state = AppState::new(Config {
+        inter_token_latency: Latency::Fixed(0),
+        ..Config::default()
+    })
+    .unwrap();
+    let (shutdown, journal) = (state.shutdown.clone(), state.journal.clone());
+    let server = tokio::spawn({
+        let binds = [
+            Bind::from("127.0.0.1:0"),
+            Bind::from("127.0.0.1:0"),
+            Bind::Unix(socket.clone()),
+        ];
+        let ready_file = ready_file.clone();
+        async move { mock_openai::start_server(&binds, None, Some(&ready_file), state).await }
+    });
+
+    let ready = ready(&ready_file).await;
+    assert_eq!(ready["event"], "ready");
+    assert_eq!(ready["pid"], std::process::id());
+    let listeners: Vec<&str> = ready["listeners"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|listener| listener.as_str().unwrap())
+        .collect();
+    assert_eq!(listeners.len(), 3);
+    assert_eq!(listeners[0], format!("http://127.0.0.1:{}", ready["port"]));
+    assert_ne!(listeners[0], listeners[1]);
+    assert_eq!(listeners[2], format!("unix:{}", socket.display()));
+
+    let client = Target::new(listeners[0]).client(1).unwrap();
+    for listener in &listeners[..2] {
+        let response = client
+            .get(format!("{}/v1/models", listener))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    let mut stream = tokio::net::UnixStream::connect(&socket).await.unwrap();
+    stream
+        .write_all(b"GET /v1/models HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    // Every listener records into the same journal
+    assert_eq!(journal.list().len(), 3);
+
+    shutdown.close();
+    server.await.unwrap().unwrap();
+    assert!(!socket.exists(), "the socket is removed on shutdown");
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{Client, content, with};
+use mock_openai::MockServer;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+
+fn chat_logprobs(top_logprobs: usize) -> Value {
+    let body = with(common::chat("mock-model", 20), "seed", 3);
+    let body = with(body, "logprobs", true);
+    with(body, "top_logprobs", top_logprobs)
+}
+
+/// Probabilities of a token's candidates, the emitted token first
+fn probabilities(logprobs: impl Iterator<Item = f64>) -> Vec<f64> {
+    logprobs.map(f64::exp).collect()
+}
+
+fn assert_plausible(probabilities: &[f64]) {
+    assert!(probabilities.iter().all(|p| *p > 0.0 && *p < 1.0));
+    assert!(probabilities.windows(2).all(|pair| pair[0] >= pair[1]));
+    let total: f64 = probabilities.iter().sum();
+    assert!(
+        total <= 1.0 + 1e-6,
+        "{:?} add up to {}",
+        probabilities,
+        total
+    );
+}
+
+#[tokio::test]
+async fn chat_logprobs_cover_every_token() {
+    let server = MockServer::builder().start().await.unwrap();
+    let client = Client::new(&server);
+    let (status, response) = client
+        .post_json("/chat/completions", &chat_logprobs(20))
+        .await;
+    assert_eq!(status, 200);
+    let entries = response["choices"][0]["logprobs"]["content"]
+        .as_array()
+        .unwrap();
+    assert_eq!(entries.len(), 20);
+    let text: String = entries
+        .iter()
+        .map(|entry| entry["token"].as_str().unwrap())
+        .collect();
+    assert_eq!(text, content(&response));
+    for entry in entries {
+        let bytes: Vec<u8> = serde_json::from_value(entry["bytes"].clone()).unwrap();
+        assert_eq!(bytes, entry["token"].as_str().unwrap().as_bytes());
+        let top = entry["top_logprobs"].as_array().unwrap();
+        assert_eq!(top.len(), 20);
+        assert_eq!(top[0]["token"], entry["token"]);
+        assert_eq!(top[0]["logprob"], entry["logprob"]);
+        let distinct: HashSet<&str> = top
+            .iter()
+            .map(|candidate| candidate["token"].as_str().unwrap())
+            .collect();
+        assert_eq!(distinct.len(), top.len());
+        assert_plausible(&probabilities(
+            top.iter()
+                .map(|candidate| candidate["logprob"].as_f64().unwrap()),
+        ));
+    }
+
+    // Streaming spreads the same values over the chunks
+    let chunks = client
+        .stream(
+            "/chat/completions",
+            &with(chat_logprobs(20), "stream", true),
+        )
+        .await;
+    let streamed: Vec<&Value> = chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"][0]["logprobs"]["content"].as_array())
+        .flatten()
+        .collect();
+    assert_eq!(streamed, entries.iter().collect::<Vec<_>>());
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn completions_logprobs_use_the_legacy_format() {
+    let server = MockServer::builder().start().await.unwrap();
+    let body = json!({ "model": "mock-model", "max_tokens": 20, "logprobs": 5, "prompt": "Hello" });
+    let (status, response) = Client::new(&server).post_json("/completions", &body).await;
+    assert_eq!(status, 200);
+    let logprobs = &response["choices"][0]["logprobs"];
+    let tokens = logprobs["tokens"].as_array().unwrap();
+    assert_eq!(tokens.len(), 20);
+    assert_eq!(logprobs["token_logprobs"].as_array().unwrap().len(), 20);
+    let text: String = tokens.iter().map(|token| token.as_str().unwrap()).collect();
+    assert_eq!(text, content(&response));
+    let mut offset = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        let token = token.as_str().unwrap();
+        // Duplicate keys would collapse when parsed, leaving fewer than asked for
+        let top = logprobs["top_logprobs"][i].as_object().unwrap();
+        assert_eq!(top.len(), 5);
+        assert_eq!(top[token], logprobs["token_logprobs"][i]);
+        let mut candidates: Vec<f64> = top.values().map(|lp| lp.as_f64().unwrap()).collect();
+        candidates.sort_by(|a, b| b.total_cmp(a));
+        assert_plausible(&probabilities(candidates.into_iter()));
+        assert_eq!(logprobs["text_offset"][i], offset);
+        offset += token.chars().count();
+    }
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn more_than_twenty_alternatives_are_rejected() {
+    let server = MockServer::builder().start().await.unwrap();
+    let client = Client::new(&server);
+    let (status, body) = client
+        .post_json("/chat/completions", &chat_logprobs(21))
+        .await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["param"], "top_logprobs");
+    assert_eq!(body["error"]["code"], "integer_above_max_value");
+
+    let legacy =
+        json!({ "model": "mock-model", "max_tokens": 2, "logprobs": 21, "prompt": "Hello" });
+    let (status, body) = client.post_json("/completions", &legacy).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["param"], "logprobs");
+    server.shutdown().await.unwrap();
+}
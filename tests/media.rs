@@ -0,0 +1,87 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+mod common;
+
+use common::Client;
+use mock_openai::MockServer;
+use mock_openai::media::MAX_BODY_BYTES;
+use serde_json::{Value, json};
+
+/// A PNG header for a `width` by `height` image, padded out to `len` bytes
+fn png(width: u32, height: u32, len: usize) -> Vec<u8> {
+    let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    bytes.extend_from_slice(&width.to_be_bytes());
+    bytes.extend_from_slice(&height.to_be_bytes());
+    bytes.resize(len, 0);
+    bytes
+}
+
+fn image_request(data_url: String) -> Value {
+    json!({
+        "model": "mock-model",
+        "max_tokens": 1,
+        "messages": [{
+            "role": "user",
+            "content": [
+                { "type": "text", "text": "What is this?" },
+                { "type": "image_url", "image_url": { "url": data_url } },
+            ],
+        }],
+    })
+}
+
+#[tokio::test]
+async fn image_over_two_megabytes_is_accepted() {
+    let server = MockServer::builder().start().await.unwrap();
+    let data = STANDARD.encode(png(1024, 1024, 3 * 1024 * 1024));
+    let (status, body) = Client::new(&server)
+        .post_json(
+            "/chat/completions",
+            &image_request(format!("data:image/png;base64,{}", data)),
+        )
+        .await;
+    assert_eq!(status, 200);
+    // 1024x1024 is scaled to 768x768, four tiles at high detail
+    let prompt_tokens = body["usage"]["prompt_tokens"].as_u64().unwrap();
+    assert!(
+        prompt_tokens > 85 + 4 * 170,
+        "prompt_tokens = {}",
+        prompt_tokens
+    );
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn body_over_the_limit_is_rejected() {
+    let server = MockServer::builder().start().await.unwrap();
+    let data = "A".repeat(MAX_BODY_BYTES);
+    let (status, body) = Client::new(&server)
+        .post_json(
+            "/chat/completions",
+            &image_request(format!("data:image/png;base64,{}", data)),
+        )
+        .await;
+    assert_eq!(status, 413);
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn text_only_model_rejects_images() {
+    let server = MockServer::builder()
+        .text_only_model("mock-model")
+        .start()
+        .await
+        .unwrap();
+    let data = STANDARD.encode(png(64, 64, 64));
+    let (status, body) = Client::new(&server)
+        .post_json(
+            "/chat/completions",
+            &image_request(format!("data:image/png;base64,{}", data)),
+        )
+        .await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["param"], "messages.[0].content.[1].type");
+    assert_eq!(body["error"]["code"], "invalid_content_type");
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::Client;
+use mock_openai::MockServer;
+use serde_json::json;
+
+#[tokio::test]
+async fn servers_listen_on_their_own_ports() {
+    let first = MockServer::builder().start().await.unwrap();
+    let second = MockServer::builder().start().await.unwrap();
+    assert_ne!(first.addr().port(), 0);
+    assert_ne!(first.addr(), second.addr());
+    assert_eq!(
+        first.base_url(),
+        format!("http://127.0.0.1:{}/v1", first.addr().port())
+    );
+    first.shutdown().await.unwrap();
+    second.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn requests_are_listed_until_cleared() {
+    let server = MockServer::builder()
+        .model("x")
+        .model("y")
+        .model("z")
+        .start()
+        .await
+        .unwrap();
+    let client = Client::new(&server);
+    let client = &client;
+    let chat = |model| async move {
+        client
+            .post_json("/chat/completions", &common::chat(model, 4))
+            .await
+            .0
+    };
+    assert_eq!(chat("x").await, 200);
+    assert_eq!(chat("y").await, 200);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/v1/chat/completions");
+    assert_eq!(requests[0].model(), Some("x"));
+    assert_eq!(requests[1].model(), Some("y"));
+    assert_eq!(
+        requests[1].field("/messages/0/content"),
+        Some(&json!("Hello"))
+    );
+    assert_eq!(server.records().len(), 2);
+
+    server.clear_requests();
+    assert!(server.requests().is_empty());
+    assert!(server.records().is_empty());
+    assert_eq!(chat("z").await, 200);
+    assert_eq!(server.requests()[0].model(), Some("z"));
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn models_are_listed() {
+    let server = MockServer::builder()
+        .model("x")
+        .model("y")
+        .start()
+        .await
+        .unwrap();
+    let (status, body) = Client::new(&server).get("/v1/models").await;
+    assert_eq!(status, 200);
+    let ids: Vec<&str> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|model| model["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["x", "y"]);
+    server.shutdown().await.unwrap();
+}
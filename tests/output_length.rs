@@ -0,0 +1,80 @@
+mod common;
+
+use common::{Client, with};
+use mock_openai::MockServer;
+use serde_json::json;
+
+#[tokio::test]
+async fn max_completion_tokens_wins_over_max_tokens() {
+    let server = MockServer::builder().start().await.unwrap();
+    let body = with(common::chat("mock-model", 10), "max_completion_tokens", 3);
+    let (status, body) = Client::new(&server)
+        .post_json("/chat/completions", &body)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(body["usage"]["completion_tokens"], 3);
+    assert_eq!(body["choices"][0]["finish_reason"], "length");
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn zero_limit_is_served_as_one_unless_strict() {
+    let lenient = MockServer::builder().start().await.unwrap();
+    let (status, body) = Client::new(&lenient)
+        .post_json("/chat/completions", &common::chat("mock-model", 0))
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(body["usage"]["completion_tokens"], 1);
+    lenient.shutdown().await.unwrap();
+
+    let strict = MockServer::builder().strict(true).start().await.unwrap();
+    let (status, body) = Client::new(&strict)
+        .post_json("/chat/completions", &common::chat("mock-model", 0))
+        .await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["param"], "max_tokens");
+    assert_eq!(body["error"]["code"], "integer_below_min_value");
+    strict.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn stream_sends_one_chunk_per_token() {
+    let server = MockServer::builder().start().await.unwrap();
+    let client = Client::new(&server);
+    for max_tokens in [1, 2, 5] {
+        let body = with(common::chat("mock-model", max_tokens), "stream", true);
+        let body = with(body, "stream_options", json!({ "include_usage": true }));
+        let chunks = client.stream("/chat/completions", &body).await;
+        let content_chunks = chunks
+            .iter()
+            .filter(|chunk| chunk["choices"][0]["delta"]["content"].is_string())
+            .filter(|chunk| chunk["choices"][0]["delta"]["role"].is_null())
+            .count();
+        assert_eq!(content_chunks, max_tokens);
+        let usage = &chunks.last().unwrap()["usage"];
+        assert_eq!(usage["completion_tokens"], max_tokens);
+    }
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn ignore_eos_false_stops_at_a_sentence_end() {
+    let server = MockServer::builder().start().await.unwrap();
+    let client = Client::new(&server);
+    let body = json!({
+        "model": "mock-model",
+        "max_tokens": 2000,
+        "min_tokens": 5,
+        "ignore_eos": false,
+        "seed": 1,
+        "messages": [{ "role": "user", "content": "Hello" }],
+    });
+    let (status, body) = client.post_json("/chat/completions", &body).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["choices"][0]["finish_reason"], "stop");
+    let completion_tokens = body["usage"]["completion_tokens"].as_u64().unwrap();
+    assert!((5..2000).contains(&completion_tokens));
+    let content = common::content(&body).trim_end();
+    assert!(content.ends_with(['.', '!', '?']), "{:?}", content);
+    server.shutdown().await.unwrap();
+}
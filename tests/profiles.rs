@@ -0,0 +1,197 @@
+mod common;
+
+use common::{Client, content, with};
+use mock_openai::MockServer;
+use mock_openai::config::{Config, Faults};
+use mock_openai::profile::{Latency, Profile};
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+
+fn long_prompt(model: &str, chars: usize) -> Value {
+    json!({
+        "model": model,
+        "max_tokens": 5,
+        "messages": [{ "role": "user", "content": "a".repeat(chars) }],
+    })
+}
+
+async fn start() -> MockServer {
+    MockServer::builder()
+        .model("plain")
+        .profile(
+            "small",
+            Profile {
+                context_length: Some(64),
+                default_max_tokens: Some(3),
+                max_output_tokens: Some(6),
+                tools: Some(false),
+                vision: Some(false),
+                ..Profile::default()
+            },
+        )
+        .profile(
+            "slow",
+            Profile {
+                inter_token_latency: Some(Latency::Fixed(40)),
+                ..Profile::default()
+            },
+        )
+        .profile(
+            "thinker",
+            Profile {
+                reasoning_tokens: Some(4),
+                ..Profile::default()
+            },
+        )
+        .profile(
+            "flaky",
+            Profile {
+                faults: Some(Faults {
+                    error_rate: 1.0,
+                    error_status: 503,
+                    ..Faults::default()
+                }),
+                ..Profile::default()
+            },
+        )
+        .start()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn profiled_models_are_listed_and_others_rejected() {
+    let server = start().await;
+    let client = Client::new(&server);
+    let (_, models) = client.get("/v1/models").await;
+    let mut ids: Vec<&str> = models["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|model| model["id"].as_str().unwrap())
+        .collect();
+    ids.sort();
+    assert_eq!(ids, ["flaky", "plain", "slow", "small", "thinker"]);
+    let (status, body) = client
+        .post_json("/chat/completions", &common::chat("unknown", 2))
+        .await;
+    assert_eq!(status, 404);
+    assert_eq!(body["error"]["code"], "model_not_found");
+    server.shutdown().await.unwrap();
+
+    // With the fallback, unknown models get the top-level settings
+    let server = MockServer::builder()
+        .config(Config {
+            inter_token_latency: Latency::Fixed(0),
+            models: vec!["plain".into()],
+            fallback_to_default: true,
+            ..Config::default()
+        })
+        .start()
+        .await
+        .unwrap();
+    let (status, _) = Client::new(&server)
+        .post_json("/chat/completions", &common::chat("unknown", 2))
+        .await;
+    assert_eq!(status, 200);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn output_length_and_context_follow_the_profile() {
+    let server = start().await;
+    let client = Client::new(&server);
+    let mut body = common::chat("small", 2);
+    body.as_object_mut().unwrap().remove("max_tokens");
+    let (_, response) = client.post_json("/chat/completions", &body).await;
+    assert_eq!(response["usage"]["completion_tokens"], 3);
+    let (_, response) = client
+        .post_json("/chat/completions", &common::chat("small", 50))
+        .await;
+    assert_eq!(response["usage"]["completion_tokens"], 6);
+
+    // 64 tokens of context fit a short prompt but not 400 characters
+    let (status, _) = client
+        .post_json("/chat/completions", &long_prompt("small", 40))
+        .await;
+    assert_eq!(status, 200);
+    let (status, body) = client
+        .post_json("/chat/completions", &long_prompt("small", 400))
+        .await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["code"], "context_length_exceeded");
+    let (status, _) = client
+        .post_json("/chat/completions", &long_prompt("plain", 400))
+        .await;
+    assert_eq!(status, 200);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn unsupported_tools_and_images_are_rejected() {
+    let server = start().await;
+    let client = Client::new(&server);
+    let tools = json!([{ "type": "function", "function": { "name": "f", "parameters": {} } }]);
+    let body = with(common::chat("small", 2), "tools", tools.clone());
+    let (status, response) = client.post_json("/chat/completions", &body).await;
+    assert_eq!(status, 400);
+    assert_eq!(response["error"]["code"], "unsupported_parameter");
+    let body = with(common::chat("plain", 2), "tools", tools);
+    assert_eq!(client.post_json("/chat/completions", &body).await.0, 200);
+
+    let image = json!({
+        "model": "small",
+        "max_tokens": 2,
+        "messages": [{
+            "role": "user",
+            "content": [{ "type": "image_url", "image_url": { "url": "https://example.com/cat.png" } }],
+        }],
+    });
+    let (status, response) = client.post_json("/chat/completions", &image).await;
+    assert_eq!(status, 400);
+    assert_eq!(response["error"]["code"], "invalid_content_type");
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn latency_reasoning_and_faults_are_per_model() {
+    let server = start().await;
+    let client = Client::new(&server);
+    let started = Instant::now();
+    client
+        .stream(
+            "/chat/completions",
+            &with(common::chat("plain", 5), "stream", true),
+        )
+        .await;
+    let fast = started.elapsed();
+    let started = Instant::now();
+    client
+        .stream(
+            "/chat/completions",
+            &with(common::chat("slow", 5), "stream", true),
+        )
+        .await;
+    let slow = started.elapsed();
+    assert!(slow >= Duration::from_millis(160), "{:?}", slow);
+    assert!(fast < slow, "{:?} vs {:?}", fast, slow);
+
+    let (_, response) = client
+        .post_json("/chat/completions", &common::chat("thinker", 10))
+        .await;
+    assert_eq!(
+        response["usage"]["completion_tokens_details"]["reasoning_tokens"],
+        4
+    );
+    assert!(!content(&response).is_empty());
+
+    let (status, _) = client
+        .post_json("/chat/completions", &common::chat("flaky", 2))
+        .await;
+    assert_eq!(status, 503);
+    let (status, _) = client
+        .post_json("/chat/completions", &common::chat("plain", 2))
+        .await;
+    assert_eq!(status, 200);
+    server.shutdown().await.unwrap();
+}
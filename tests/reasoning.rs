@@ -0,0 +1,140 @@
+mod common;
+
+use common::{Client, content, with};
+use mock_openai::MockServer;
+use mock_openai::compat::Compat;
+use mock_openai::config::Config;
+use mock_openai::profile::Latency;
+use mock_openai::reasoning::{ReasoningConfig, ReasoningField};
+use serde_json::Value;
+
+async fn start(compat: Compat, field: ReasoningField) -> MockServer {
+    let config = Config {
+        inter_token_latency: Latency::Fixed(0),
+        compat,
+        models: vec!["thinker".into(), "plain".into()],
+        reasoning: ReasoningConfig {
+            models: vec!["thinker".into()],
+            tokens: 8,
+            field,
+        },
+        ..Config::default()
+    };
+    MockServer::builder().config(config).start().await.unwrap()
+}
+
+fn reasoning_tokens(response: &Value) -> u64 {
+    response["usage"]["completion_tokens_details"]["reasoning_tokens"]
+        .as_u64()
+        .unwrap_or(0)
+}
+
+#[tokio::test]
+async fn reasoning_comes_before_the_answer_and_counts_towards_the_limit() {
+    let server = start(Compat::Vllm, ReasoningField::ReasoningContent).await;
+    let client = Client::new(&server);
+    let (status, response) = client
+        .post_json("/chat/completions", &common::chat("thinker", 20))
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(response["usage"]["completion_tokens"], 20);
+    assert_eq!(reasoning_tokens(&response), 8);
+    let message = &response["choices"][0]["message"];
+    assert!(!message["reasoning_content"].as_str().unwrap().is_empty());
+    assert!(!content(&response).is_empty());
+
+    let body = with(common::chat("thinker", 20), "stream", true);
+    let body = with(
+        body,
+        "stream_options",
+        serde_json::json!({ "include_usage": true }),
+    );
+    let chunks = client.stream("/chat/completions", &body).await;
+    let deltas: Vec<&Value> = chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"].get(0))
+        .map(|choice| &choice["delta"])
+        .collect();
+    let kinds: Vec<&str> = deltas
+        .iter()
+        .filter_map(|delta| {
+            if delta["reasoning_content"]
+                .as_str()
+                .is_some_and(|s| !s.is_empty())
+            {
+                Some("reasoning")
+            } else if delta["content"].as_str().is_some_and(|s| !s.is_empty()) {
+                Some("content")
+            } else {
+                None
+            }
+        })
+        .collect();
+    assert_eq!(kinds.iter().filter(|kind| **kind == "reasoning").count(), 8);
+    assert_eq!(kinds.iter().filter(|kind| **kind == "content").count(), 12);
+    assert!(kinds[..8].iter().all(|kind| *kind == "reasoning"));
+    let usage = chunks.last().unwrap();
+    assert_eq!(reasoning_tokens(usage), 8);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn effort_scales_the_budget_for_any_model() {
+    let server = start(Compat::Vllm, ReasoningField::Reasoning).await;
+    let client = Client::new(&server);
+    for (model, effort, expected) in [
+        ("plain", None, 0),
+        ("plain", Some("low"), 4),
+        ("plain", Some("high"), 16),
+        ("thinker", Some("none"), 0),
+        ("thinker", Some("minimal"), 2),
+    ] {
+        let mut body = common::chat(model, 40);
+        if let Some(effort) = effort {
+            body = with(body, "reasoning_effort", effort);
+        }
+        let (status, response) = client.post_json("/chat/completions", &body).await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            reasoning_tokens(&response),
+            expected,
+            "{} {:?}",
+            model,
+            effort
+        );
+        let message = &response["choices"][0]["message"];
+        assert_eq!(
+            message["reasoning"].as_str().is_some_and(|s| !s.is_empty()),
+            expected > 0,
+            "{}",
+            message
+        );
+    }
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn a_short_limit_keeps_one_answer_token() {
+    let server = start(Compat::Vllm, ReasoningField::ReasoningContent).await;
+    let (status, response) = Client::new(&server)
+        .post_json("/chat/completions", &common::chat("thinker", 4))
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(response["usage"]["completion_tokens"], 4);
+    assert_eq!(reasoning_tokens(&response), 3);
+    assert!(!content(&response).is_empty());
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn openai_compat_leaves_the_reasoning_out() {
+    let server = start(Compat::OpenAi, ReasoningField::ReasoningContent).await;
+    let (status, response) = Client::new(&server)
+        .post_json("/chat/completions", &common::chat("thinker", 20))
+        .await;
+    assert_eq!(status, 200);
+    let message = response["choices"][0]["message"].as_object().unwrap();
+    assert!(!message.contains_key("reasoning_content"), "{:?}", message);
+    assert_eq!(reasoning_tokens(&response), 8);
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,98 @@
+use axum::body::Body;
+use axum::http::Request;
+use mock_openai::auth::KeyConfig;
+use mock_openai::config::Config;
+use mock_openai::profile::Latency;
+use mock_openai::request_log::RequestLogger;
+use mock_openai::routes::AppState;
+use serde_json::Value;
+use std::path::PathBuf;
+use tower::ServiceExt;
+
+fn key(secret: &str, requests_per_minute: Option<u64>) -> KeyConfig {
+    KeyConfig {
+        key: secret.to_string(),
+        name: secret.to_string(),
+        models: Vec::new(),
+        requests_per_minute,
+        tokens_per_minute: None,
+        token_quota: None,
+    }
+}
+
+fn chat_request(token: Option<&str>, body: &str) -> Request<Body> {
+    let mut request =
+        Request::post("/v1/chat/completions").header("content-type", "application/json");
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {}", token));
+    }
+    request.body(Body::from(body.to_string())).unwrap()
+}
+
+fn log_path(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("mock-openai-{}-{}.jsonl", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[tokio::test]
+async fn rejected_requests_are_logged() {
+    let path = log_path("rejected");
+    let config = Config {
+        inter_token_latency: Latency::Fixed(0),
+        keys: vec![key("limited", Some(1))],
+        ..Config::default()
+    };
+    let mut state = AppState::new(config).unwrap();
+    let logger = RequestLogger::open(&path).await.unwrap();
+    state.request_log = Some(logger.clone());
+    let app = mock_openai::router(state);
+
+    let chat = r#"{"model":"m","max_tokens":2,"messages":[{"role":"user","content":"Hi"}]}"#;
+    let requests = [
+        chat_request(None, chat),
+        chat_request(Some("wrong"), chat),
+        chat_request(Some("limited"), "{not json"),
+        chat_request(Some("limited"), chat),
+        chat_request(Some("limited"), chat),
+    ];
+    let mut statuses = Vec::new();
+    for request in requests {
+        let response = app.clone().oneshot(request).await.unwrap();
+        statuses.push(response.status().as_u16());
+    }
+    assert_eq!(statuses, [401, 401, 400, 200, 429]);
+
+    logger.flush().await;
+    let records: Vec<Value> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let logged: Vec<(u64, &str)> = records
+        .iter()
+        .map(|record| {
+            (
+                record["status"].as_u64().unwrap(),
+                record["key"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        logged,
+        [
+            (401, "unauthenticated"),
+            (401, "unauthenticated"),
+            (400, "limited"),
+            (200, "limited"),
+            (429, "limited"),
+        ]
+    );
+    assert!(
+        records
+            .iter()
+            .all(|record| record["route"] == "/v1/chat/completions")
+    );
+    std::fs::remove_file(&path).unwrap();
+}
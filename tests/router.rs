@@ -0,0 +1,66 @@
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::Request;
+use mock_openai::config::Config;
+use mock_openai::profile::Latency;
+use serde_json::Value;
+use tower::ServiceExt;
+
+fn router() -> Router {
+    let config = Config {
+        inter_token_latency: Latency::Fixed(0),
+        ..Config::default()
+    };
+    mock_openai::build_router(config).expect("default config is valid")
+}
+
+fn chat_request(stream: bool) -> Request<Body> {
+    let body = format!(
+        r#"{{"model":"test","stream":{},"max_tokens":5,"messages":[{{"role":"user","content":"Hello"}}]}}"#,
+        stream
+    );
+    Request::post("/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn call(request: Request<Body>) -> (u16, String) {
+    let response = router().oneshot(request).await.unwrap();
+    let status = response.status().as_u16();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn chat_completion_in_process() {
+    let (status, body) = call(chat_request(false)).await;
+    assert_eq!(status, 200);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["object"], "chat.completion");
+    assert_eq!(body["usage"]["completion_tokens"], 5);
+}
+
+#[tokio::test]
+async fn chat_completion_stream_in_process() {
+    let (status, body) = call(chat_request(true)).await;
+    assert_eq!(status, 200);
+    let chunks: Vec<&str> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .collect();
+    assert_eq!(chunks.last(), Some(&"[DONE]"));
+    for chunk in &chunks[..chunks.len() - 1] {
+        let chunk: Value = serde_json::from_str(chunk).unwrap();
+        assert_eq!(chunk["object"], "chat.completion.chunk");
+    }
+}
+
+#[tokio::test]
+async fn unknown_path_in_process() {
+    let request = Request::get("/v1/nope").body(Body::empty()).unwrap();
+    let (status, body) = call(request).await;
+    assert_eq!(status, 404);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{Client, content};
+use mock_openai::MockServer;
+use mock_openai::corpus::OffsetMode;
+use serde_json::Value;
+
+async fn chat(server: &MockServer, seed: Option<i64>) -> Value {
+    let mut body = common::chat("mock-model", 20);
+    if let Some(seed) = seed {
+        body["seed"] = seed.into();
+    }
+    let (status, body) = Client::new(server)
+        .post_json("/chat/completions", &body)
+        .await;
+    assert_eq!(status, 200);
+    body
+}
+
+#[tokio::test]
+async fn same_seed_gives_same_output() {
+    let server = MockServer::builder().start().await.unwrap();
+    let first = chat(&server, Some(7)).await;
+    let second = chat(&server, Some(7)).await;
+    let other = chat(&server, Some(8)).await;
+    assert_eq!(content(&first), content(&second));
+    assert_ne!(content(&first), content(&other));
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn seed_gives_same_output_across_servers() {
+    let first = MockServer::builder().start().await.unwrap();
+    let second = MockServer::builder()
+        .offset_mode(OffsetMode::Zero)
+        .start()
+        .await
+        .unwrap();
+    let a = chat(&first, Some(42)).await;
+    let b = chat(&second, Some(42)).await;
+    assert_eq!(content(&a), content(&b));
+    first.shutdown().await.unwrap();
+    second.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn offset_modes_without_a_seed() {
+    let zero = MockServer::builder()
+        .offset_mode(OffsetMode::Zero)
+        .start()
+        .await
+        .unwrap();
+    let from_start = content(&chat(&zero, None).await).to_string();
+    let corpus_start: String = mock_openai::generated::TOKENIZED_OUTPUT[..20]
+        .iter()
+        .map(|token| serde_json::from_str::<String>(token).unwrap())
+        .collect();
+    assert_eq!(from_start, corpus_start);
+    assert_eq!(content(&chat(&zero, None).await), from_start);
+
+    let random = MockServer::builder()
+        .offset_mode(OffsetMode::Random)
+        .start()
+        .await
+        .unwrap();
+    let outputs: Vec<String> = futures_util::future::join_all((0..5).map(|_| chat(&random, None)))
+        .await
+        .iter()
+        .map(|body| content(body).to_string())
+        .collect();
+    assert!(outputs.iter().any(|output| *output != outputs[0]));
+    zero.shutdown().await.unwrap();
+    random.shutdown().await.unwrap();
+}
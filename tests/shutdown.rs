@@ -0,0 +1,50 @@
+mod common;
+
+use common::Client;
+use mock_openai::MockServer;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Start a stream of `max_tokens` chunks and wait for the response headers,
+/// returning a task with the whole body
+async fn start_stream(server: &MockServer, max_tokens: usize) -> JoinHandle<String> {
+    let body = common::with(common::chat("x", max_tokens), "stream", true);
+    let response = Client::new(server).post("/chat/completions", &body).await;
+    assert_eq!(response.status(), 200);
+    tokio::spawn(async move { response.text().await.unwrap() })
+}
+
+#[tokio::test]
+async fn shutdown_lets_running_streams_finish() {
+    let server = MockServer::builder()
+        .model("x")
+        .itl(10)
+        .drain_timeout(Duration::from_secs(5))
+        .start()
+        .await
+        .unwrap();
+    let body = start_stream(&server, 20).await;
+    server.shutdown().await.unwrap();
+    let body = body.await.unwrap();
+    // The role chunk, then one per token
+    assert_eq!(common::data_lines(&body).len(), 21);
+    assert!(body.ends_with("data: [DONE]\n\n"), "{}", body);
+    assert!(!body.contains("server_shutting_down"));
+}
+
+#[tokio::test]
+async fn shutdown_ends_streams_after_the_drain_timeout() {
+    let server = MockServer::builder()
+        .model("x")
+        .itl(50)
+        .drain_timeout(Duration::from_millis(100))
+        .start()
+        .await
+        .unwrap();
+    let body = start_stream(&server, 1000).await;
+    let started = std::time::Instant::now();
+    server.shutdown().await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2));
+    let body = body.await.unwrap();
+    assert!(body.contains("server_shutting_down"), "{}", body);
+}
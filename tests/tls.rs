@@ -0,0 +1,86 @@
+use mock_openai::client::Target;
+use mock_openai::config::Config;
+use mock_openai::listen::Bind;
+use mock_openai::profile::Latency;
+use mock_openai::{AppState, tls};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mock-openai-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The ready line, once the server has written it
+async fn ready(path: &Path) -> Value {
+    for _ in 0..100 {
+        if let Ok(line) = std::fs::read_to_string(path) {
+            return serde_json::from_str(&line).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("no ready file at {}", path.display());
+}
+
+#[tokio::test]
+async fn self_signed_certificate_is_trusted_through_the_ca_file() {
+    let dir = temp_dir("tls");
+    let ca_file = dir.join("ca.pem");
+    let ready_file = dir.join("ready.json");
+    let server_config = tls::self_signed(vec!["localhost".into()], &ca_file).unwrap();
+    let state = AppState::new(Config {
+        inter_token_latency: Latency::Fixed(0),
+        ..Config::default()
+    })
+    .unwrap();
+    let shutdown = state.shutdown.clone();
+    let server = tokio::spawn({
+        let ready_file = ready_file.clone();
+        async move {
+            let binds = [Bind::from("127.0.0.1:0")];
+            mock_openai::start_server(&binds, Some(server_config), Some(&ready_file), state).await
+        }
+    });
+
+    let ready = ready(&ready_file).await;
+    let port = ready["port"].as_u64().unwrap();
+    assert_eq!(ready["listeners"][0], format!("https://127.0.0.1:{}", port));
+    let base = format!("https://localhost:{}/v1", port);
+
+    let mut target = Target::new(&base);
+    target.cacert = Some(ca_file.clone());
+    let client = target.client(1).unwrap();
+    let response = target.get(&client, "/models").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    // HTTP/2 is negotiated through ALPN
+    assert_eq!(response.version(), reqwest::Version::HTTP_2);
+
+    // Clients that don't trust the CA are turned away
+    let untrusted = Target::new(&base).client(1).unwrap();
+    assert!(
+        untrusted
+            .get(format!("{}/models", base))
+            .send()
+            .await
+            .is_err()
+    );
+    // And plain HTTP gets nowhere
+    let plain = format!("http://127.0.0.1:{}/v1/models", port);
+    assert!(client.get(plain).send().await.is_err());
+
+    shutdown.close();
+    server.await.unwrap().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unreadable_certificates_are_reported() {
+    let dir = temp_dir("tls-invalid");
+    let cert = dir.join("cert.pem");
+    std::fs::write(&cert, "not a certificate").unwrap();
+    let err = tls::load(&cert, &dir.join("missing.pem")).unwrap_err();
+    assert!(err.to_string().contains("cert.pem"), "{}", err);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use common::{Client, with};
+use mock_openai::MockServer;
+use serde_json::{Value, json};
+
+fn ids(value: &Value) -> Vec<u64> {
+    serde_json::from_value(value.clone()).unwrap()
+}
+
+#[tokio::test]
+async fn ids_match_the_usage_counts() {
+    let server = MockServer::builder().start().await.unwrap();
+    let client = Client::new(&server);
+    let body = with(common::chat("mock-model", 12), "return_token_ids", true);
+    let (status, response) = client.post_json("/chat/completions", &body).await;
+    assert_eq!(status, 200);
+    let usage = &response["usage"];
+    let output = ids(&response["choices"][0]["token_ids"]);
+    assert_eq!(output.len() as u64, usage["completion_tokens"]);
+    let prompt = ids(&response["prompt_token_ids"]);
+    assert_eq!(prompt.len() as u64, usage["prompt_tokens"]);
+
+    // The same prompt gets the same ids, and streaming spreads the output ids
+    // over the chunks
+    let chunks = client
+        .stream("/chat/completions", &with(body, "stream", true))
+        .await;
+    assert_eq!(ids(&chunks[0]["prompt_token_ids"]), prompt);
+    let streamed: Vec<u64> = chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"][0]["token_ids"].as_array())
+        .flatten()
+        .map(|id| id.as_u64().unwrap())
+        .collect();
+    assert_eq!(streamed.len(), output.len());
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn images_get_placeholder_prompt_ids() {
+    let server = MockServer::builder().start().await.unwrap();
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    png.extend_from_slice(&512u32.to_be_bytes());
+    png.extend_from_slice(&512u32.to_be_bytes());
+    png.resize(64, 0);
+    let body = json!({
+        "model": "mock-model",
+        "max_tokens": 1,
+        "return_token_ids": true,
+        "messages": [{
+            "role": "user",
+            "content": [
+                { "type": "text", "text": "What is this?" },
+                {
+                    "type": "image_url",
+                    "image_url": { "url": format!("data:image/png;base64,{}", STANDARD.encode(png)) },
+                },
+            ],
+        }],
+    });
+    let (status, response) = Client::new(&server)
+        .post_json("/chat/completions", &body)
+        .await;
+    assert_eq!(status, 200);
+    let prompt_tokens = response["usage"]["prompt_tokens"].as_u64().unwrap();
+    assert!(prompt_tokens > 85, "prompt_tokens = {}", prompt_tokens);
+    assert_eq!(
+        ids(&response["prompt_token_ids"]).len() as u64,
+        prompt_tokens
+    );
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn legacy_token_id_prompts_come_back_as_they_are() {
+    let server = MockServer::builder().start().await.unwrap();
+    let body = json!({
+        "model": "mock-model",
+        "max_tokens": 4,
+        "return_token_ids": true,
+        "prompt": [1, 2, 3, 50_000],
+    });
+    let (status, response) = Client::new(&server).post_json("/completions", &body).await;
+    assert_eq!(status, 200);
+    let choice = &response["choices"][0];
+    assert_eq!(ids(&choice["prompt_token_ids"]), [1, 2, 3, 50_000]);
+    assert_eq!(ids(&choice["token_ids"]).len(), 4);
+    server.shutdown().await.unwrap();
+}